@@ -23,15 +23,23 @@ use pathfinder_geometry::dash::OutlineDash;
 use pathfinder_geometry::outline::{ArcDirection, Contour, Outline};
 use pathfinder_geometry::stroke::{LineCap, LineJoin as StrokeLineJoin};
 use pathfinder_geometry::stroke::{OutlineStrokeToFill, StrokeStyle};
-use pathfinder_renderer::paint::{Paint, PaintId};
-use pathfinder_renderer::scene::{PathObject, Scene};
+use pathfinder_renderer::paint::{BlendMode, ColorStop, Gradient, Paint, PaintId};
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, Layer, PathObject, Scene};
 use pathfinder_text::{SceneExt, TextRenderMode};
 use skribo::{FontCollection, FontFamily, Layout, TextStyle};
 use std::default::Default;
+use std::error::Error as StdError;
 use std::f32::consts::PI;
+use std::fmt::{self, Display, Formatter};
 use std::mem;
 use std::sync::Arc;
 
+pub use crate::path_data::{ParseError, ParseErrorKind};
+pub use pathfinder_geometry::fill::FillRule;
+pub use pathfinder_renderer::paint::{Image, Pattern};
+
+mod path_data;
+
 const HAIRLINE_STROKE_WIDTH: f32 = 0.0333;
 const DEFAULT_FONT_SIZE: f32 = 10.0;
 
@@ -81,15 +89,40 @@ impl CanvasRenderingContext2D {
         self.stroke_path(path);
     }
 
+    // Drawing images
+
+    /// Draws the image scaled to fill `dest_rect`.
+    pub fn draw_image(&mut self, image: &Image, dest_rect: RectF) {
+        let image_size = image.size().to_f32();
+        let scale = Vector2F::new(dest_rect.size().x() / image_size.x(),
+                                  dest_rect.size().y() / image_size.y());
+        let mut pattern = Pattern::new(image.clone());
+        pattern.transform = Transform2DF::from_scale(scale)
+            .post_mul(&Transform2DF::from_translation(dest_rect.origin()));
+        pattern.repeat_x = false;
+        pattern.repeat_y = false;
+
+        let mut path = Path2D::new();
+        path.rect(dest_rect);
+        let mut outline = path.into_outline();
+        outline.transform(&self.current_state.transform);
+
+        let paint = self.current_state.resolve_paint(&Paint::Pattern(pattern));
+        let paint_id = self.scene.push_paint(&paint);
+        self.push_path(outline, paint_id, FillRule::Winding);
+    }
+
     // Drawing text
 
     pub fn fill_text(&mut self, string: &str, position: Vector2F) {
-        let paint_id = self.scene.push_paint(&self.current_state.fill_paint);
+        let paint = self.current_state.resolve_paint(&self.current_state.fill_paint);
+        let paint_id = self.scene.push_paint(&paint);
         self.fill_or_stroke_text(string, position, paint_id, TextRenderMode::Fill);
     }
 
     pub fn stroke_text(&mut self, string: &str, position: Vector2F) {
-        let paint_id = self.scene.push_paint(&self.current_state.stroke_paint);
+        let paint = self.current_state.resolve_paint(&self.current_state.stroke_paint);
+        let paint_id = self.scene.push_paint(&paint);
         let render_mode = TextRenderMode::Stroke(self.current_state.resolve_stroke_style());
         self.fill_or_stroke_text(string, position, paint_id, render_mode);
     }
@@ -114,13 +147,32 @@ impl CanvasRenderingContext2D {
         let transform = Transform2DF::from_translation(position).post_mul(&self.current_state
                                                                                .transform);
 
+        // The text is composited as a whole, like any other shape.
+        let blend_mode = self.current_state.global_composite_operation.to_blend_mode();
+        if blend_mode.clears_backdrop() {
+            self.push_composite_layer(blend_mode);
+        }
+
         // TODO(pcwalton): Report errors.
+        let first_path_index = self.scene.path_count();
         drop(self.scene.push_layout(&layout,
                                     &TextStyle { size: self.current_state.font_size },
                                     &transform,
                                     render_mode,
                                     HintingOptions::None,
                                     paint_id));
+
+        // The glyphs are pushed unclipped and with source-over, so fix them up afterward.
+        if blend_mode.clears_backdrop() {
+            self.scene.pop_layer();
+        } else if self.current_state.clip_path.is_some() || !blend_mode.is_source_over() {
+            for path_index in first_path_index..self.scene.path_count() {
+                let mut path = self.scene.path(path_index).clone();
+                path.set_clip_path(self.current_state.clip_path);
+                path.set_blend_mode(blend_mode);
+                self.scene.replace_path(path_index, path);
+            }
+        }
     }
 
     fn layout_text(&self, string: &str) -> Layout {
@@ -192,11 +244,41 @@ impl CanvasRenderingContext2D {
         self.current_state.stroke_paint = new_stroke_style.to_paint();
     }
 
+    #[inline]
+    pub fn create_pattern(&self, image: &Image, repetition: PatternRepetition) -> Pattern {
+        let mut pattern = Pattern::new(image.clone());
+        pattern.repeat_x = match repetition {
+            PatternRepetition::Repeat | PatternRepetition::RepeatX => true,
+            PatternRepetition::RepeatY | PatternRepetition::NoRepeat => false,
+        };
+        pattern.repeat_y = match repetition {
+            PatternRepetition::Repeat | PatternRepetition::RepeatY => true,
+            PatternRepetition::RepeatX | PatternRepetition::NoRepeat => false,
+        };
+        pattern
+    }
+
+    #[inline]
+    pub fn create_linear_gradient(&self, line: LineSegment2F) -> CanvasGradient {
+        CanvasGradient { gradient: Gradient::linear(line) }
+    }
+
+    #[inline]
+    pub fn create_radial_gradient(&self, line: LineSegment2F, start_radius: f32, end_radius: f32)
+                                  -> CanvasGradient {
+        CanvasGradient { gradient: Gradient::radial(line, start_radius, end_radius) }
+    }
+
+    #[inline]
+    pub fn create_conic_gradient(&self, start_angle: f32, center: Vector2F) -> CanvasGradient {
+        CanvasGradient { gradient: Gradient::conic(center, start_angle) }
+    }
+
     // Shadows
 
     #[inline]
     pub fn set_shadow_color(&mut self, new_shadow_color: ColorU) {
-        self.current_state.shadow_paint = Paint { color: new_shadow_color };
+        self.current_state.shadow_paint = Paint::Color(new_shadow_color);
     }
 
     #[inline]
@@ -208,20 +290,44 @@ impl CanvasRenderingContext2D {
 
     #[inline]
     pub fn fill_path(&mut self, path: Path2D) {
+        self.fill_path_with_rule(path, FillRule::Winding)
+    }
+
+    pub fn fill_path_with_rule(&mut self, path: Path2D, fill_rule: FillRule) {
         let mut outline = path.into_outline();
         outline.transform(&self.current_state.transform);
 
-        let paint = self.current_state.resolve_paint(self.current_state.fill_paint);
+        let paint = self.current_state.resolve_paint(&self.current_state.fill_paint);
         let paint_id = self.scene.push_paint(&paint);
 
-        self.push_path(outline, paint_id);
+        self.push_path(outline, paint_id, fill_rule);
     }
 
     #[inline]
     pub fn stroke_path(&mut self, path: Path2D) {
-        let paint = self.current_state.resolve_paint(self.current_state.stroke_paint);
+        let paint = self.current_state.resolve_paint(&self.current_state.stroke_paint);
         let paint_id = self.scene.push_paint(&paint);
 
+        let outline = self.stroke_outline(path);
+        self.push_path(outline, paint_id, FillRule::Winding);
+    }
+
+    /// Returns true if filling `path` in the current transform would cover `point`, which is in
+    /// canvas coordinates.
+    pub fn is_point_in_path(&self, path: &Path2D, point: Vector2F, fill_rule: FillRule) -> bool {
+        let mut outline = path.clone().into_outline();
+        outline.transform(&self.current_state.transform);
+        outline.contains_point(point, fill_rule)
+    }
+
+    /// Returns true if stroking `path` with the current line styles and transform would cover
+    /// `point`, which is in canvas coordinates.
+    pub fn is_point_in_stroke(&self, path: &Path2D, point: Vector2F) -> bool {
+        self.stroke_outline(path.clone()).contains_point(point, FillRule::Winding)
+    }
+
+    // Returns the outline that stroking the path fills, in canvas coordinates.
+    fn stroke_outline(&self, path: Path2D) -> Outline {
         let mut stroke_style = self.current_state.resolve_stroke_style();
         stroke_style.line_width = f32::max(stroke_style.line_width, HAIRLINE_STROKE_WIDTH);
 
@@ -239,20 +345,62 @@ impl CanvasRenderingContext2D {
         outline = stroke_to_fill.into_outline();
 
         outline.transform(&self.current_state.transform);
-        self.push_path(outline, paint_id);
+        outline
     }
 
-    fn push_path(&mut self, outline: Outline, paint_id: PaintId) {
+    fn push_path(&mut self, outline: Outline, paint_id: PaintId, fill_rule: FillRule) {
         if !self.current_state.shadow_paint.is_fully_transparent() {
-            let paint = self.current_state.resolve_paint(self.current_state.shadow_paint);
+            let paint = self.current_state.resolve_paint(&self.current_state.shadow_paint);
             let paint_id = self.scene.push_paint(&paint);
 
             let mut outline = outline.clone();
             outline.transform(&Transform2DF::from_translation(self.current_state.shadow_offset));
-            self.scene.push_path(PathObject::new(outline, paint_id, String::new()))
+            let mut path = PathObject::new(outline, paint_id, String::new());
+            path.set_fill_rule(fill_rule);
+            self.push_composited_path(path);
         }
 
-        self.scene.push_path(PathObject::new(outline, paint_id, String::new()))
+        let mut path = PathObject::new(outline, paint_id, String::new());
+        path.set_fill_rule(fill_rule);
+        self.push_composited_path(path)
+    }
+
+    // Canvas composites each shape with the whole canvas, within the clip region. Only the modes
+    // that clear the backdrop can tell the difference, so the shapes drawn with them get a layer
+    // of their own, which is composited over the whole view box.
+    fn push_composited_path(&mut self, mut path: PathObject) {
+        let blend_mode = self.current_state.global_composite_operation.to_blend_mode();
+        if blend_mode.clears_backdrop() {
+            self.push_composite_layer(blend_mode);
+            self.scene.push_path(path);
+            self.scene.pop_layer();
+        } else {
+            path.set_clip_path(self.current_state.clip_path);
+            path.set_blend_mode(blend_mode);
+            self.scene.push_path(path);
+        }
+    }
+
+    fn push_composite_layer(&mut self, blend_mode: BlendMode) {
+        let mut layer = Layer::new(String::new());
+        layer.set_blend_mode(blend_mode);
+        layer.set_clip_path(self.current_state.clip_path);
+        self.scene.push_layer(layer);
+    }
+
+    // Clipping
+
+    /// Intersects the clip region with the interior of `path`, in the current transform.
+    ///
+    /// The clip region is part of the canvas state, so `restore()` undoes this.
+    pub fn clip_path(&mut self, path: Path2D, fill_rule: FillRule) {
+        let mut outline = path.into_outline();
+        outline.transform(&self.current_state.transform);
+
+        let mut clip_path = ClipPath::new(outline, String::new());
+        clip_path.set_fill_rule(fill_rule);
+        clip_path.set_clip_path(self.current_state.clip_path);
+        self.current_state.clip_path = Some(self.scene.push_clip_path(clip_path));
     }
 
     // Transformations
@@ -284,6 +432,16 @@ impl CanvasRenderingContext2D {
         self.current_state.global_alpha = new_global_alpha;
     }
 
+    #[inline]
+    pub fn global_composite_operation(&self) -> CompositeOperation {
+        self.current_state.global_composite_operation
+    }
+
+    #[inline]
+    pub fn set_global_composite_operation(&mut self, new_composite_operation: CompositeOperation) {
+        self.current_state.global_composite_operation = new_composite_operation;
+    }
+
     // The canvas state
 
     #[inline]
@@ -316,6 +474,8 @@ struct State {
     shadow_offset: Vector2F,
     text_align: TextAlign,
     global_alpha: f32,
+    global_composite_operation: CompositeOperation,
+    clip_path: Option<ClipPathId>,
 }
 
 impl State {
@@ -330,18 +490,47 @@ impl State {
             miter_limit: 10.0,
             line_dash: vec![],
             line_dash_offset: 0.0,
-            fill_paint: Paint { color: ColorU::black() },
-            stroke_paint: Paint { color: ColorU::black() },
-            shadow_paint: Paint { color: ColorU::transparent_black() },
+            fill_paint: Paint::Color(ColorU::black()),
+            stroke_paint: Paint::Color(ColorU::black()),
+            shadow_paint: Paint::Color(ColorU::transparent_black()),
             shadow_offset: Vector2F::default(),
             text_align: TextAlign::Left,
             global_alpha: 1.0,
+            global_composite_operation: CompositeOperation::SourceOver,
+            clip_path: None,
         }
     }
 
-    fn resolve_paint(&self, mut paint: Paint) -> Paint {
-        paint.color.a = (paint.color.a as f32 * self.global_alpha).round() as u8;
-        paint
+    fn resolve_paint(&self, paint: &Paint) -> Paint {
+        match *paint {
+            Paint::Color(color) => Paint::Color(self.resolve_color(color)),
+            Paint::Gradient(ref gradient) => {
+                let mut resolved_gradient = Gradient::new(gradient.geometry);
+                // Like patterns, gradients are specified in the coordinate space current at the
+                // time of drawing.
+                resolved_gradient.spread = gradient.spread;
+                resolved_gradient.transform = gradient.transform.then(&self.transform);
+                for stop in gradient.stops() {
+                    resolved_gradient.add_color_stop(ColorStop {
+                        offset: stop.offset,
+                        color: self.resolve_color(stop.color),
+                    });
+                }
+                Paint::Gradient(resolved_gradient)
+            }
+            Paint::Pattern(ref pattern) => {
+                // Patterns are specified in the coordinate space current at the time of drawing.
+                let mut resolved_pattern = pattern.clone();
+                resolved_pattern.transform = pattern.transform.then(&self.transform);
+                resolved_pattern.opacity *= self.global_alpha;
+                Paint::Pattern(resolved_pattern)
+            }
+        }
+    }
+
+    fn resolve_color(&self, mut color: ColorU) -> ColorU {
+        color.a = (color.a as f32 * self.global_alpha).round() as u8;
+        color
     }
 
     fn resolve_stroke_style(&self) -> StrokeStyle {
@@ -468,19 +657,68 @@ impl Path2D {
     }
 }
 
-// TODO(pcwalton): Gradients.
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum FillStyle {
     Color(ColorU),
+    Gradient(CanvasGradient),
+    Pattern(Pattern),
 }
 
 impl FillStyle {
     #[inline]
     fn to_paint(&self) -> Paint {
-        match *self { FillStyle::Color(color) => Paint { color } }
+        match *self {
+            FillStyle::Color(color) => Paint::Color(color),
+            FillStyle::Gradient(ref gradient) => Paint::Gradient(gradient.gradient.clone()),
+            FillStyle::Pattern(ref pattern) => Paint::Pattern(pattern.clone()),
+        }
+    }
+}
+
+/// A gradient, as returned by `CanvasRenderingContext2D.createLinearGradient()` and friends.
+#[derive(Clone, Debug)]
+pub struct CanvasGradient {
+    gradient: Gradient,
+}
+
+impl CanvasGradient {
+    /// Adds a color stop at `offset`, which must be between 0 and 1 inclusive. Otherwise the
+    /// gradient is left unchanged.
+    #[inline]
+    pub fn add_color_stop(&mut self, offset: f32, color: ColorU) -> Result<(), ColorStopError> {
+        if !(0.0..=1.0).contains(&offset) {
+            return Err(ColorStopError { offset });
+        }
+        self.gradient.add_color_stop(ColorStop { offset, color });
+        Ok(())
+    }
+}
+
+/// The error from `CanvasGradient::add_color_stop()` for an offset that is NaN or outside
+/// `[0, 1]`, which the canvas API reports as an `IndexSizeError`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStopError {
+    pub offset: f32,
+}
+
+impl Display for ColorStopError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "color stop offset {} is outside the range [0, 1]", self.offset)
     }
 }
 
+impl StdError for ColorStopError {}
+
+/// How a pattern repeats, as in the `repetition` argument to
+/// `CanvasRenderingContext2D.createPattern()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PatternRepetition {
+    Repeat,
+    RepeatX,
+    RepeatY,
+    NoRepeat,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TextAlign {
     Left,
@@ -488,6 +726,71 @@ pub enum TextAlign {
     Center,
 }
 
+/// How drawing is combined with the contents of the canvas, as in
+/// `CanvasRenderingContext2D.globalCompositeOperation`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompositeOperation {
+    SourceOver,
+    SourceIn,
+    SourceOut,
+    SourceAtop,
+    DestinationOver,
+    DestinationIn,
+    DestinationOut,
+    DestinationAtop,
+    Lighter,
+    Copy,
+    Xor,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl CompositeOperation {
+    fn to_blend_mode(self) -> BlendMode {
+        match self {
+            CompositeOperation::SourceOver => BlendMode::SourceOver,
+            CompositeOperation::SourceIn => BlendMode::SourceIn,
+            CompositeOperation::SourceOut => BlendMode::SourceOut,
+            CompositeOperation::SourceAtop => BlendMode::SourceAtop,
+            CompositeOperation::DestinationOver => BlendMode::DestinationOver,
+            CompositeOperation::DestinationIn => BlendMode::DestinationIn,
+            CompositeOperation::DestinationOut => BlendMode::DestinationOut,
+            CompositeOperation::DestinationAtop => BlendMode::DestinationAtop,
+            CompositeOperation::Lighter => BlendMode::Lighter,
+            CompositeOperation::Copy => BlendMode::Copy,
+            CompositeOperation::Xor => BlendMode::Xor,
+            CompositeOperation::Multiply => BlendMode::Multiply,
+            CompositeOperation::Screen => BlendMode::Screen,
+            CompositeOperation::Overlay => BlendMode::Overlay,
+            CompositeOperation::Darken => BlendMode::Darken,
+            CompositeOperation::Lighten => BlendMode::Lighten,
+            CompositeOperation::ColorDodge => BlendMode::ColorDodge,
+            CompositeOperation::ColorBurn => BlendMode::ColorBurn,
+            CompositeOperation::HardLight => BlendMode::HardLight,
+            CompositeOperation::SoftLight => BlendMode::SoftLight,
+            CompositeOperation::Difference => BlendMode::Difference,
+            CompositeOperation::Exclusion => BlendMode::Exclusion,
+            CompositeOperation::Hue => BlendMode::Hue,
+            CompositeOperation::Saturation => BlendMode::Saturation,
+            CompositeOperation::Color => BlendMode::Color,
+            CompositeOperation::Luminosity => BlendMode::Luminosity,
+        }
+    }
+}
+
 // We duplicate `pathfinder_geometry::stroke::LineJoin` here because the HTML canvas API treats the
 // miter limit as part of the canvas state, while the native Pathfinder API treats the miter limit
 // as part of the line join. Pathfinder's choice is more logical, because the miter limit is
@@ -554,3 +857,129 @@ impl LayoutExt for Layout {
         last_glyph.offset.x + glyph_rect.max_x() * scale_factor
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{CanvasFontContext, CanvasRenderingContext2D, ColorStopError};
+    use super::{CompositeOperation, FillRule, FillStyle, Path2D};
+    use font_kit::source::SystemSource;
+    use pathfinder_geometry::basic::line_segment::LineSegment2F;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::transform2d::Transform2DF;
+    use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_renderer::concurrent::executor::SequentialExecutor;
+    use pathfinder_renderer::cpu::renderer::SoftwareRenderer;
+    use pathfinder_renderer::gpu_data::RenderCommand;
+    use pathfinder_renderer::options::RenderOptions;
+    use skribo::FontCollection;
+    use std::sync::{Arc, Mutex};
+
+    const RED: ColorU = ColorU { r: 255, g: 0, b: 0, a: 255 };
+
+    // Makes a context without fonts, so that the tests don't depend on the fonts installed.
+    fn context(size: Vector2F) -> CanvasRenderingContext2D {
+        let font_context = CanvasFontContext {
+            font_source: Arc::new(SystemSource::new()),
+            default_font_collection: Arc::new(FontCollection::new()),
+        };
+        CanvasRenderingContext2D::new(font_context, size)
+    }
+
+    fn render(context: CanvasRenderingContext2D) -> SoftwareRenderer {
+        let scene = context.into_scene();
+        let commands = Arc::new(Mutex::new(vec![]));
+        let listener_commands = commands.clone();
+        let listener = move |command: RenderCommand| {
+            listener_commands.lock().unwrap().push(command)
+        };
+        scene.build(RenderOptions::default(), Box::new(listener), &SequentialExecutor);
+
+        let mut renderer = SoftwareRenderer::new(scene.view_box().size().to_i32());
+        renderer.begin_scene();
+        for command in commands.lock().unwrap().iter() {
+            renderer.render_command(command);
+        }
+        renderer.end_scene();
+        renderer
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> RectF {
+        RectF::new(Vector2F::new(x, y), Vector2F::new(width, height))
+    }
+
+    #[test]
+    fn test_color_stop_offset_out_of_range() {
+        let line = LineSegment2F::new(Vector2F::default(), Vector2F::new(16.0, 0.0));
+        let mut gradient = context(Vector2F::splat(16.0)).create_linear_gradient(line);
+        assert_eq!(gradient.add_color_stop(0.5, ColorU::black()), Ok(()));
+        assert_eq!(gradient.add_color_stop(1.5, ColorU::black()),
+                   Err(ColorStopError { offset: 1.5 }));
+        assert!(gradient.add_color_stop(f32::NAN, ColorU::black()).is_err());
+        assert_eq!(gradient.gradient.stops().len(), 1);
+    }
+
+    #[test]
+    fn test_clip_restored() {
+        let mut context = context(Vector2F::splat(16.0));
+        context.set_fill_style(FillStyle::Color(RED));
+
+        context.save();
+        let mut clip = Path2D::new();
+        clip.rect(rect(0.0, 0.0, 8.0, 16.0));
+        context.clip_path(clip, FillRule::Winding);
+        context.fill_rect(rect(0.0, 0.0, 16.0, 8.0));
+        context.restore();
+        context.fill_rect(rect(0.0, 8.0, 16.0, 8.0));
+
+        let renderer = render(context);
+        assert_eq!(renderer.pixel(Vector2I::new(4, 4)), RED);
+        assert_eq!(renderer.pixel(Vector2I::new(12, 4)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(12, 12)), RED);
+    }
+
+    #[test]
+    fn test_copy_clears_outside_shape() {
+        let blue = ColorU { r: 0, g: 0, b: 255, a: 255 };
+        let mut context = context(Vector2F::splat(16.0));
+        context.set_fill_style(FillStyle::Color(RED));
+        context.fill_rect(rect(0.0, 0.0, 16.0, 16.0));
+
+        context.set_global_composite_operation(CompositeOperation::Copy);
+        context.set_fill_style(FillStyle::Color(blue));
+        context.fill_rect(rect(4.0, 4.0, 8.0, 8.0));
+
+        let renderer = render(context);
+        assert_eq!(renderer.pixel(Vector2I::new(8, 8)), blue);
+        assert_eq!(renderer.pixel(Vector2I::new(1, 1)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(14, 8)), ColorU::transparent_black());
+    }
+
+    #[test]
+    fn test_point_in_path_with_hole() {
+        let context = context(Vector2F::splat(16.0));
+        let mut path = Path2D::new();
+        path.rect(rect(0.0, 0.0, 16.0, 16.0));
+        path.rect(rect(4.0, 4.0, 8.0, 8.0));
+
+        let (ring, hole) = (Vector2F::new(2.0, 8.0), Vector2F::splat(8.0));
+        assert!(context.is_point_in_path(&path, ring, FillRule::EvenOdd));
+        assert!(!context.is_point_in_path(&path, hole, FillRule::EvenOdd));
+        assert!(context.is_point_in_path(&path, hole, FillRule::Winding));
+    }
+
+    #[test]
+    fn test_point_in_transformed_stroke() {
+        let mut context = context(Vector2F::splat(16.0));
+        context.set_current_transform(&Transform2DF::from_scale(Vector2F::splat(2.0)));
+        context.set_line_width(2.0);
+        let mut path = Path2D::new();
+        path.move_to(Vector2F::new(0.0, 4.0));
+        path.line_to(Vector2F::new(8.0, 4.0));
+
+        // The stroke covers 6 <= y <= 10 in canvas coordinates.
+        assert!(context.is_point_in_stroke(&path, Vector2F::new(8.0, 9.5)));
+        assert!(!context.is_point_in_stroke(&path, Vector2F::new(8.0, 10.5)));
+        assert!(!context.is_point_in_stroke(&path, Vector2F::new(8.0, 4.5)));
+    }
+}
@@ -0,0 +1,527 @@
+// pathfinder/canvas/src/path_data.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Builds paths from SVG path data, as the `Path2D` constructor does.
+
+use crate::Path2D;
+use pathfinder_geometry::basic::transform2d::Transform2DF;
+use pathfinder_geometry::basic::vector::Vector2F;
+use pathfinder_geometry::outline::{ArcDirection, Contour};
+use std::error::Error as StdError;
+use std::f32::consts::PI;
+use std::fmt::{self, Display, Formatter};
+
+/// An error in SVG path data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParseError {
+    /// The byte offset of the problem in the path data.
+    pub offset: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    /// The path data doesn't start with a moveto command.
+    ExpectedMoveTo,
+    /// Something other than a command letter was found between commands.
+    ExpectedCommand,
+    ExpectedNumber,
+    /// An elliptical arc flag wasn't `0` or `1`.
+    ExpectedFlag,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let expected = match self.kind {
+            ParseErrorKind::ExpectedMoveTo => "a moveto command",
+            ParseErrorKind::ExpectedCommand => "a command",
+            ParseErrorKind::ExpectedNumber => "a number",
+            ParseErrorKind::ExpectedFlag => "an arc flag",
+        };
+        write!(formatter, "expected {} at byte {} of the path data", expected, self.offset)
+    }
+}
+
+impl StdError for ParseError {}
+
+impl Path2D {
+    /// Builds a path from the contents of an SVG `d` attribute, as in
+    /// `new Path2D("M10 10 h 80 v 80 h -80 Z")`.
+    ///
+    /// The whole SVG path grammar is accepted. Empty path data makes an empty path.
+    pub fn from_svg_path_data(data: &str) -> Result<Path2D, ParseError> {
+        let mut parser = Parser { data, offset: 0 };
+        let mut builder = PathBuilder {
+            path: Path2D::new(),
+            current: Vector2F::default(),
+            subpath_start: Vector2F::default(),
+            last_control: LastControl::None,
+            subpath_closed: false,
+        };
+
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => return Ok(builder.path),
+            Some(b'M') | Some(b'm') => {}
+            Some(_) => return Err(parser.error(ParseErrorKind::ExpectedMoveTo)),
+        }
+
+        while let Some(mut command) = parser.peek() {
+            match command.to_ascii_uppercase() {
+                b'Z' => {
+                    parser.offset += 1;
+                    parser.skip_whitespace();
+                    builder.close_path();
+                    continue;
+                }
+                b'M' | b'L' | b'H' | b'V' | b'C' | b'S' | b'Q' | b'T' | b'A' => {
+                    parser.offset += 1;
+                    parser.skip_whitespace();
+                }
+                _ => return Err(parser.error(ParseErrorKind::ExpectedCommand)),
+            }
+
+            // Commands repeat for as long as arguments follow, except that extra coordinate pairs
+            // after a moveto are linetos.
+            loop {
+                builder.push_command(command, &mut parser)?;
+                if !parser.skip_separator() && !parser.at_number() {
+                    break;
+                }
+                command = match command {
+                    b'M' => b'L',
+                    b'm' => b'l',
+                    _ => command,
+                };
+            }
+        }
+
+        Ok(builder.path)
+    }
+}
+
+struct PathBuilder {
+    path: Path2D,
+    current: Vector2F,
+    subpath_start: Vector2F,
+    last_control: LastControl,
+    subpath_closed: bool,
+}
+
+// The control point that a smooth curve reflects.
+#[derive(Clone, Copy)]
+enum LastControl {
+    None,
+    Cubic(Vector2F),
+    Quadratic(Vector2F),
+}
+
+impl PathBuilder {
+    fn push_command(&mut self, command: u8, parser: &mut Parser) -> Result<(), ParseError> {
+        let origin = if command.is_ascii_lowercase() { self.current } else { Vector2F::default() };
+        let command = command.to_ascii_uppercase();
+
+        // Drawing after a closepath starts a new subpath at the same point.
+        if self.subpath_closed && command != b'M' {
+            self.path.move_to(self.subpath_start);
+        }
+        self.subpath_closed = false;
+
+        let mut last_control = LastControl::None;
+        let to = match command {
+            b'M' => {
+                let to = origin + parser.point()?;
+                self.path.move_to(to);
+                self.subpath_start = to;
+                to
+            }
+            b'L' => {
+                let to = origin + parser.point()?;
+                self.path.line_to(to);
+                to
+            }
+            b'H' => {
+                let to = Vector2F::new(origin.x() + parser.number()?, self.current.y());
+                self.path.line_to(to);
+                to
+            }
+            b'V' => {
+                let to = Vector2F::new(self.current.x(), origin.y() + parser.number()?);
+                self.path.line_to(to);
+                to
+            }
+            b'C' | b'S' => {
+                let ctrl0 = if command == b'C' {
+                    let ctrl0 = origin + parser.point()?;
+                    parser.skip_separator();
+                    ctrl0
+                } else {
+                    match self.last_control {
+                        LastControl::Cubic(ctrl) => self.current.scale(2.0) - ctrl,
+                        LastControl::None | LastControl::Quadratic(_) => self.current,
+                    }
+                };
+                let ctrl1 = origin + parser.point()?;
+                parser.skip_separator();
+                let to = origin + parser.point()?;
+                self.path.bezier_curve_to(ctrl0, ctrl1, to);
+                last_control = LastControl::Cubic(ctrl1);
+                to
+            }
+            b'Q' | b'T' => {
+                let ctrl = if command == b'Q' {
+                    let ctrl = origin + parser.point()?;
+                    parser.skip_separator();
+                    ctrl
+                } else {
+                    match self.last_control {
+                        LastControl::Quadratic(ctrl) => self.current.scale(2.0) - ctrl,
+                        LastControl::None | LastControl::Cubic(_) => self.current,
+                    }
+                };
+                let to = origin + parser.point()?;
+                self.path.quadratic_curve_to(ctrl, to);
+                last_control = LastControl::Quadratic(ctrl);
+                to
+            }
+            b'A' => {
+                let radius_x = parser.number()?;
+                parser.skip_separator();
+                let radius_y = parser.number()?;
+                parser.skip_separator();
+                let x_axis_rotation = parser.number()?;
+                parser.skip_separator();
+                let large_arc = parser.flag()?;
+                parser.skip_separator();
+                let sweep = parser.flag()?;
+                parser.skip_separator();
+                let to = origin + parser.point()?;
+                self.push_arc(Vector2F::new(radius_x, radius_y),
+                              x_axis_rotation.to_radians(),
+                              large_arc,
+                              sweep,
+                              to);
+                to
+            }
+            _ => unreachable!(),
+        };
+
+        self.current = to;
+        self.last_control = last_control;
+        Ok(())
+    }
+
+    fn close_path(&mut self) {
+        self.path.close_path();
+        self.current = self.subpath_start;
+        self.last_control = LastControl::None;
+        self.subpath_closed = true;
+    }
+
+    // Converts from the endpoint parameterization of SVG to the center parameterization of
+    // `Contour::push_arc()`. See "Elliptical arc implementation notes" in the SVG specification.
+    fn push_arc(&mut self,
+                radii: Vector2F,
+                x_axis_rotation: f32,
+                large_arc: bool,
+                sweep: bool,
+                to: Vector2F) {
+        let from = self.current;
+        if from == to {
+            return;
+        }
+        let mut radii = Vector2F::new(radii.x().abs(), radii.y().abs());
+        if radii.x() == 0.0 || radii.y() == 0.0 {
+            self.path.line_to(to);
+            return;
+        }
+
+        // Find the start point relative to the middle of the chord, with the ellipse unrotated.
+        let (sin, cos) = x_axis_rotation.sin_cos();
+        let half_chord = (from - to).scale(0.5);
+        let from_center = Vector2F::new(cos * half_chord.x() + sin * half_chord.y(),
+                                        cos * half_chord.y() - sin * half_chord.x());
+
+        // Scale the radii up if they're too small to reach.
+        let reach = (from_center.x() / radii.x()).powi(2) + (from_center.y() / radii.y()).powi(2);
+        if reach > 1.0 {
+            radii = radii.scale(reach.sqrt());
+        }
+
+        let (rx2, ry2) = (radii.x() * radii.x(), radii.y() * radii.y());
+        let (x2, y2) = (from_center.x() * from_center.x(), from_center.y() * from_center.y());
+        let mut center_scale =
+            f32::sqrt(f32::max((rx2 * ry2 - rx2 * y2 - ry2 * x2) / (rx2 * y2 + ry2 * x2), 0.0));
+        if large_arc == sweep {
+            center_scale = -center_scale;
+        }
+        let center = Vector2F::new(radii.x() * from_center.y() / radii.y(),
+                                   -radii.y() * from_center.x() / radii.x()).scale(center_scale);
+
+        let start_vector = (from_center - center).scale_xy(Vector2F::new(1.0 / radii.x(),
+                                                                         1.0 / radii.y()));
+        let end_vector = (-from_center - center).scale_xy(Vector2F::new(1.0 / radii.x(),
+                                                                        1.0 / radii.y()));
+        let start_angle = f32::atan2(start_vector.y(), start_vector.x());
+        let mut sweep_angle = f32::atan2(end_vector.y(), end_vector.x()) - start_angle;
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += 2.0 * PI;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= 2.0 * PI;
+        }
+
+        // Scale the unit circle to the radii, rotate it, and move it to the center.
+        let center = Vector2F::new(cos * center.x() - sin * center.y(),
+                                   sin * center.x() + cos * center.y()) + (from + to).scale(0.5);
+        let transform = Transform2DF::row_major(cos * radii.x(),
+                                                -sin * radii.y(),
+                                                sin * radii.x(),
+                                                cos * radii.y(),
+                                                center.x(),
+                                                center.y());
+
+        // `push_arc()` only handles transforms that preserve circles, so sweep the arc around
+        // the unit circle and then transform the points.
+        let direction = if sweep { ArcDirection::CW } else { ArcDirection::CCW };
+        let mut arc = Contour::new();
+        arc.push_arc(&Transform2DF::default(), start_angle, start_angle + sweep_angle, direction);
+        arc.transform(&transform);
+        for segment in arc.iter() {
+            if segment.is_cubic() {
+                self.path.bezier_curve_to(segment.ctrl.from(),
+                                          segment.ctrl.to(),
+                                          segment.baseline.to());
+            } else if segment.is_quadratic() {
+                self.path.quadratic_curve_to(segment.ctrl.from(), segment.baseline.to());
+            } else {
+                self.path.line_to(segment.baseline.to());
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    data: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.data.as_bytes().get(self.offset).cloned()
+    }
+
+    #[inline]
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError { offset: self.offset, kind }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') | Some(b'\x0c') =
+                self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    // Skips whitespace and at most one comma. Returns true if there was a comma.
+    fn skip_separator(&mut self) -> bool {
+        self.skip_whitespace();
+        if self.peek() != Some(b',') {
+            return false;
+        }
+        self.offset += 1;
+        self.skip_whitespace();
+        true
+    }
+
+    #[inline]
+    fn at_number(&self) -> bool {
+        match self.peek() {
+            Some(b'+') | Some(b'-') | Some(b'.') => true,
+            Some(byte) => byte.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, ParseError> {
+        let bytes = self.data.as_bytes();
+        let mut end = self.offset;
+        if let Some(b'+') | Some(b'-') = bytes.get(end) {
+            end += 1;
+        }
+        let integer_start = end;
+        end = skip_digits(bytes, end);
+        let mut has_digits = end > integer_start;
+        if bytes.get(end) == Some(&b'.') {
+            let fraction_start = end + 1;
+            end = skip_digits(bytes, fraction_start);
+            has_digits = has_digits || end > fraction_start;
+        }
+        if !has_digits {
+            return Err(self.error(ParseErrorKind::ExpectedNumber));
+        }
+
+        // An `e` that isn't followed by digits belongs to whatever comes next.
+        if let Some(b'e') | Some(b'E') = bytes.get(end) {
+            let mut exponent_start = end + 1;
+            if let Some(b'+') | Some(b'-') = bytes.get(exponent_start) {
+                exponent_start += 1;
+            }
+            let exponent_end = skip_digits(bytes, exponent_start);
+            if exponent_end > exponent_start {
+                end = exponent_end;
+            }
+        }
+
+        let number = self.data[self.offset..end].parse().unwrap();
+        self.offset = end;
+        Ok(number)
+    }
+
+    fn point(&mut self) -> Result<Vector2F, ParseError> {
+        let x = self.number()?;
+        self.skip_separator();
+        let y = self.number()?;
+        Ok(Vector2F::new(x, y))
+    }
+
+    // Flags are single characters, so they need no separator after them.
+    fn flag(&mut self) -> Result<bool, ParseError> {
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error(ParseErrorKind::ExpectedFlag)),
+        };
+        self.offset += 1;
+        Ok(flag)
+    }
+}
+
+fn skip_digits(bytes: &[u8], mut offset: usize) -> usize {
+    while let Some(b'0'..=b'9') = bytes.get(offset) {
+        offset += 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod test {
+    use crate::path_data::{ParseError, ParseErrorKind};
+    use crate::Path2D;
+    use pathfinder_geometry::basic::vector::Vector2F;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::Contour;
+
+    fn endpoints(contour: &Contour) -> Vec<Vector2F> {
+        (0..contour.len()).filter(|&point_index| contour.point_is_endpoint(point_index))
+                          .map(|point_index| contour.position_of(point_index))
+                          .collect()
+    }
+
+    fn error(data: &str) -> ParseError {
+        Path2D::from_svg_path_data(data).err().unwrap()
+    }
+
+    #[test]
+    fn test_relative_lines() {
+        let outline = Path2D::from_svg_path_data("M10 10 h 80 v 80 h -80 Z").unwrap()
+                                                                           .into_outline();
+        assert_eq!(outline.contours().len(), 1);
+        let contour = &outline.contours()[0];
+        assert!(contour.is_closed());
+        assert_eq!(endpoints(contour), vec![
+            Vector2F::new(10.0, 10.0),
+            Vector2F::new(90.0, 10.0),
+            Vector2F::new(90.0, 90.0),
+            Vector2F::new(10.0, 90.0),
+        ]);
+    }
+
+    #[test]
+    fn test_implicit_commands() {
+        // Extra pairs after a moveto are linetos, numbers need no separators when their signs or
+        // decimal points tell them apart, and drawing after a closepath starts at the same point.
+        let outline = Path2D::from_svg_path_data("m1-2 3,4 .5.5 z l1e1 0").unwrap().into_outline();
+        assert_eq!(outline.contours().len(), 2);
+        assert_eq!(endpoints(&outline.contours()[0]), vec![
+            Vector2F::new(1.0, -2.0),
+            Vector2F::new(4.0, 2.0),
+            Vector2F::new(4.5, 2.5),
+        ]);
+        assert_eq!(endpoints(&outline.contours()[1]), vec![
+            Vector2F::new(1.0, -2.0),
+            Vector2F::new(11.0, -2.0),
+        ]);
+    }
+
+    #[test]
+    fn test_smooth_curves() {
+        let outline = Path2D::from_svg_path_data("M0 0 C0 10 10 10 10 0 S20 -10 20 0 \
+                                                  Q25 10 30 0 T40 0").unwrap().into_outline();
+        let contour = &outline.contours()[0];
+        let positions: Vec<_> = (0..contour.len()).map(|index| contour.position_of(index))
+                                                  .collect();
+        assert_eq!(positions, vec![
+            Vector2F::new(0.0, 0.0),
+            Vector2F::new(0.0, 10.0),
+            Vector2F::new(10.0, 10.0),
+            Vector2F::new(10.0, 0.0),
+            Vector2F::new(10.0, -10.0),
+            Vector2F::new(20.0, -10.0),
+            Vector2F::new(20.0, 0.0),
+            Vector2F::new(25.0, 10.0),
+            Vector2F::new(30.0, 0.0),
+            Vector2F::new(35.0, -10.0),
+            Vector2F::new(40.0, 0.0),
+        ]);
+    }
+
+    #[test]
+    fn test_arcs() {
+        // Two half circles of radius 10 around (10, 0), the first with its flags packed together,
+        // and an arc whose radii are too small to reach, which is scaled up to a half circle.
+        let outline = Path2D::from_svg_path_data("M0 0 A10 10 0 1120 0 a10,10 0 0,1 -20,0 Z \
+                                                  M0 40 A1 1 0 0 0 20 40").unwrap()
+                                                                          .into_outline();
+        let circle = &outline.contours()[0];
+        let bounds = circle.bounds();
+        assert!((bounds.min_x() - 0.0).abs() < 0.01 && (bounds.max_x() - 20.0).abs() < 0.01);
+        assert!((bounds.min_y() + 10.0).abs() < 0.01 && (bounds.max_y() - 10.0).abs() < 0.01);
+        assert!(outline.contains_point(Vector2F::new(10.0, 9.0), FillRule::Winding));
+        assert!(outline.contains_point(Vector2F::new(10.0, -9.0), FillRule::Winding));
+
+        // Sweeping in the negative angle direction from (0, 40) to (20, 40) passes below.
+        let half_circle = &outline.contours()[1];
+        let bounds = half_circle.bounds();
+        assert!((bounds.max_y() - 50.0).abs() < 0.01 && (bounds.min_y() - 40.0).abs() < 0.01);
+
+        // An ellipse turned on its side has its long axis along the chord.
+        let outline = Path2D::from_svg_path_data("M0 0 A20 10 90 0 1 0 40").unwrap()
+                                                                          .into_outline();
+        let bounds = outline.bounds();
+        assert!(bounds.min_x().abs() < 0.01 && (bounds.max_x() - 10.0).abs() < 0.01);
+        assert!(bounds.min_y().abs() < 0.01 && (bounds.max_y() - 40.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Path2D::from_svg_path_data(" ").unwrap().into_outline().contours().is_empty());
+        assert_eq!(error("L 10 10"),
+                   ParseError { offset: 0, kind: ParseErrorKind::ExpectedMoveTo });
+        assert_eq!(error("M 10 10 X"),
+                   ParseError { offset: 8, kind: ParseErrorKind::ExpectedCommand });
+        assert_eq!(error("M 10,, 10"),
+                   ParseError { offset: 5, kind: ParseErrorKind::ExpectedNumber });
+        assert_eq!(error("M 10 10 L 5"),
+                   ParseError { offset: 11, kind: ParseErrorKind::ExpectedNumber });
+        assert_eq!(error("M 0 0 A 1 1 0 2 0 5 5"),
+                   ParseError { offset: 14, kind: ParseErrorKind::ExpectedFlag });
+    }
+}
@@ -252,6 +252,7 @@ impl<W> DemoApp<W> where W: Window {
                 Vector2F::default()
             },
             subpixel_aa_enabled: self.ui_model.subpixel_aa_effect_enabled,
+            zero_timings: false,
         };
 
         self.render_command_stream = Some(self.scene_proxy.build_with_stream(render_options));
@@ -290,7 +290,7 @@ impl<W> DemoApp<W> where W: Window {
         for command in self.render_command_stream.as_mut().unwrap() {
             self.renderer.render_command(&command);
 
-            if let RenderCommand::Finish { build_time } = command {
+            if let RenderCommand::Finish { build_time, .. } = command {
                 self.build_time = Some(build_time);
             }
         }
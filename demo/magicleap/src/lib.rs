@@ -223,6 +223,7 @@ pub unsafe extern "C" fn magicleap_pathfinder_render(pf: *mut c_void, options: *
             transform: RenderTransform::Transform2D(transform),
             dilation: Vector2F::default(),
             subpixel_aa_enabled: false,
+            zero_timings: false,
         };
 
         let scene_proxy = SceneProxy::from_scene(svg.scene.clone(), SequentialExecutor);
@@ -14,6 +14,7 @@ use crate::basic::vector::Vector2F;
 use crate::basic::transform2d::Matrix2x2F;
 use crate::util;
 use pathfinder_simd::default::F32x4;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::{Add, Sub};
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
@@ -284,6 +285,21 @@ impl Sub<Vector2F> for LineSegment2F {
     }
 }
 
+/// Serialized as a `(from, to)` pair.
+impl Serialize for LineSegment2F {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.from(), self.to()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LineSegment2F {
+    fn deserialize<D>(deserializer: D) -> Result<LineSegment2F, D::Error>
+                      where D: Deserializer<'de> {
+        let (from, to) = Deserialize::deserialize(deserializer)?;
+        Ok(LineSegment2F::new(from, to))
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 #[repr(transparent)]
 pub struct LineSegmentU4(pub u16);
@@ -12,6 +12,7 @@
 
 use crate::basic::vector::{Vector2F, Vector2I};
 use pathfinder_simd::default::{F32x4, I32x4};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct RectF(pub F32x4);
@@ -150,6 +151,20 @@ impl RectF {
     }
 }
 
+/// Serialized as an `(origin, size)` pair.
+impl Serialize for RectF {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.origin(), self.size()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RectF {
+    fn deserialize<D>(deserializer: D) -> Result<RectF, D::Error> where D: Deserializer<'de> {
+        let (origin, size) = Deserialize::deserialize(deserializer)?;
+        Ok(RectF::new(origin, size))
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub struct RectI(pub I32x4);
 
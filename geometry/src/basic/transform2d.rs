@@ -17,6 +17,7 @@ use crate::basic::transform3d::Transform3DF;
 use crate::segment::Segment;
 use crate::unit_vector::UnitVector;
 use pathfinder_simd::default::F32x4;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::Sub;
 
 /// A 2x2 matrix, optimized with SIMD, in column-major order.
@@ -211,6 +212,24 @@ impl Transform2DF {
         other.post_mul(self)
     }
 
+    /// Returns the transform that applies this transform and then `other`.
+    ///
+    /// Unlike `post_mul()`, which applies `other` first to points but last to the translation,
+    /// this composes the two in the same order throughout.
+    #[inline]
+    pub fn then(&self, other: &Transform2DF) -> Transform2DF {
+        let matrix = other.matrix.post_mul(&self.matrix);
+        let vector = other.transform_point(self.vector);
+        Transform2DF { matrix, vector }
+    }
+
+    #[inline]
+    pub fn inverse(&self) -> Transform2DF {
+        let matrix = self.matrix.inverse();
+        let vector = -matrix.transform_point(self.vector);
+        Transform2DF { matrix, vector }
+    }
+
     // TODO(pcwalton): Optimize better with SIMD.
     #[inline]
     pub fn to_3d(&self) -> Transform3DF {
@@ -296,6 +315,23 @@ impl Transform2DF {
     }
 }
 
+// Serialized as the row-major tuple `(m11, m12, m21, m22, m31, m32)`.
+impl Serialize for Transform2DF {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let translation = self.translation();
+        (self.m11(), self.m12(), self.m21(), self.m22(), translation.x(), translation.y())
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Transform2DF {
+    fn deserialize<D>(deserializer: D) -> Result<Transform2DF, D::Error>
+                      where D: Deserializer<'de> {
+        let (m11, m12, m21, m22, m31, m32) = Deserialize::deserialize(deserializer)?;
+        Ok(Transform2DF::row_major(m11, m12, m21, m22, m31, m32))
+    }
+}
+
 /// Transforms a path with a SIMD 2D transform.
 pub struct Transform2DFPathIter<I>
 where
@@ -349,3 +385,20 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::basic::transform2d::Transform2DF;
+    use crate::basic::vector::Vector2F;
+
+    #[test]
+    fn test_then() {
+        let a = Transform2DF::row_major(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let b = Transform2DF::row_major(0.0, 1.0, -1.0, 0.0, 7.0, 8.0);
+        let c = Transform2DF::row_major(3.0, 4.0, -1.0, -2.0, 13.0, 3.0);
+        assert_eq!(a.then(&b), c);
+
+        let p = Vector2F::new(3.0, -2.0);
+        assert_eq!(a.then(&b).transform_point(p), b.transform_point(a.transform_point(p)));
+    }
+}
@@ -11,6 +11,7 @@
 //! A SIMD-optimized point type.
 
 use pathfinder_simd::default::{F32x4, I32x4};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::{Add, AddAssign, Mul, Neg, Sub};
 
 /// 2D points with 32-bit floating point coordinates.
@@ -148,6 +149,20 @@ impl PartialEq for Vector2F {
     }
 }
 
+/// Serialized as an `(x, y)` pair.
+impl Serialize for Vector2F {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.x(), self.y()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Vector2F {
+    fn deserialize<D>(deserializer: D) -> Result<Vector2F, D::Error> where D: Deserializer<'de> {
+        let (x, y) = Deserialize::deserialize(deserializer)?;
+        Ok(Vector2F::new(x, y))
+    }
+}
+
 impl Add<Vector2F> for Vector2F {
     type Output = Vector2F;
     #[inline]
@@ -262,6 +277,20 @@ impl PartialEq for Vector2I {
     }
 }
 
+/// Serialized as an `(x, y)` pair.
+impl Serialize for Vector2I {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        (self.x(), self.y()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Vector2I {
+    fn deserialize<D>(deserializer: D) -> Result<Vector2I, D::Error> where D: Deserializer<'de> {
+        let (x, y) = Deserialize::deserialize(deserializer)?;
+        Ok(Vector2I::new(x, y))
+    }
+}
+
 /// 3D homogeneous points.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vector4F(pub F32x4);
@@ -0,0 +1,26 @@
+// pathfinder/geometry/src/fill.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fill rules, which determine which regions of an outline are inside it.
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum FillRule {
+    /// A point is inside if the winding number of the outline around it is nonzero.
+    Winding,
+    /// A point is inside if the winding number of the outline around it is odd.
+    EvenOdd,
+}
+
+impl Default for FillRule {
+    #[inline]
+    fn default() -> FillRule {
+        FillRule::Winding
+    }
+}
@@ -16,11 +16,14 @@
 extern crate bitflags;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
 pub mod basic;
 pub mod clip;
 pub mod color;
 pub mod dash;
+pub mod fill;
 pub mod orientation;
 pub mod outline;
 pub mod segment;
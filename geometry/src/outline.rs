@@ -17,13 +17,20 @@ use crate::basic::transform2d::Transform2DF;
 use crate::basic::transform3d::Perspective;
 use crate::clip::{self, ContourPolygonClipper, ContourRectClipper};
 use crate::dilation::ContourDilator;
+use crate::fill::FillRule;
 use crate::orientation::Orientation;
 use crate::segment::{Segment, SegmentFlags, SegmentKind};
 use crate::unit_vector::UnitVector;
+use serde::de::Error as DeserializeError;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::f32::consts::PI;
 use std::fmt::{self, Debug, Formatter};
 use std::mem;
 
+// The number of bisection steps used to find where a monotonic curve crosses a horizontal line.
+const MAX_CROSSING_ITERATIONS: u32 = 24;
+
 #[derive(Clone)]
 pub struct Outline {
     pub(crate) contours: Vec<Contour>,
@@ -174,6 +181,25 @@ impl Outline {
             .unwrap_or_else(|| RectF::default());
     }
 
+    /// Returns the number of times the outline winds around the given point. Open contours are
+    /// treated as closed, as they are when filled.
+    pub fn winding_number(&self, point: Vector2F) -> i32 {
+        self.contours.iter().map(|contour| contour.winding_number(point)).sum()
+    }
+
+    /// Returns true if filling the outline with the given fill rule would cover the point.
+    pub fn contains_point(&self, point: Vector2F, fill_rule: FillRule) -> bool {
+        if !self.bounds.contains_point(point) {
+            return false;
+        }
+
+        let winding_number = self.winding_number(point);
+        match fill_rule {
+            FillRule::Winding => winding_number != 0,
+            FillRule::EvenOdd => winding_number % 2 != 0,
+        }
+    }
+
     pub fn is_outside_polygon(&self, clip_polygon: &[Vector2F]) -> bool {
         clip::rect_is_outside_polygon(self.bounds, clip_polygon)
     }
@@ -280,6 +306,26 @@ impl Contour {
         self.points.last().cloned()
     }
 
+    /// Returns the number of times this contour winds around the given point, closing it first
+    /// if it's open.
+    pub fn winding_number(&self, point: Vector2F) -> i32 {
+        if self.points.is_empty() ||
+                point.y() < self.bounds.min_y() ||
+                point.y() >= self.bounds.max_y() ||
+                point.x() >= self.bounds.max_x() {
+            return 0;
+        }
+
+        let mut winding_number = self.iter()
+                                     .map(|segment| segment_winding_number(&segment, point))
+                                     .sum();
+        if !self.closed {
+            let closing_line = LineSegment2F::new(self.position_of_last(1), self.position_of(0));
+            winding_number += line_winding_number(&closing_line, point);
+        }
+        winding_number
+    }
+
     #[inline]
     pub(crate) fn position_of_last(&self, index: u32) -> Vector2F {
         self.points[self.points.len() - index as usize]
@@ -759,6 +805,101 @@ impl Debug for Contour {
     }
 }
 
+/// Serialized as the sequence of its contours. The bounds are recomputed when deserializing.
+impl Serialize for Outline {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.contours.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Outline {
+    fn deserialize<D>(deserializer: D) -> Result<Outline, D::Error> where D: Deserializer<'de> {
+        let contours: Vec<Contour> = Deserialize::deserialize(deserializer)?;
+        let mut outline = Outline::new();
+        for contour in contours {
+            outline.push_contour(contour);
+        }
+        Ok(outline)
+    }
+}
+
+impl Serialize for Contour {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut state = serializer.serialize_struct("Contour", 3)?;
+        state.serialize_field("points", &self.points)?;
+        state.serialize_field("flags", &self.flags)?;
+        state.serialize_field("closed", &self.closed)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Contour {
+    fn deserialize<D>(deserializer: D) -> Result<Contour, D::Error> where D: Deserializer<'de> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Contour")]
+        struct ContourData {
+            points: Vec<Vector2F>,
+            flags: Vec<PointFlags>,
+            closed: bool,
+        }
+
+        let data = ContourData::deserialize(deserializer)?;
+        if data.points.len() != data.flags.len() {
+            return Err(D::Error::custom("contour has different numbers of points and flags"));
+        }
+
+        // Every segment is one endpoint preceded by no control points, by one quadratic control
+        // point, or by two cubic control points, so that iterating over the segments stays in
+        // bounds.
+        let mut control_point_count = 0;
+        for (point_index, &flags) in data.flags.iter().enumerate() {
+            let valid = if flags.is_empty() {
+                control_point_count = 0;
+                true
+            } else if flags == PointFlags::CONTROL_POINT_0 {
+                control_point_count += 1;
+                point_index > 0 && control_point_count == 1
+            } else if flags == PointFlags::CONTROL_POINT_1 {
+                control_point_count += 1;
+                control_point_count == 2
+            } else {
+                false
+            };
+            if !valid {
+                return Err(D::Error::custom("contour has a misplaced control point"));
+            }
+        }
+        if control_point_count != 0 {
+            return Err(D::Error::custom("contour ends with a control point"));
+        }
+
+        let mut contour = Contour::new();
+        for (&point, &flags) in data.points.iter().zip(data.flags.iter()) {
+            if !point.x().is_finite() || !point.y().is_finite() {
+                return Err(D::Error::custom("contour has a NaN or infinite point"));
+            }
+            contour.push_point(point, flags, true);
+        }
+        contour.closed = data.closed;
+        Ok(contour)
+    }
+}
+
+impl Serialize for PointFlags {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.bits().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PointFlags {
+    fn deserialize<D>(deserializer: D) -> Result<PointFlags, D::Error>
+                      where D: Deserializer<'de> {
+        let bits = Deserialize::deserialize(deserializer)?;
+        PointFlags::from_bits(bits).ok_or_else(|| D::Error::custom("invalid point flags"))
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PointIndex(u32);
 
@@ -830,6 +971,90 @@ impl<'a> Iterator for ContourIter<'a> {
     }
 }
 
+// Counts the crossings of the segment with a ray cast from the point in the +x direction.
+fn segment_winding_number(segment: &Segment, point: Vector2F) -> i32 {
+    if segment.is_line() {
+        return line_winding_number(&segment.baseline, point);
+    }
+
+    // Split the curve at its vertical extrema so that each piece crosses the ray at most once.
+    let segment = segment.to_cubic();
+    if point.y() < segment.as_cubic_segment().min_y() ||
+            point.y() > segment.as_cubic_segment().max_y() ||
+            point.x() >= segment.as_cubic_segment().max_x() {
+        return 0;
+    }
+    match segment.as_cubic_segment().y_extrema() {
+        (None, None) => monotonic_cubic_winding_number(&segment, point),
+        (Some(t0), None) | (None, Some(t0)) => {
+            let (prev, next) = segment.as_cubic_segment().split(t0);
+            monotonic_cubic_winding_number(&prev, point) +
+                monotonic_cubic_winding_number(&next, point)
+        }
+        (Some(t0), Some(t1)) => {
+            let (prev, rest) = segment.as_cubic_segment().split(t0);
+            let (mid, next) = rest.as_cubic_segment().split((t1 - t0) / (1.0 - t0));
+            monotonic_cubic_winding_number(&prev, point) +
+                monotonic_cubic_winding_number(&mid, point) +
+                monotonic_cubic_winding_number(&next, point)
+        }
+    }
+}
+
+// Spans are half-open in y so that a ray through a shared endpoint is counted only once.
+fn line_winding_number(line: &LineSegment2F, point: Vector2F) -> i32 {
+    let y_winding = if line.from_y() <= point.y() && point.y() < line.to_y() {
+        1
+    } else if line.to_y() <= point.y() && point.y() < line.from_y() {
+        -1
+    } else {
+        return 0;
+    };
+
+    if line.solve_x_for_y(point.y()) > point.x() {
+        y_winding
+    } else {
+        0
+    }
+}
+
+fn monotonic_cubic_winding_number(segment: &Segment, point: Vector2F) -> i32 {
+    let baseline = segment.baseline;
+    let y_winding = if baseline.from_y() <= point.y() && point.y() < baseline.to_y() {
+        1
+    } else if baseline.to_y() <= point.y() && point.y() < baseline.from_y() {
+        -1
+    } else {
+        return 0;
+    };
+
+    let cubic = segment.as_cubic_segment();
+    if point.x() < cubic.min_x() {
+        return y_winding;
+    }
+    if point.x() >= cubic.max_x() {
+        return 0;
+    }
+
+    // Bisect to find where the curve reaches the ray's y coordinate.
+    let (mut min_t, mut max_t) = (0.0, 1.0);
+    for _ in 0..MAX_CROSSING_ITERATIONS {
+        let mid_t = 0.5 * (min_t + max_t);
+        let mid_y = cubic.sample(mid_t).y();
+        if (mid_y < point.y()) == (y_winding > 0) {
+            min_t = mid_t;
+        } else {
+            max_t = mid_t;
+        }
+    }
+
+    if cubic.sample(0.5 * (min_t + max_t)).x() > point.x() {
+        y_winding
+    } else {
+        0
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ArcDirection {
     CW,
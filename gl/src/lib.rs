@@ -47,10 +47,17 @@ impl GLDevice {
     }
 
     fn set_texture_parameters(&self, texture: &GLTexture) {
+        // 32-bit floating point textures aren't filterable everywhere, and the texture is
+        // incomplete if we ask for filtering that isn't supported.
+        let filter = match texture.format {
+            TextureFormat::RGBA32F => gl::NEAREST,
+            TextureFormat::R8 | TextureFormat::R16F | TextureFormat::RGBA8 => gl::LINEAR,
+        };
+
         self.bind_texture(texture, 0);
         unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint); ck();
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint); ck();
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint); ck();
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint); ck();
             gl::TexParameteri(gl::TEXTURE_2D,
                               gl::TEXTURE_WRAP_S,
                               gl::CLAMP_TO_EDGE as GLint); ck();
@@ -88,6 +95,11 @@ impl GLDevice {
                                           gl::ONE); ck();
                     gl::Enable(gl::BLEND); ck();
                 }
+                BlendState::RGBDestColorAlphaZero => {
+                    gl::BlendEquation(gl::FUNC_ADD); ck();
+                    gl::BlendFunc(gl::DST_COLOR, gl::ZERO); ck();
+                    gl::Enable(gl::BLEND); ck();
+                }
             }
 
             // Set depth.
@@ -134,7 +146,8 @@ impl GLDevice {
                 BlendState::Off => {}
                 BlendState::RGBOneAlphaOneMinusSrcAlpha |
                 BlendState::RGBOneAlphaOne |
-                BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha => {
+                BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha |
+                BlendState::RGBDestColorAlphaZero => {
                     gl::Disable(gl::BLEND); ck();
                 }
             }
@@ -165,26 +178,9 @@ impl Device for GLDevice {
     type VertexAttr = GLVertexAttr;
 
     fn create_texture(&self, format: TextureFormat, size: Vector2I) -> GLTexture {
-        let (gl_internal_format, gl_format, gl_type);
-        match format {
-            TextureFormat::R8 => {
-                gl_internal_format = gl::R8 as GLint;
-                gl_format = gl::RED;
-                gl_type = gl::UNSIGNED_BYTE;
-            }
-            TextureFormat::R16F => {
-                gl_internal_format = gl::R16F as GLint;
-                gl_format = gl::RED;
-                gl_type = gl::HALF_FLOAT;
-            }
-            TextureFormat::RGBA8 => {
-                gl_internal_format = gl::RGBA as GLint;
-                gl_format = gl::RGBA;
-                gl_type = gl::UNSIGNED_BYTE;
-            }
-        }
+        let (gl_internal_format, gl_format, gl_type) = gl_texture_format(format);
 
-        let mut texture = GLTexture { gl_texture: 0, size };
+        let mut texture = GLTexture { gl_texture: 0, size, format };
         unsafe {
             gl::GenTextures(1, &mut texture.gl_texture); ck();
             self.bind_texture(&texture, 0);
@@ -206,7 +202,7 @@ impl Device for GLDevice {
     fn create_texture_from_data(&self, size: Vector2I, data: &[u8]) -> GLTexture {
         assert!(data.len() >= size.x() as usize * size.y() as usize);
 
-        let mut texture = GLTexture { gl_texture: 0, size };
+        let mut texture = GLTexture { gl_texture: 0, size, format: TextureFormat::R8 };
         unsafe {
             gl::GenTextures(1, &mut texture.gl_texture); ck();
             self.bind_texture(&texture, 0);
@@ -455,23 +451,45 @@ impl Device for GLDevice {
     }
 
     fn upload_to_texture(&self, texture: &Self::Texture, size: Vector2I, data: &[u8]) {
-        assert!(data.len() >= size.x() as usize * size.y() as usize * 4);
+        let bytes_per_pixel = match texture.format {
+            TextureFormat::R8 => 1,
+            TextureFormat::R16F => 2,
+            TextureFormat::RGBA8 => 4,
+            TextureFormat::RGBA32F => 16,
+        };
+        assert!(data.len() >= size.x() as usize * size.y() as usize * bytes_per_pixel);
+
+        let (gl_internal_format, gl_format, gl_type) = gl_texture_format(texture.format);
         unsafe {
             self.bind_texture(texture, 0);
             gl::TexImage2D(gl::TEXTURE_2D,
                            0,
-                           gl::RGBA as GLint,
+                           gl_internal_format,
                            size.x() as GLsizei,
                            size.y() as GLsizei,
                            0,
-                           gl::RGBA,
-                           gl::UNSIGNED_BYTE,
+                           gl_format,
+                           gl_type,
                            data.as_ptr() as *const GLvoid); ck();
         }
 
         self.set_texture_parameters(texture);
     }
 
+    fn copy_framebuffer_to_texture(&self, texture: &Self::Texture, rect: RectI) {
+        unsafe {
+            self.bind_texture(texture, 0);
+            gl::CopyTexSubImage2D(gl::TEXTURE_2D,
+                                  0,
+                                  0,
+                                  0,
+                                  rect.origin().x(),
+                                  rect.origin().y(),
+                                  rect.size().x() as GLsizei,
+                                  rect.size().y() as GLsizei); ck();
+        }
+    }
+
     fn read_pixels_from_default_framebuffer(&self, size: Vector2I) -> Vec<u8> {
         let mut pixels = vec![0; size.x() as usize * size.y() as usize * 4];
         unsafe {
@@ -800,6 +818,7 @@ impl Drop for GLShader {
 pub struct GLTexture {
     gl_texture: GLuint,
     pub size: Vector2I,
+    pub format: TextureFormat,
 }
 
 pub struct GLTimerQuery {
@@ -892,6 +911,7 @@ impl VertexAttrTypeExt for VertexAttrType {
             VertexAttrType::I16 => gl::SHORT,
             VertexAttrType::I8  => gl::BYTE,
             VertexAttrType::U16 => gl::UNSIGNED_SHORT,
+            VertexAttrType::U32 => gl::UNSIGNED_INT,
             VertexAttrType::U8  => gl::UNSIGNED_BYTE,
         }
     }
@@ -916,6 +936,17 @@ impl GLVersion {
     }
 }
 
+// Texture formats
+
+fn gl_texture_format(format: TextureFormat) -> (GLint, GLenum, GLenum) {
+    match format {
+        TextureFormat::R8 => (gl::R8 as GLint, gl::RED, gl::UNSIGNED_BYTE),
+        TextureFormat::R16F => (gl::R16F as GLint, gl::RED, gl::HALF_FLOAT),
+        TextureFormat::RGBA8 => (gl::RGBA as GLint, gl::RGBA, gl::UNSIGNED_BYTE),
+        TextureFormat::RGBA32F => (gl::RGBA32F as GLint, gl::RGBA, gl::FLOAT),
+    }
+}
+
 // Error checking
 
 #[cfg(debug)]
@@ -21,10 +21,12 @@ use std::time::Duration;
 
 pub mod resources;
 
-static INCLUDES: [&str; 6] = [
+static INCLUDES: [&str; 8] = [
     "tile_alpha_vertex",
+    "tile_blend",
     "tile_monochrome",
     "tile_multicolor",
+    "tile_paint",
     "tile_solid_vertex",
     "post_convolve",
     "post_gamma_correct",
@@ -76,6 +78,8 @@ pub trait Device {
     fn framebuffer_texture<'f>(&self, framebuffer: &'f Self::Framebuffer) -> &'f Self::Texture;
     fn texture_size(&self, texture: &Self::Texture) -> Vector2I;
     fn upload_to_texture(&self, texture: &Self::Texture, size: Vector2I, data: &[u8]);
+    /// Copies `rect` of the currently-bound framebuffer to the origin of `texture`.
+    fn copy_framebuffer_to_texture(&self, texture: &Self::Texture, rect: RectI);
     fn read_pixels_from_default_framebuffer(&self, size: Vector2I) -> Vec<u8>;
     fn clear(&self, params: &ClearParams);
     fn draw_arrays(&self, primitive: Primitive, index_count: u32, render_state: &RenderState);
@@ -143,11 +147,12 @@ pub trait Device {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TextureFormat {
     R8,
     R16F,
     RGBA8,
+    RGBA32F,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -156,6 +161,7 @@ pub enum VertexAttrType {
     I16,
     I8,
     U16,
+    U32,
     U8,
 }
 
@@ -221,6 +227,7 @@ pub enum BlendState {
     RGBOneAlphaOne,
     RGBOneAlphaOneMinusSrcAlpha,
     RGBSrcAlphaAlphaOneMinusSrcAlpha,
+    RGBDestColorAlphaZero,
 }
 
 #[derive(Clone, Copy, Default, Debug)]
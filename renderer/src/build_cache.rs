@@ -0,0 +1,188 @@
+// pathfinder/renderer/src/build_cache.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Keeps the tiles of paths and clip paths from one build to the next.
+//!
+//! Tiling is most of the work of building a scene. Every path and clip path in a scene has a
+//! revision number that changes whenever it does, so a build can reuse the fills and tiles of
+//! anything that has the same revision as last time, provided that the outlines were tiled the
+//! same way. If the render transform only moved by whole tiles, the old tiles are moved to match,
+//! as long as the outline is in view both times so that clipping to the view box doesn't change
+//! it. Cached objects are never modified, so reusing one only costs a reference count; how far its
+//! tiles have moved is kept alongside it.
+
+use crate::gpu_data::BuiltObject;
+use crate::options::{PreparedRenderOptions, PreparedRenderTransform};
+use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
+use hashbrown::HashMap;
+use pathfinder_geometry::basic::rect::RectF;
+use pathfinder_geometry::basic::transform2d::Transform2DF;
+use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a revision number that no path or clip path has had yet.
+#[inline]
+pub(crate) fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The tiles of paths and clip paths as of the last build of a scene.
+///
+/// A clone of a scene starts out with the objects of the last build, but keeps its own from then
+/// on, so that building one clone doesn't evict the objects of the other.
+#[derive(Default)]
+pub(crate) struct BuildCache {
+    state: Mutex<BuildCacheState>,
+}
+
+#[derive(Clone, Default)]
+struct BuildCacheState {
+    context: Option<TilingContext>,
+    objects: HashMap<u64, CachedObject>,
+}
+
+/// Everything other than the outline itself that determines how an outline is tiled.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct TilingContext {
+    transform: Transform2DF,
+    dilation: Vector2F,
+    view_box: RectF,
+}
+
+#[derive(Clone)]
+pub(crate) struct CachedObject {
+    /// The tiled outline, with alpha tile indices local to the object.
+    pub(crate) object: Arc<BuiltObject>,
+    /// How many tiles the outline has moved by since it was tiled.
+    pub(crate) tile_offset: Vector2I,
+    /// The bounds of the outline in device space before clipping to the view box.
+    pub(crate) device_bounds: RectF,
+}
+
+/// The cache as seen by one build.
+///
+/// Objects that the build uses, whether reused or newly tiled, are retained for the next build;
+/// everything else is dropped.
+pub(crate) struct CacheFrame {
+    context: Option<TilingContext>,
+    previous_objects: HashMap<u64, CachedObject>,
+    tile_offset: Option<Vector2I>,
+    retained_objects: Mutex<HashMap<u64, CachedObject>>,
+}
+
+impl BuildCache {
+    /// Starts a build. Perspective transforms are never cached.
+    pub(crate) fn begin(&self, options: &PreparedRenderOptions, view_box: RectF) -> CacheFrame {
+        let context = match options.transform {
+            PreparedRenderTransform::Perspective { .. } => None,
+            PreparedRenderTransform::None | PreparedRenderTransform::Transform2D(_) => {
+                Some(TilingContext {
+                    transform: options.transform_2d(),
+                    dilation: options.dilation,
+                    view_box,
+                })
+            }
+        };
+
+        let state = mem::take(&mut *self.state.lock().unwrap());
+        let tile_offset = match (state.context, context) {
+            (Some(ref old_context), Some(ref new_context)) => {
+                old_context.tile_offset_to(new_context)
+            }
+            _ => None,
+        };
+
+        CacheFrame {
+            context,
+            previous_objects: state.objects,
+            tile_offset,
+            retained_objects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Finishes a build, keeping the objects that it used.
+    pub(crate) fn end(&self, frame: CacheFrame) {
+        let objects = frame.retained_objects.into_inner().unwrap();
+        *self.state.lock().unwrap() = BuildCacheState { context: frame.context, objects };
+    }
+}
+
+impl Clone for BuildCache {
+    fn clone(&self) -> BuildCache {
+        BuildCache { state: Mutex::new(self.state.lock().unwrap().clone()) }
+    }
+}
+
+impl TilingContext {
+    /// Returns the offset in tiles by which outlines tiled in this context move when tiled in
+    /// `other` instead, if the two differ only by a translation by whole tiles.
+    fn tile_offset_to(&self, other: &TilingContext) -> Option<Vector2I> {
+        if self.dilation != other.dilation || self.view_box != other.view_box ||
+                self.transform.m11() != other.transform.m11() ||
+                self.transform.m12() != other.transform.m12() ||
+                self.transform.m21() != other.transform.m21() ||
+                self.transform.m22() != other.transform.m22() {
+            return None;
+        }
+
+        let offset = other.transform.translation() - self.transform.translation();
+        let tile_offset = offset.scale_xy(Vector2F::new(1.0 / TILE_WIDTH as f32,
+                                                        1.0 / TILE_HEIGHT as f32));
+        if tile_offset.x().fract() != 0.0 || tile_offset.y().fract() != 0.0 {
+            return None;
+        }
+        Some(tile_offset.to_i32())
+    }
+}
+
+impl CacheFrame {
+    /// Returns the transform that outlines are tiled with, if this build is cacheable.
+    #[inline]
+    pub(crate) fn transform(&self) -> Option<Transform2DF> {
+        self.context.map(|context| context.transform)
+    }
+
+    /// Returns the tiles of the object with the given revision from the last build, moved to
+    /// where they belong in this one.
+    pub(crate) fn get(&self, revision: u64) -> Option<CachedObject> {
+        let tile_offset = self.tile_offset?;
+        let cached_object = self.previous_objects.get(&revision)?;
+        if tile_offset == Vector2I::default() {
+            return Some(cached_object.clone());
+        }
+
+        let offset = tile_offset.to_f32().scale_xy(Vector2F::new(TILE_WIDTH as f32,
+                                                                 TILE_HEIGHT as f32));
+        let device_bounds = RectF::new(cached_object.device_bounds.origin() + offset,
+                                       cached_object.device_bounds.size());
+        let view_box = self.context.unwrap().view_box;
+        if !view_box.contains_rect(cached_object.device_bounds) ||
+                !view_box.contains_rect(device_bounds) {
+            return None;
+        }
+
+        Some(CachedObject {
+            object: cached_object.object.clone(),
+            tile_offset: cached_object.tile_offset + tile_offset,
+            device_bounds,
+        })
+    }
+
+    /// Keeps the tiles of the object with the given revision for the next build.
+    pub(crate) fn retain(&self, revision: u64, cached_object: CachedObject) {
+        if self.context.is_some() {
+            self.retained_objects.lock().unwrap().insert(revision, cached_object);
+        }
+    }
+}
@@ -10,29 +10,50 @@
 
 //! Packs data onto the GPU.
 
+use crate::build_cache::{CacheFrame, CachedObject};
+use crate::clip::{BuiltClipPath, ClipTile, ResolvedClipPaths};
 use crate::concurrent::executor::Executor;
-use crate::gpu_data::{AlphaTileBatchPrimitive, BuiltObject, FillBatchPrimitive, RenderCommand};
+use crate::gpu_data::{AlphaTileBatchPrimitive, BuildError, BuildProfile, BuiltObject, ObjectFill};
+use crate::gpu_data::{CompositeTileBatchPrimitive, FillBatchPrimitive, PathStats, RenderCommand};
 use crate::options::{PreparedRenderOptions, RenderCommandListener};
-use crate::scene::Scene;
+use crate::paint::BlendMode;
+use crate::scene::{Layer, Scene};
 use crate::tile_map::DenseTileMap;
 use crate::tiles::{self, TILE_HEIGHT, TILE_WIDTH, Tiler};
 use crate::z_buffer::ZBuffer;
 use pathfinder_geometry::basic::line_segment::{LineSegment2F, LineSegmentU4, LineSegmentU8};
 use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
 use pathfinder_geometry::basic::rect::{RectF, RectI};
+use pathfinder_geometry::fill::FillRule;
+use pathfinder_geometry::outline::Outline;
 use pathfinder_geometry::util;
 use pathfinder_simd::default::{F32x4, I32x4};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Instant;
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::u16;
 
+/// The number of alpha tiles that fit in the mask framebuffer at once. The last tile index is
+/// left out, since it marks tiles that have no mask.
+pub(crate) const MAX_ALPHA_TILES_PER_PAGE: u32 = u16::MAX as u32;
+
 pub(crate) struct SceneBuilder<'a> {
     scene: &'a Scene,
     built_options: &'a PreparedRenderOptions,
 
-    pub(crate) next_alpha_tile_index: AtomicUsize,
     pub(crate) z_buffer: ZBuffer,
     pub(crate) listener: Box<dyn RenderCommandListener>,
+    resolved_clip_paths: Option<ResolvedClipPaths>,
+    layer_parents: Vec<Option<u32>>,
+    cache_frame: CacheFrame,
+    profile: BuildProfile,
+}
+
+/// The time spent on an outline before its tiles are culled.
+#[derive(Clone, Copy, Default)]
+struct TilingTimes {
+    apply_render_options: Duration,
+    tiling: Duration,
 }
 
 impl<'a> SceneBuilder<'a> {
@@ -46,64 +67,253 @@ impl<'a> SceneBuilder<'a> {
             scene,
             built_options,
 
-            next_alpha_tile_index: AtomicUsize::new(0),
             z_buffer: ZBuffer::new(effective_view_box),
             listener,
+            resolved_clip_paths: None,
+            layer_parents: scene.layer_parents(),
+            cache_frame: scene.build_cache.begin(built_options, effective_view_box),
+            profile: BuildProfile::default(),
         }
     }
 
-    pub fn build<E>(&mut self, executor: &E) where E: Executor {
+    pub fn build<E>(mut self, executor: &E) where E: Executor + Sync {
         let start_time = Instant::now();
 
         let bounding_quad = self.built_options.bounding_quad();
         let path_count = self.scene.paths.len();
         self.listener.send(RenderCommand::Start { bounding_quad, path_count });
+        if executor.is_cancelled() {
+            return self.finish_without_tiles(start_time, None);
+        }
 
-        self.listener.send(RenderCommand::AddPaintData(self.scene.build_paint_data()));
+        let render_transform = self.built_options.transform_2d();
+        let (paint_data, image_data) = match self.scene.build_paint_data(&render_transform) {
+            Ok(paint_data) => paint_data,
+            Err(error) => return self.finish_without_tiles(start_time, Some(error)),
+        };
+        self.listener.send(RenderCommand::AddImageData(image_data));
+        self.listener.send(RenderCommand::AddPaintData(paint_data));
 
         let effective_view_box = self.scene.effective_view_box(self.built_options);
-        let alpha_tiles = executor.flatten_into_vector(path_count, |path_index| {
-            self.build_path(path_index, effective_view_box, &self.built_options, &self.scene)
+        let clip_path_count = self.scene.clip_paths.len();
+        // Jobs that find the build cancelled produce nothing, so the results only line up with
+        // the objects if the build is still going once all of them are done.
+        let clip_objects = executor.flatten_into_vector(clip_path_count, |clip_path_index| {
+            if executor.is_cancelled() {
+                return vec![];
+            }
+            vec![self.build_clip_path(clip_path_index,
+                                      effective_view_box,
+                                      self.scene,
+                                      executor)]
         });
+        if executor.is_cancelled() {
+            return self.finish_without_tiles(start_time, None);
+        }
 
-        self.finish_building(alpha_tiles);
+        // Alpha tiles are assigned to pages of the mask framebuffer in drawing order, clip paths
+        // first, once all the outlines have been tiled.
+        let mut pages = AlphaTilePages::new();
+        let mut built_clip_paths = Vec::with_capacity(clip_path_count);
+        for ((cached_object, tiling_times), clip_path) in
+                clip_objects.into_iter().zip(self.scene.clip_paths.iter()) {
+            self.profile.add_tiling_times(tiling_times);
+            let alpha_tiles = pages.allocate(&cached_object.object);
+            built_clip_paths.push(BuiltClipPath {
+                object: cached_object.object,
+                tile_offset: cached_object.tile_offset,
+                alpha_tiles,
+                fill_rule: clip_path.fill_rule(),
+            });
+        }
+        match ResolvedClipPaths::new(self.scene, &built_clip_paths, pages.fills.len()) {
+            Ok(resolved_clip_paths) => self.resolved_clip_paths = Some(resolved_clip_paths),
+            Err(error) => return self.finish_without_tiles(start_time, Some(error)),
+        }
 
-        let build_time = Instant::now() - start_time;
-        self.listener.send(RenderCommand::Finish { build_time });
+        // Paths that can't be in view aren't built at all, so they have no tiles.
+        let visible_paths = self.scene.paths_in_view(self.built_options);
+        let built_objects = executor.flatten_into_vector(visible_paths.len(), |visible_index| {
+            if executor.is_cancelled() {
+                return vec![];
+            }
+            vec![self.build_path(visible_paths[visible_index] as usize,
+                                 effective_view_box,
+                                 self.scene,
+                                 executor)]
+        });
+        if executor.is_cancelled() {
+            return self.finish_without_tiles(start_time, None);
+        }
+
+        let packing_start_time = Instant::now();
+        let (mut alpha_tiles, mut alpha_tile_pages) = (vec![], vec![]);
+        self.profile.paths = vec![PathStats::default(); path_count];
+        for (&path_index, (built_object, path_alpha_tiles, tiling_times, culling_time)) in
+                visible_paths.iter().zip(built_objects) {
+            self.profile.add_tiling_times(tiling_times);
+            self.profile.culling_time += culling_time;
+            let path_stats = &mut self.profile.paths[path_index as usize];
+            path_stats.fill_count = built_object.fills.len() as u32;
+            path_stats.tiling_time = tiling_times.apply_render_options + tiling_times.tiling;
+
+            let allocation = pages.allocate(&built_object);
+            relocate_alpha_tiles(path_alpha_tiles,
+                                 allocation,
+                                 &mut alpha_tiles,
+                                 &mut alpha_tile_pages);
+        }
+        self.profile.packing_time += Instant::now() - packing_start_time;
+
+        let resolved_clip_paths = self.resolved_clip_paths.take().unwrap();
+        self.finish_building(alpha_tiles, &alpha_tile_pages, pages, resolved_clip_paths);
+        self.scene.build_cache.end(self.cache_frame);
+
+        let mut build_time = Instant::now() - start_time;
+        let mut profile = mem::take(&mut self.profile);
+        if self.built_options.zero_timings {
+            build_time = Duration::default();
+            profile.clear_timings();
+        }
+        self.listener.send(RenderCommand::Finish {
+            build_time,
+            profile,
+            cancelled: false,
+            error: None,
+        });
     }
 
-    fn build_path(
+    /// Ends a build that was cancelled, or failed with the given error, before any tiles were
+    /// sent. The tiles built so far are kept for the next build.
+    fn finish_without_tiles(self, start_time: Instant, error: Option<BuildError>) {
+        self.scene.build_cache.end(self.cache_frame);
+
+        let mut build_time = Instant::now() - start_time;
+        if self.built_options.zero_timings {
+            build_time = Duration::default();
+        }
+        let profile = BuildProfile::default();
+        let cancelled = error.is_none();
+        self.listener.send(RenderCommand::Finish { build_time, profile, cancelled, error });
+    }
+
+    fn build_path<E>(
         &self,
         path_index: usize,
         view_box: RectF,
-        built_options: &PreparedRenderOptions,
         scene: &Scene,
-    ) -> Vec<AlphaTileBatchPrimitive> {
+        executor: &E,
+    ) -> (Arc<BuiltObject>, Vec<(AlphaTileBatchPrimitive, u32)>, TilingTimes, Duration)
+          where E: Executor {
         let path_object = &scene.paths[path_index];
-        let outline = scene.apply_render_options(path_object.outline(), built_options);
         let paint_id = path_object.paint();
-        let object_is_opaque = scene.paints[paint_id.0 as usize].is_opaque();
 
-        let mut tiler = Tiler::new(self,
-                                   &outline,
-                                   view_box,
-                                   path_index as u16,
-                                   paint_id,
-                                   object_is_opaque);
+        // Paths in layers can't occlude anything, since the layer may be translucent.
+        let in_layer = scene.innermost_layer(&self.layer_parents, path_index as u32).is_some();
+        // Neither can paths with other blend modes, since their result depends on what's beneath.
+        let object_is_opaque = scene.paints[paint_id.0 as usize].is_opaque() && !in_layer &&
+            path_object.blend_mode().is_source_over();
+
+        let clip_tiles = path_object.clip_path().map(|clip_path_id| {
+            &self.resolved_clip_paths.as_ref().unwrap().tiles[clip_path_id.0 as usize]
+        });
+
+        let (cached_object, tiling_times) = self.tile_outline(path_object.outline(),
+                                                              path_object.fill_rule(),
+                                                              scene.path_revisions[path_index],
+                                                              view_box,
+                                                              executor);
+        let culling_start_time = Instant::now();
+        let alpha_tiles = cached_object.object.pack_and_cull(cached_object.tile_offset,
+                                                             &self.z_buffer,
+                                                             path_index as u32,
+                                                             path_object,
+                                                             object_is_opaque,
+                                                             clip_tiles);
+        (cached_object.object, alpha_tiles, tiling_times, Instant::now() - culling_start_time)
+    }
+
+    fn build_clip_path<E>(
+        &self,
+        clip_path_index: usize,
+        view_box: RectF,
+        scene: &Scene,
+        executor: &E,
+    ) -> (CachedObject, TilingTimes) where E: Executor {
+        let clip_path = &scene.clip_paths[clip_path_index];
+        self.tile_outline(clip_path.outline(),
+                          clip_path.fill_rule(),
+                          scene.clip_path_revisions[clip_path_index],
+                          view_box,
+                          executor)
+    }
 
-        tiler.generate_tiles();
+    /// Tiles the outline of a path or clip path, or reuses its tiles from the last build if it
+    /// hasn't changed.
+    fn tile_outline<E>(
+        &self,
+        outline: &Outline,
+        fill_rule: FillRule,
+        revision: u64,
+        view_box: RectF,
+        executor: &E,
+    ) -> (CachedObject, TilingTimes) where E: Executor {
+        let mut tiling_times = TilingTimes::default();
+        let cached_object = match self.cache_frame.get(revision) {
+            Some(cached_object) => cached_object,
+            None => {
+                let start_time = Instant::now();
+                let prepared_outline = self.scene.apply_render_options(outline,
+                                                                       self.built_options);
+                let tiling_start_time = Instant::now();
+                let mut tiler = Tiler::new(&prepared_outline, view_box, fill_rule);
+                let finished = tiler.generate_tiles(executor);
+                tiling_times = TilingTimes {
+                    apply_render_options: tiling_start_time - start_time,
+                    tiling: Instant::now() - tiling_start_time,
+                };
+
+                let device_bounds = match self.cache_frame.transform() {
+                    Some(transform) => {
+                        let dilation = self.built_options.dilation;
+                        transform.transform_rect(&outline.bounds()).dilate(dilation)
+                    }
+                    None => RectF::default(),
+                };
+                let cached_object = CachedObject {
+                    object: Arc::new(tiler.built_object),
+                    tile_offset: Vector2I::default(),
+                    device_bounds,
+                };
+
+                // The tiles of an outline whose tiling was cut short by cancelling the build
+                // are unfinished, so they mustn't be reused.
+                if !finished {
+                    return (cached_object, tiling_times);
+                }
+                cached_object
+            }
+        };
+        self.cache_frame.retain(revision, cached_object.clone());
+        (cached_object, tiling_times)
+    }
 
-        self.listener.send(RenderCommand::AddFills(tiler.built_object.fills));
-        tiler.built_object.alpha_tiles
+    /// Sends the fills of one page of alpha tiles and draws them into the mask framebuffer.
+    fn send_fill_page(&self, fills: Vec<FillBatchPrimitive>) {
+        if !fills.is_empty() {
+            self.listener.send(RenderCommand::AddFills(fills));
+        }
+        self.listener.send(RenderCommand::FlushFills);
     }
 
-    fn cull_alpha_tiles(&self, alpha_tiles: &mut Vec<AlphaTileBatchPrimitive>) {
+    fn cull_alpha_tiles(&mut self, alpha_tiles: &mut Vec<AlphaTileBatchPrimitive>) {
         for alpha_tile in alpha_tiles {
             let alpha_tile_coords = alpha_tile.tile_coords();
             if self
                 .z_buffer
-                .test(alpha_tile_coords, alpha_tile.object_index as u32)
+                .test(alpha_tile_coords, alpha_tile.object_index)
             {
+                self.profile.paths[alpha_tile.object_index as usize].alpha_tile_count += 1;
                 continue;
             }
 
@@ -114,28 +324,291 @@ impl<'a> SceneBuilder<'a> {
         }
     }
 
-    fn pack_alpha_tiles(&mut self, alpha_tiles: Vec<AlphaTileBatchPrimitive>) {
+    fn pack_alpha_tiles(&mut self,
+                        alpha_tiles: Vec<AlphaTileBatchPrimitive>,
+                        alpha_tile_pages: &[u32],
+                        fill_pages: &mut dyn Iterator<Item = Vec<FillBatchPrimitive>>,
+                        mut current_page: u32,
+                        layer_commands: Vec<(u32, RenderCommand)>) {
         let path_count = self.scene.paths.len() as u32;
         let solid_tiles = self.z_buffer.build_solid_tiles(&self.scene.paths, 0..path_count);
+        for solid_tile in &solid_tiles {
+            self.profile.paths[solid_tile.object_index as usize].solid_tile_count += 1;
+        }
         if !solid_tiles.is_empty() {
             self.listener.send(RenderCommand::SolidTile(solid_tiles));
         }
-        if !alpha_tiles.is_empty() {
-            self.listener.send(RenderCommand::AlphaTile(alpha_tiles));
+
+        // Split the alpha tiles into batches wherever a layer starts or ends or the blend mode
+        // changes. Blend modes other than source-over read the destination, which is only
+        // up to date if no two tiles in the batch overlap, so those batches hold one path each.
+        // Batches also end wherever the tiles move on to the next page of the mask framebuffer,
+        // whose fills are then drawn over the last page's.
+        let mut layer_commands = layer_commands.into_iter().peekable();
+        let mut batch = vec![];
+        let mut batch_key = None;
+        for (alpha_tile, &page) in alpha_tiles.into_iter().zip(alpha_tile_pages) {
+            let object_index = alpha_tile.object_index;
+            let blend_mode = self.scene.paths[object_index as usize].blend_mode();
+            let key = (if blend_mode.is_source_over() { None } else { Some(object_index) },
+                       blend_mode);
+            while let Some(&(path_index, _)) = layer_commands.peek() {
+                if path_index > object_index {
+                    break;
+                }
+                self.flush_alpha_tiles(&mut batch, batch_key);
+                self.listener.send(layer_commands.next().unwrap().1);
+            }
+            while current_page < page {
+                self.flush_alpha_tiles(&mut batch, batch_key);
+                self.send_fill_page(fill_pages.next().unwrap());
+                current_page += 1;
+            }
+            if batch_key != Some(key) {
+                self.flush_alpha_tiles(&mut batch, batch_key);
+                batch_key = Some(key);
+            }
+            batch.push(alpha_tile);
+        }
+        self.flush_alpha_tiles(&mut batch, batch_key);
+        for (_, layer_command) in layer_commands {
+            self.listener.send(layer_command);
         }
     }
 
-    fn finish_building(&mut self, mut alpha_tiles: Vec<AlphaTileBatchPrimitive>) {
-        self.listener.send(RenderCommand::FlushFills);
+    fn flush_alpha_tiles(&self,
+                         batch: &mut Vec<AlphaTileBatchPrimitive>,
+                         batch_key: Option<(Option<u32>, BlendMode)>) {
+        if !batch.is_empty() {
+            let blend_mode = batch_key.map_or(BlendMode::SourceOver, |(_, blend_mode)| blend_mode);
+            self.listener.send(RenderCommand::AlphaTile(mem::take(batch), blend_mode));
+        }
+    }
+
+    /// Returns the `PushLayer` and `PopLayer` commands for the scene, each paired with the index
+    /// of the first path drawn after it.
+    fn build_layer_commands(&self,
+                            alpha_tiles: &[AlphaTileBatchPrimitive],
+                            clip_tiles: &[DenseTileMap<ClipTile>])
+                            -> Vec<(u32, RenderCommand)> {
+        // At each path index, layers are closed innermost first and then opened outermost first.
+        let mut events = vec![];
+        for (layer_index, layer) in self.scene.layers.iter().enumerate() {
+            let path_range = layer.path_range();
+            if path_range.start == path_range.end {
+                // Empty layers draw nothing.
+                continue;
+            }
+            events.push((path_range.start, true, layer_index as isize));
+            events.push((path_range.end, false, -(layer_index as isize)));
+        }
+        events.sort();
+
+        events.into_iter().map(|(path_index, is_push, order)| {
+            let command = if is_push {
+                RenderCommand::PushLayer
+            } else {
+                let layer = &self.scene.layers[-order as usize];
+                let composite_tiles = self.build_composite_tiles(layer, alpha_tiles, clip_tiles);
+                RenderCommand::PopLayer(composite_tiles, layer.blend_mode())
+            };
+            (path_index, command)
+        }).collect()
+    }
+
+    fn build_composite_tiles(&self,
+                             layer: &Layer,
+                             alpha_tiles: &[AlphaTileBatchPrimitive],
+                             clip_tiles: &[DenseTileMap<ClipTile>])
+                             -> Vec<CompositeTileBatchPrimitive> {
+        // Paths in layers never become solid tiles, so the alpha tiles of the layer's paths are
+        // exactly the tiles that the layer draws to. A layer that clears the backdrop also
+        // affects the tiles that it doesn't draw to, so it covers all of them.
+        let path_range = layer.path_range();
+        let view_box = self.scene.effective_view_box(self.built_options);
+        let clears_backdrop = layer.blend_mode().clears_backdrop();
+        let mut covered_tiles: DenseTileMap<bool> =
+            DenseTileMap::from_builder(|_| clears_backdrop,
+                                       tiles::round_rect_out_to_tile_bounds(view_box));
+        for alpha_tile in alpha_tiles {
+            if !path_range.contains(&alpha_tile.object_index) {
+                continue;
+            }
+            if let Some(tile_index) = covered_tiles.coords_to_index(alpha_tile.tile_coords()) {
+                covered_tiles.data[tile_index] = true;
+            }
+        }
+
+        let clip_tiles = layer.clip_path().map(|clip_path_id| &clip_tiles[clip_path_id.0 as usize]);
+        let opacity = f32::round(util::clamp(layer.opacity(), 0.0, 1.0) * 255.0) as u8;
+
+        let mut composite_tiles = vec![];
+        for (tile_index, &covered) in covered_tiles.data.iter().enumerate() {
+            let tile_coords = covered_tiles.index_to_coords(tile_index);
+            if !covered || !self.z_buffer.test(tile_coords, path_range.end - 1) {
+                continue;
+            }
+
+            let clip_tile_index = match ClipTile::at(clip_tiles, tile_coords) {
+                ClipTile::Empty => continue,
+                ClipTile::Full => !0,
+                ClipTile::Mask(clip_tile_index) => clip_tile_index,
+            };
+
+            composite_tiles.push(CompositeTileBatchPrimitive {
+                tile_x: tile_coords.x() as i16,
+                tile_y: tile_coords.y() as i16,
+                clip_tile_index,
+                opacity,
+                pad: 0,
+            });
+        }
+        composite_tiles
+    }
+
+    fn finish_building(&mut self,
+                       mut alpha_tiles: Vec<AlphaTileBatchPrimitive>,
+                       alpha_tile_pages: &[u32],
+                       pages: AlphaTilePages,
+                       mut resolved_clip_paths: ResolvedClipPaths) {
+        let start_time = Instant::now();
+
+        // The clip mask has to be finished before any tiles are drawn, so every page that clip
+        // paths use comes first.
+        let mut fill_pages = pages.fills.into_iter();
+        let clip_tile_pages = mem::take(&mut resolved_clip_paths.clip_tiles);
+        let clip_page_count = clip_tile_pages.len() as u32;
+        for clip_tiles in clip_tile_pages {
+            self.send_fill_page(fill_pages.next().unwrap());
+            if !clip_tiles.is_empty() {
+                self.listener.send(RenderCommand::ClipTiles(clip_tiles));
+            }
+        }
+
+        let layer_commands = self.build_layer_commands(&alpha_tiles, &resolved_clip_paths.tiles);
+        let culling_start_time = Instant::now();
         self.cull_alpha_tiles(&mut alpha_tiles);
-        self.pack_alpha_tiles(alpha_tiles);
+        let packing_start_time = Instant::now();
+        self.pack_alpha_tiles(alpha_tiles,
+                              alpha_tile_pages,
+                              &mut fill_pages,
+                              clip_page_count - 1,
+                              layer_commands);
+
+        self.profile.culling_time += packing_start_time - culling_start_time;
+        self.profile.packing_time += (culling_start_time - start_time) +
+            (Instant::now() - packing_start_time);
+    }
+}
+
+/// The fills of the alpha tiles in each page of the mask framebuffer.
+struct AlphaTilePages {
+    fills: Vec<Vec<FillBatchPrimitive>>,
+    /// The next free alpha tile, counting from the start of the first page.
+    next_alpha_tile_index: u32,
+}
+
+/// Where the alpha tiles of an object went in the mask framebuffer. They are numbered
+/// consecutively from the start of the first page, so an object with more alpha tiles than fit
+/// in a page carries on into the next.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AlphaTileAllocation {
+    first_alpha_tile_index: u32,
+}
+
+impl AlphaTilePages {
+    fn new() -> AlphaTilePages {
+        AlphaTilePages { fills: vec![vec![]], next_alpha_tile_index: 0 }
+    }
+
+    /// Finds room for the alpha tiles of the object and adds its fills to their pages. Objects
+    /// that fit in a page are never split, so they start a new page if they don't fit in the
+    /// current one; bigger objects start a new page and take as many as they need.
+    fn allocate(&mut self, built_object: &BuiltObject) -> AlphaTileAllocation {
+        let alpha_tile_count = built_object.alpha_tile_count;
+        let index_in_page = self.next_alpha_tile_index % MAX_ALPHA_TILES_PER_PAGE;
+        if index_in_page != 0 && index_in_page + alpha_tile_count > MAX_ALPHA_TILES_PER_PAGE {
+            self.next_alpha_tile_index += MAX_ALPHA_TILES_PER_PAGE - index_in_page;
+        }
+
+        let allocation = AlphaTileAllocation { first_alpha_tile_index: self.next_alpha_tile_index };
+        self.next_alpha_tile_index += alpha_tile_count;
+
+        // Make sure that the first page exists even if the object has no alpha tiles, since its
+        // solid tiles are drawn with it.
+        self.page_fills(allocation.first_page());
+        for &ObjectFill { px, subpx, alpha_tile_index } in &built_object.fills {
+            let (page, alpha_tile_index) = allocation.locate(alpha_tile_index);
+            self.page_fills(page).push(FillBatchPrimitive { px, subpx, alpha_tile_index });
+        }
+        allocation
+    }
+
+    fn page_fills(&mut self, page: u32) -> &mut Vec<FillBatchPrimitive> {
+        while self.fills.len() <= page as usize {
+            self.fills.push(vec![]);
+        }
+        &mut self.fills[page as usize]
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct TileStats {
-    pub solid_tile_count: u32,
-    pub alpha_tile_count: u32,
+impl AlphaTileAllocation {
+    #[inline]
+    fn first_page(self) -> u32 {
+        self.first_alpha_tile_index / MAX_ALPHA_TILES_PER_PAGE
+    }
+
+    /// Returns the page, and the index within it, of the object's alpha tile with the given
+    /// local index.
+    #[inline]
+    pub(crate) fn locate(self, local_alpha_tile_index: u32) -> (u32, u16) {
+        let alpha_tile_index = self.first_alpha_tile_index + local_alpha_tile_index;
+        (alpha_tile_index / MAX_ALPHA_TILES_PER_PAGE,
+         (alpha_tile_index % MAX_ALPHA_TILES_PER_PAGE) as u16)
+    }
+}
+
+/// Points the packed alpha tiles of an object, each paired with its alpha tile index local to
+/// the object, at where those alpha tiles went, and appends them along with their pages.
+///
+/// The tiles of an object never overlap, so those of an object that spans several pages are
+/// reordered to be drawn one page after another.
+fn relocate_alpha_tiles(packed_alpha_tiles: Vec<(AlphaTileBatchPrimitive, u32)>,
+                        allocation: AlphaTileAllocation,
+                        alpha_tiles: &mut Vec<AlphaTileBatchPrimitive>,
+                        alpha_tile_pages: &mut Vec<u32>) {
+    let mut relocated_alpha_tiles: Vec<_> =
+        packed_alpha_tiles.into_iter().map(|(mut alpha_tile, local_alpha_tile_index)| {
+            if local_alpha_tile_index == !0 {
+                return (allocation.first_page(), alpha_tile);
+            }
+            let (page, alpha_tile_index) = allocation.locate(local_alpha_tile_index);
+            alpha_tile.tile_index = alpha_tile_index;
+            (page, alpha_tile)
+        }).collect();
+    relocated_alpha_tiles.sort_by_key(|&(page, _)| page);
+
+    for (page, alpha_tile) in relocated_alpha_tiles {
+        alpha_tiles.push(alpha_tile);
+        alpha_tile_pages.push(page);
+    }
+}
+
+impl BuildProfile {
+    fn add_tiling_times(&mut self, tiling_times: TilingTimes) {
+        self.apply_render_options_time += tiling_times.apply_render_options;
+        self.tiling_time += tiling_times.tiling;
+    }
+
+    fn clear_timings(&mut self) {
+        self.apply_render_options_time = Duration::default();
+        self.tiling_time = Duration::default();
+        self.culling_time = Duration::default();
+        self.packing_time = Duration::default();
+        for path_stats in &mut self.paths {
+            path_stats.tiling_time = Duration::default();
+        }
+    }
 }
 
 // Utilities for built objects
@@ -145,10 +618,9 @@ impl BuiltObject {
         let tile_rect = tiles::round_rect_out_to_tile_bounds(bounds);
         let tiles = DenseTileMap::new(tile_rect);
         BuiltObject {
-            bounds,
             fills: vec![],
-            alpha_tiles: vec![],
             tiles,
+            alpha_tile_count: 0,
         }
     }
 
@@ -159,7 +631,6 @@ impl BuiltObject {
 
     fn add_fill(
         &mut self,
-        builder: &SceneBuilder,
         segment: &LineSegment2F,
         tile_coords: Vector2I,
     ) {
@@ -201,37 +672,31 @@ impl BuiltObject {
         }
 
         // Allocate global tile if necessary.
-        let alpha_tile_index = self.get_or_allocate_alpha_tile_index(builder, tile_coords);
+        let alpha_tile_index = self.get_or_allocate_alpha_tile_index(tile_coords);
 
         debug!("... OK, pushing");
-        self.fills.push(FillBatchPrimitive {
+        self.fills.push(ObjectFill {
             px,
             subpx,
             alpha_tile_index,
         });
     }
 
-    fn get_or_allocate_alpha_tile_index(
-        &mut self,
-        builder: &SceneBuilder,
-        tile_coords: Vector2I,
-    ) -> u16 {
+    fn get_or_allocate_alpha_tile_index(&mut self, tile_coords: Vector2I) -> u32 {
         let local_tile_index = self.tiles.coords_to_index_unchecked(tile_coords);
         let alpha_tile_index = self.tiles.data[local_tile_index].alpha_tile_index;
         if alpha_tile_index != !0 {
             return alpha_tile_index;
         }
 
-        let alpha_tile_index = builder
-            .next_alpha_tile_index
-            .fetch_add(1, Ordering::Relaxed) as u16;
+        let alpha_tile_index = self.alpha_tile_count;
+        self.alpha_tile_count += 1;
         self.tiles.data[local_tile_index].alpha_tile_index = alpha_tile_index;
         alpha_tile_index
     }
 
     pub(crate) fn add_active_fill(
         &mut self,
-        builder: &SceneBuilder,
         left: f32,
         right: f32,
         mut winding: i32,
@@ -256,7 +721,7 @@ impl BuiltObject {
         );
 
         while winding != 0 {
-            self.add_fill(builder, &segment, tile_coords);
+            self.add_fill(&segment, tile_coords);
             if winding < 0 {
                 winding += 1
             } else {
@@ -267,7 +732,6 @@ impl BuiltObject {
 
     pub(crate) fn generate_fill_primitives_for_line(
         &mut self,
-        builder: &SceneBuilder,
         mut segment: LineSegment2F,
         tile_y: i32,
     ) {
@@ -300,7 +764,7 @@ impl BuiltObject {
         for subsegment_tile_x in segment_tile_left..segment_tile_right {
             let (mut fill_from, mut fill_to) = (segment.from(), segment.to());
             let subsegment_tile_right =
-                ((i32::from(subsegment_tile_x) + 1) * TILE_HEIGHT as i32) as f32;
+                ((subsegment_tile_x + 1) * TILE_HEIGHT as i32) as f32;
             if subsegment_tile_right < segment_right {
                 let x = subsegment_tile_right;
                 let point = Vector2F::new(x, segment.solve_y_for_x(x));
@@ -315,7 +779,7 @@ impl BuiltObject {
 
             let fill_segment = LineSegment2F::new(fill_from, fill_to);
             let fill_tile_coords = Vector2I::new(subsegment_tile_x, tile_y);
-            self.add_fill(builder, &fill_segment, fill_tile_coords);
+            self.add_fill(&fill_segment, fill_tile_coords);
         }
     }
 
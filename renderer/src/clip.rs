@@ -0,0 +1,178 @@
+// pathfinder/renderer/src/clip.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Resolves clip paths, including nested ones, into per-tile clip masks.
+//!
+//! Clip paths are tiled like any other path, so their coverage ends up in the mask framebuffer.
+//! For each tile, the coverage of a clip path and of all the clip paths enclosing it is then
+//! multiplied together into a tile of the clip mask, which alpha tiles multiply their own
+//! coverage by. Since each clip path's coverage is only in the mask framebuffer while its page
+//! is, clip tiles are grouped by the page of the tile that they read from.
+
+use crate::builder::AlphaTileAllocation;
+use crate::gpu_data::{BuildError, BuiltObject, ClipTileBatchPrimitive, TileObjectPrimitive};
+use crate::scene::Scene;
+use crate::tile_map::DenseTileMap;
+use crate::tiles;
+use pathfinder_geometry::basic::rect::RectI;
+use pathfinder_geometry::basic::vector::Vector2I;
+use pathfinder_geometry::fill::FillRule;
+use std::sync::Arc;
+
+/// The number of tiles in the clip mask. The last tile index is left out, since it marks tiles
+/// that aren't clipped.
+pub(crate) const MAX_CLIP_TILES: u32 = u16::MAX as u32;
+
+/// How a clip path affects a single tile.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum ClipTile {
+    /// The tile is entirely outside the clip path, so nothing in it is drawn.
+    Empty,
+    /// The tile is entirely inside the clip path, so it is drawn as though unclipped.
+    Full,
+    /// The tile straddles the edge of the clip path. Its coverage is multiplied by this tile of
+    /// the clip mask.
+    Mask(u16),
+}
+
+/// The tiles of a single clip path, without regard to the clip paths enclosing it.
+pub(crate) struct BuiltClipPath {
+    pub(crate) object: Arc<BuiltObject>,
+    /// How many tiles the clip path has moved by since it was tiled.
+    pub(crate) tile_offset: Vector2I,
+    /// Where the alpha tiles of the clip path are in the mask framebuffer.
+    pub(crate) alpha_tiles: AlphaTileAllocation,
+    pub(crate) fill_rule: FillRule,
+}
+
+pub(crate) struct ResolvedClipPaths {
+    /// Indexed by clip path ID.
+    pub(crate) tiles: Vec<DenseTileMap<ClipTile>>,
+    /// Indexed by page.
+    pub(crate) clip_tiles: Vec<Vec<ClipTileBatchPrimitive>>,
+}
+
+impl Default for ClipTile {
+    #[inline]
+    fn default() -> ClipTile {
+        ClipTile::Empty
+    }
+}
+
+impl ClipTile {
+    /// Looks up the clip tile at the given tile coordinates, where `None` means unclipped.
+    pub(crate) fn at(clip_tiles: Option<&DenseTileMap<ClipTile>>, tile_coords: Vector2I)
+                     -> ClipTile {
+        match clip_tiles {
+            None => ClipTile::Full,
+            Some(clip_tiles) => {
+                match clip_tiles.coords_to_index(tile_coords) {
+                    None => ClipTile::Empty,
+                    Some(clip_tile_index) => clip_tiles.data[clip_tile_index],
+                }
+            }
+        }
+    }
+}
+
+impl BuiltClipPath {
+    fn tile_rect(&self) -> RectI {
+        let tile_rect = self.object.tiles.rect;
+        RectI::new(tile_rect.origin() + self.tile_offset, tile_rect.size())
+    }
+
+    /// Returns the tile of the clip path at the given tile coordinates.
+    fn tile(&self, tile_coords: Vector2I) -> TileObjectPrimitive {
+        let tiles = &self.object.tiles;
+        match tiles.coords_to_index(tile_coords - self.tile_offset) {
+            None => TileObjectPrimitive::default(),
+            Some(tile_index) => tiles.data[tile_index],
+        }
+    }
+}
+
+impl ResolvedClipPaths {
+    /// Fails if the clip paths need more tiles of the clip mask than it has.
+    pub(crate) fn new(scene: &Scene, built_clip_paths: &[BuiltClipPath], page_count: usize)
+                      -> Result<ResolvedClipPaths, BuildError> {
+        let mut resolved = ResolvedClipPaths {
+            tiles: vec![],
+            clip_tiles: vec![vec![]; page_count],
+        };
+        let mut next_clip_tile_index = 0;
+        let mut tile_clip_tiles = vec![];
+
+        for clip_path_index in 0..built_clip_paths.len() {
+            // Gather this clip path and all the ones that enclose it.
+            let mut chain = vec![&built_clip_paths[clip_path_index]];
+            let mut parent = scene.clip_paths[clip_path_index].clip_path();
+            while let Some(parent_id) = parent {
+                chain.push(&built_clip_paths[parent_id.0 as usize]);
+                parent = scene.clip_paths[parent_id.0 as usize].clip_path();
+            }
+
+            // Nothing outside the innermost clip path can be visible.
+            let tile_rect = built_clip_paths[clip_path_index].tile_rect();
+            let mut tiles = DenseTileMap::new(tile_rect);
+            for tile_index in 0..tiles.data.len() {
+                let tile_coords = tiles.index_to_coords(tile_index);
+                tiles.data[tile_index] = resolved.resolve_tile(&chain,
+                                                               tile_coords,
+                                                               &mut tile_clip_tiles,
+                                                               &mut next_clip_tile_index)?;
+            }
+            resolved.tiles.push(tiles);
+        }
+
+        Ok(resolved)
+    }
+
+    fn resolve_tile(&mut self,
+                    chain: &[&BuiltClipPath],
+                    tile_coords: Vector2I,
+                    tile_clip_tiles: &mut Vec<(u32, ClipTileBatchPrimitive)>,
+                    next_clip_tile_index: &mut u32)
+                    -> Result<ClipTile, BuildError> {
+        tile_clip_tiles.clear();
+        let dest_tile_index = *next_clip_tile_index as u16;
+
+        for built_clip_path in chain {
+            let tile = built_clip_path.tile(tile_coords);
+
+            if tile.is_solid() {
+                if tile.backdrop == 0 {
+                    return Ok(ClipTile::Empty);
+                }
+                continue;
+            }
+
+            let (page, src_tile_index) = built_clip_path.alpha_tiles.locate(tile.alpha_tile_index);
+            tile_clip_tiles.push((page, ClipTileBatchPrimitive {
+                dest_tile_index,
+                src_tile_index,
+                src_backdrop: tile.backdrop,
+                src_fill_rule: tiles::encode_fill_rule(built_clip_path.fill_rule),
+            }));
+        }
+
+        if tile_clip_tiles.is_empty() {
+            return Ok(ClipTile::Full);
+        }
+        if *next_clip_tile_index == MAX_CLIP_TILES {
+            return Err(BuildError::TooManyClipTiles);
+        }
+        for &(page, clip_tile) in tile_clip_tiles.iter() {
+            self.clip_tiles[page as usize].push(clip_tile);
+        }
+
+        *next_clip_tile_index += 1;
+        Ok(ClipTile::Mask(dest_tile_index))
+    }
+}
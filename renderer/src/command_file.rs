@@ -0,0 +1,738 @@
+// pathfinder/renderer/src/command_file.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recording the render commands of a build to a file and playing them back.
+//!
+//! A recording captures exactly what the scene builder sent to the renderer, so a rendering bug
+//! can be reproduced without the scene, and it can be told whether the builder or the renderer is
+//! at fault.
+//!
+//! A command file is the magic number `PFRC`, the format version as a little-endian `u32`, and
+//! then the commands one after another. Each command is a `u8` tag followed by its payload, in
+//! which numbers are little-endian and of fixed size, sequences are prefixed with a `u32` length,
+//! and primitives are their fields in declaration order. Readers reject files with a version
+//! other than `COMMAND_FILE_VERSION`.
+
+use crate::gpu::renderer::Renderer;
+use crate::gpu_data::{AlphaTileBatchPrimitive, BuildError, BuildProfile, ClipTileBatchPrimitive};
+use crate::gpu_data::{CompositeTileBatchPrimitive, FillBatchPrimitive, ImageData, PaintData};
+use crate::gpu_data::{PaintMetadata, PathStats, RenderCommand, SolidTileBatchPrimitive};
+use crate::options::RenderCommandListener;
+use crate::paint::BlendMode;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use pathfinder_geometry::basic::line_segment::{LineSegmentU4, LineSegmentU8};
+use pathfinder_geometry::basic::vector::{Vector2I, Vector4F};
+use pathfinder_gpu::Device;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The version of the command file format that this crate reads and writes.
+pub const COMMAND_FILE_VERSION: u32 = 1;
+
+const MAGIC: [u8; 4] = *b"PFRC";
+
+const TAG_START: u8 = 0;
+const TAG_ADD_IMAGE_DATA: u8 = 1;
+const TAG_ADD_PAINT_DATA: u8 = 2;
+const TAG_ADD_FILLS: u8 = 3;
+const TAG_FLUSH_FILLS: u8 = 4;
+const TAG_CLIP_TILES: u8 = 5;
+const TAG_ALPHA_TILE: u8 = 6;
+const TAG_SOLID_TILE: u8 = 7;
+const TAG_PUSH_LAYER: u8 = 8;
+const TAG_POP_LAYER: u8 = 9;
+const TAG_FINISH: u8 = 10;
+
+// How a build ended, in the payload of `TAG_FINISH`.
+const FINISH_DONE: u8 = 0;
+const FINISH_TOO_MANY_CLIP_TILES: u8 = 1;
+const FINISH_TOO_MANY_PAINTS: u8 = 2;
+const FINISH_IMAGES_TOO_LARGE: u8 = 3;
+const FINISH_CANCELLED: u8 = 4;
+
+#[derive(Debug)]
+pub enum CommandFileError {
+    Io(io::Error),
+    /// The file doesn't start with the command file magic number.
+    NotACommandFile,
+    /// The file was written by an incompatible version of Pathfinder.
+    UnsupportedVersion(u32),
+    /// The file couldn't be decoded.
+    Malformed(String),
+}
+
+impl Display for CommandFileError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            CommandFileError::Io(ref error) => write!(formatter, "I/O error: {}", error),
+            CommandFileError::NotACommandFile => write!(formatter, "not a command file"),
+            CommandFileError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported command file version {}", version)
+            }
+            CommandFileError::Malformed(ref message) => {
+                write!(formatter, "malformed command file: {}", message)
+            }
+        }
+    }
+}
+
+impl StdError for CommandFileError {}
+
+impl From<io::Error> for CommandFileError {
+    #[inline]
+    fn from(error: io::Error) -> CommandFileError {
+        CommandFileError::Io(error)
+    }
+}
+
+/// A render command listener that writes the commands it receives to a command file, and then
+/// passes them on to another listener, if there is one.
+///
+/// Clones share the same file, so a clone can be given to `Scene::build()` while this one is kept
+/// to call `finish()` afterward.
+pub struct CommandRecorder<W> where W: Write + Send {
+    state: Arc<Mutex<RecorderState<W>>>,
+    listener: Option<Arc<dyn RenderCommandListener>>,
+}
+
+struct RecorderState<W> {
+    writer: W,
+    // The first write error, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl<W> CommandRecorder<W> where W: Write + Send {
+    /// Creates a recorder, writing the file header right away.
+    pub fn new(mut writer: W) -> io::Result<CommandRecorder<W>> {
+        writer.write_all(&MAGIC)?;
+        writer.write_u32::<LittleEndian>(COMMAND_FILE_VERSION)?;
+        Ok(CommandRecorder {
+            state: Arc::new(Mutex::new(RecorderState { writer, error: None })),
+            listener: None,
+        })
+    }
+
+    /// Passes the commands on to the given listener once they're written.
+    pub fn with_listener(mut self, listener: Box<dyn RenderCommandListener>)
+                         -> CommandRecorder<W> {
+        self.listener = Some(Arc::from(listener));
+        self
+    }
+
+    /// Flushes the file and returns the first error that occurred while writing it, if any.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(error) = state.error.take() {
+            return Err(error);
+        }
+        state.writer.flush()
+    }
+}
+
+impl<W> Clone for CommandRecorder<W> where W: Write + Send {
+    fn clone(&self) -> CommandRecorder<W> {
+        CommandRecorder { state: self.state.clone(), listener: self.listener.clone() }
+    }
+}
+
+impl<W> RenderCommandListener for CommandRecorder<W> where W: Write + Send {
+    fn send(&self, command: RenderCommand) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.error.is_none() {
+                if let Err(error) = write_command(&mut state.writer, &command) {
+                    state.error = Some(error);
+                }
+            }
+        }
+        if let Some(ref listener) = self.listener {
+            listener.send(command);
+        }
+    }
+}
+
+/// Reads the commands of a command file back, in order.
+pub struct CommandPlayer<R> where R: Read {
+    reader: R,
+    done: bool,
+}
+
+impl<R> CommandPlayer<R> where R: Read {
+    /// Creates a player, checking the file header right away.
+    pub fn new(mut reader: R) -> Result<CommandPlayer<R>, CommandFileError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(CommandFileError::NotACommandFile);
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != COMMAND_FILE_VERSION {
+            return Err(CommandFileError::UnsupportedVersion(version));
+        }
+        Ok(CommandPlayer { reader, done: false })
+    }
+
+    /// Sends the commands of the next recorded build, up to and including its `Finish`, to the
+    /// renderer. Returns false if there are no builds left.
+    ///
+    /// As with commands from a scene build, the caller is responsible for calling
+    /// `begin_scene()` and `end_scene()` around them.
+    pub fn play<D>(&mut self, renderer: &mut Renderer<D>) -> Result<bool, CommandFileError>
+                   where D: Device {
+        let mut played = false;
+        for command in self.by_ref() {
+            let command = command?;
+            renderer.render_command(&command);
+            played = true;
+            if let RenderCommand::Finish { .. } = command {
+                break;
+            }
+        }
+        Ok(played)
+    }
+}
+
+/// A file may hold several builds one after another. Iteration ends at the end of the file or
+/// after the first error.
+impl<R> Iterator for CommandPlayer<R> where R: Read {
+    type Item = Result<RenderCommand, CommandFileError>;
+
+    fn next(&mut self) -> Option<Result<RenderCommand, CommandFileError>> {
+        if self.done {
+            return None;
+        }
+
+        let tag = match self.reader.read_u8() {
+            Ok(tag) => tag,
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                self.done = true;
+                return None;
+            }
+            Err(error) => {
+                self.done = true;
+                return Some(Err(error.into()));
+            }
+        };
+
+        let command = read_command(&mut self.reader, tag);
+        if command.is_err() {
+            self.done = true;
+        }
+        Some(command)
+    }
+}
+
+// Writing
+
+fn write_command<W>(writer: &mut W, command: &RenderCommand) -> io::Result<()> where W: Write {
+    match *command {
+        RenderCommand::Start { path_count, ref bounding_quad } => {
+            writer.write_u8(TAG_START)?;
+            writer.write_u64::<LittleEndian>(path_count as u64)?;
+            for point in bounding_quad {
+                for &value in &[point.x(), point.y(), point.z(), point.w()] {
+                    writer.write_f32::<LittleEndian>(value)?;
+                }
+            }
+        }
+        RenderCommand::AddImageData(ref image_data) => {
+            writer.write_u8(TAG_ADD_IMAGE_DATA)?;
+            write_vector_2i(writer, image_data.size)?;
+            write_bytes(writer, &image_data.texels)?;
+        }
+        RenderCommand::AddPaintData(ref paint_data) => {
+            writer.write_u8(TAG_ADD_PAINT_DATA)?;
+            write_vector_2i(writer, paint_data.size)?;
+            write_bytes(writer, &paint_data.texels)?;
+            write_len(writer, paint_data.metadata.len())?;
+            for metadata in &paint_data.metadata {
+                for &value in &metadata.to_f32_array() {
+                    writer.write_f32::<LittleEndian>(value)?;
+                }
+            }
+        }
+        RenderCommand::AddFills(ref fills) => {
+            writer.write_u8(TAG_ADD_FILLS)?;
+            write_len(writer, fills.len())?;
+            for fill in fills {
+                let (px, subpx, alpha_tile_index) = (fill.px, fill.subpx, fill.alpha_tile_index);
+                writer.write_u16::<LittleEndian>(px.0)?;
+                writer.write_u32::<LittleEndian>(subpx.0)?;
+                writer.write_u16::<LittleEndian>(alpha_tile_index)?;
+            }
+        }
+        RenderCommand::FlushFills => writer.write_u8(TAG_FLUSH_FILLS)?,
+        RenderCommand::ClipTiles(ref tiles) => {
+            writer.write_u8(TAG_CLIP_TILES)?;
+            write_len(writer, tiles.len())?;
+            for tile in tiles {
+                writer.write_u16::<LittleEndian>(tile.dest_tile_index)?;
+                writer.write_u16::<LittleEndian>(tile.src_tile_index)?;
+                writer.write_i8(tile.src_backdrop)?;
+                writer.write_u8(tile.src_fill_rule)?;
+            }
+        }
+        RenderCommand::AlphaTile(ref tiles, blend_mode) => {
+            writer.write_u8(TAG_ALPHA_TILE)?;
+            write_len(writer, tiles.len())?;
+            for tile in tiles {
+                writer.write_u8(tile.tile_x_lo)?;
+                writer.write_u8(tile.tile_y_lo)?;
+                writer.write_u8(tile.tile_hi)?;
+                writer.write_i8(tile.backdrop)?;
+                writer.write_u16::<LittleEndian>(tile.tile_index)?;
+                writer.write_u16::<LittleEndian>(tile.clip_tile_index)?;
+                writer.write_u32::<LittleEndian>(tile.paint_id)?;
+                writer.write_u32::<LittleEndian>(tile.object_index)?;
+                writer.write_u8(tile.fill_rule)?;
+            }
+            write_blend_mode(writer, blend_mode)?;
+        }
+        RenderCommand::SolidTile(ref tiles) => {
+            writer.write_u8(TAG_SOLID_TILE)?;
+            write_len(writer, tiles.len())?;
+            for tile in tiles {
+                writer.write_i16::<LittleEndian>(tile.tile_x)?;
+                writer.write_i16::<LittleEndian>(tile.tile_y)?;
+                writer.write_u32::<LittleEndian>(tile.paint_id)?;
+                writer.write_u32::<LittleEndian>(tile.object_index)?;
+            }
+        }
+        RenderCommand::PushLayer => writer.write_u8(TAG_PUSH_LAYER)?,
+        RenderCommand::PopLayer(ref tiles, blend_mode) => {
+            writer.write_u8(TAG_POP_LAYER)?;
+            write_len(writer, tiles.len())?;
+            for tile in tiles {
+                writer.write_i16::<LittleEndian>(tile.tile_x)?;
+                writer.write_i16::<LittleEndian>(tile.tile_y)?;
+                writer.write_u16::<LittleEndian>(tile.clip_tile_index)?;
+                writer.write_u8(tile.opacity)?;
+            }
+            write_blend_mode(writer, blend_mode)?;
+        }
+        RenderCommand::Finish { build_time, ref profile, cancelled, error } => {
+            writer.write_u8(TAG_FINISH)?;
+            write_duration(writer, build_time)?;
+            writer.write_u8(match (cancelled, error) {
+                (false, None) => FINISH_DONE,
+                (false, Some(BuildError::TooManyClipTiles)) => FINISH_TOO_MANY_CLIP_TILES,
+                (false, Some(BuildError::TooManyPaints)) => FINISH_TOO_MANY_PAINTS,
+                (false, Some(BuildError::ImagesTooLarge)) => FINISH_IMAGES_TOO_LARGE,
+                (true, _) => FINISH_CANCELLED,
+            })?;
+            write_duration(writer, profile.apply_render_options_time)?;
+            write_duration(writer, profile.tiling_time)?;
+            write_duration(writer, profile.culling_time)?;
+            write_duration(writer, profile.packing_time)?;
+            write_len(writer, profile.paths.len())?;
+            for path_stats in &profile.paths {
+                writer.write_u32::<LittleEndian>(path_stats.fill_count)?;
+                writer.write_u32::<LittleEndian>(path_stats.alpha_tile_count)?;
+                writer.write_u32::<LittleEndian>(path_stats.solid_tile_count)?;
+                write_duration(writer, path_stats.tiling_time)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_len<W>(writer: &mut W, len: usize) -> io::Result<()> where W: Write {
+    if len > u32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "sequence is too long"));
+    }
+    writer.write_u32::<LittleEndian>(len as u32)
+}
+
+fn write_bytes<W>(writer: &mut W, bytes: &[u8]) -> io::Result<()> where W: Write {
+    write_len(writer, bytes.len())?;
+    writer.write_all(bytes)
+}
+
+fn write_vector_2i<W>(writer: &mut W, vector: Vector2I) -> io::Result<()> where W: Write {
+    writer.write_i32::<LittleEndian>(vector.x())?;
+    writer.write_i32::<LittleEndian>(vector.y())
+}
+
+fn write_blend_mode<W>(writer: &mut W, blend_mode: BlendMode) -> io::Result<()> where W: Write {
+    writer.write_u8(blend_mode.to_shader_index() as u8)
+}
+
+fn write_duration<W>(writer: &mut W, duration: Duration) -> io::Result<()> where W: Write {
+    writer.write_u64::<LittleEndian>(duration.as_secs())?;
+    writer.write_u32::<LittleEndian>(duration.subsec_nanos())
+}
+
+// Reading
+
+fn read_command<R>(reader: &mut R, tag: u8) -> Result<RenderCommand, CommandFileError>
+                   where R: Read {
+    Ok(match tag {
+        TAG_START => {
+            let path_count = reader.read_u64::<LittleEndian>()? as usize;
+            let mut bounding_quad = [Vector4F::default(); 4];
+            for point in &mut bounding_quad {
+                let mut values = [0.0; 4];
+                reader.read_f32_into::<LittleEndian>(&mut values)?;
+                *point = Vector4F::new(values[0], values[1], values[2], values[3]);
+            }
+            RenderCommand::Start { path_count, bounding_quad }
+        }
+        TAG_ADD_IMAGE_DATA => {
+            let (size, texels) = read_texels(reader)?;
+            RenderCommand::AddImageData(ImageData { size, texels })
+        }
+        TAG_ADD_PAINT_DATA => {
+            let (size, texels) = read_texels(reader)?;
+            let metadata = read_seq(reader, |reader| {
+                let mut values = [0.0; 16];
+                reader.read_f32_into::<LittleEndian>(&mut values)?;
+                Ok(PaintMetadata {
+                    color_u: values[0],
+                    color_v: values[1],
+                    kind: values[2],
+                    spread: values[3],
+                    transform: [values[4], values[5], values[6], values[7], values[8], values[9]],
+                    radii: [values[10], values[11]],
+                    line: [values[12], values[13], values[14], values[15]],
+                })
+            })?;
+            RenderCommand::AddPaintData(PaintData { size, texels, metadata })
+        }
+        TAG_ADD_FILLS => {
+            RenderCommand::AddFills(read_seq(reader, |reader| {
+                Ok(FillBatchPrimitive {
+                    px: LineSegmentU4(reader.read_u16::<LittleEndian>()?),
+                    subpx: LineSegmentU8(reader.read_u32::<LittleEndian>()?),
+                    alpha_tile_index: reader.read_u16::<LittleEndian>()?,
+                })
+            })?)
+        }
+        TAG_FLUSH_FILLS => RenderCommand::FlushFills,
+        TAG_CLIP_TILES => {
+            RenderCommand::ClipTiles(read_seq(reader, |reader| {
+                Ok(ClipTileBatchPrimitive {
+                    dest_tile_index: reader.read_u16::<LittleEndian>()?,
+                    src_tile_index: reader.read_u16::<LittleEndian>()?,
+                    src_backdrop: reader.read_i8()?,
+                    src_fill_rule: reader.read_u8()?,
+                })
+            })?)
+        }
+        TAG_ALPHA_TILE => {
+            let tiles = read_seq(reader, |reader| {
+                Ok(AlphaTileBatchPrimitive {
+                    tile_x_lo: reader.read_u8()?,
+                    tile_y_lo: reader.read_u8()?,
+                    tile_hi: reader.read_u8()?,
+                    backdrop: reader.read_i8()?,
+                    tile_index: reader.read_u16::<LittleEndian>()?,
+                    clip_tile_index: reader.read_u16::<LittleEndian>()?,
+                    paint_id: reader.read_u32::<LittleEndian>()?,
+                    object_index: reader.read_u32::<LittleEndian>()?,
+                    fill_rule: reader.read_u8()?,
+                    pad: [0; 3],
+                })
+            })?;
+            RenderCommand::AlphaTile(tiles, read_blend_mode(reader)?)
+        }
+        TAG_SOLID_TILE => {
+            RenderCommand::SolidTile(read_seq(reader, |reader| {
+                Ok(SolidTileBatchPrimitive {
+                    tile_x: reader.read_i16::<LittleEndian>()?,
+                    tile_y: reader.read_i16::<LittleEndian>()?,
+                    paint_id: reader.read_u32::<LittleEndian>()?,
+                    object_index: reader.read_u32::<LittleEndian>()?,
+                })
+            })?)
+        }
+        TAG_PUSH_LAYER => RenderCommand::PushLayer,
+        TAG_POP_LAYER => {
+            let tiles = read_seq(reader, |reader| {
+                Ok(CompositeTileBatchPrimitive {
+                    tile_x: reader.read_i16::<LittleEndian>()?,
+                    tile_y: reader.read_i16::<LittleEndian>()?,
+                    clip_tile_index: reader.read_u16::<LittleEndian>()?,
+                    opacity: reader.read_u8()?,
+                    pad: 0,
+                })
+            })?;
+            RenderCommand::PopLayer(tiles, read_blend_mode(reader)?)
+        }
+        TAG_FINISH => {
+            let build_time = read_duration(reader)?;
+            let (cancelled, error) = match reader.read_u8()? {
+                FINISH_DONE => (false, None),
+                FINISH_TOO_MANY_CLIP_TILES => (false, Some(BuildError::TooManyClipTiles)),
+                FINISH_TOO_MANY_PAINTS => (false, Some(BuildError::TooManyPaints)),
+                FINISH_IMAGES_TOO_LARGE => (false, Some(BuildError::ImagesTooLarge)),
+                FINISH_CANCELLED => (true, None),
+                value => {
+                    let message = format!("invalid build outcome {}", value);
+                    return Err(CommandFileError::Malformed(message));
+                }
+            };
+            let apply_render_options_time = read_duration(reader)?;
+            let tiling_time = read_duration(reader)?;
+            let culling_time = read_duration(reader)?;
+            let packing_time = read_duration(reader)?;
+            let paths = read_seq(reader, |reader| {
+                Ok(PathStats {
+                    fill_count: reader.read_u32::<LittleEndian>()?,
+                    alpha_tile_count: reader.read_u32::<LittleEndian>()?,
+                    solid_tile_count: reader.read_u32::<LittleEndian>()?,
+                    tiling_time: read_duration(reader)?,
+                })
+            })?;
+            let profile = BuildProfile {
+                apply_render_options_time,
+                tiling_time,
+                culling_time,
+                packing_time,
+                paths,
+            };
+            RenderCommand::Finish { build_time, profile, cancelled, error }
+        }
+        _ => return Err(CommandFileError::Malformed(format!("unknown command tag {}", tag))),
+    })
+}
+
+// The initial capacity is capped so that a corrupt length can't make us allocate without bound.
+fn read_seq<R, T, F>(reader: &mut R, mut read_element: F) -> Result<Vec<T>, CommandFileError>
+                     where R: Read, F: FnMut(&mut R) -> Result<T, CommandFileError> {
+    let len = reader.read_u32::<LittleEndian>()? as usize;
+    let mut elements = Vec::with_capacity(usize::min(len, 4096));
+    for _ in 0..len {
+        elements.push(read_element(reader)?);
+    }
+    Ok(elements)
+}
+
+fn read_bytes<R>(reader: &mut R) -> Result<Vec<u8>, CommandFileError> where R: Read {
+    let len = reader.read_u32::<LittleEndian>()? as u64;
+    let mut bytes = vec![];
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated byte array").into());
+    }
+    Ok(bytes)
+}
+
+// Reads the size of an RGBA8 texture and then its texels, which the renderer uploads as is, so
+// there must be exactly as many as the size calls for.
+fn read_texels<R>(reader: &mut R) -> Result<(Vector2I, Vec<u8>), CommandFileError>
+                  where R: Read {
+    let size = read_vector_2i(reader)?;
+    if size.x() < 0 || size.y() < 0 {
+        let message = format!("negative texture size {}x{}", size.x(), size.y());
+        return Err(CommandFileError::Malformed(message));
+    }
+    let texels = read_bytes(reader)?;
+    if texels.len() as u64 != size.x() as u64 * size.y() as u64 * 4 {
+        let message = format!("{} bytes of texels for a {}x{} texture",
+                              texels.len(),
+                              size.x(),
+                              size.y());
+        return Err(CommandFileError::Malformed(message));
+    }
+    Ok((size, texels))
+}
+
+fn read_vector_2i<R>(reader: &mut R) -> Result<Vector2I, CommandFileError> where R: Read {
+    let x = reader.read_i32::<LittleEndian>()?;
+    let y = reader.read_i32::<LittleEndian>()?;
+    Ok(Vector2I::new(x, y))
+}
+
+fn read_blend_mode<R>(reader: &mut R) -> Result<BlendMode, CommandFileError> where R: Read {
+    let index = reader.read_u8()?;
+    BlendMode::from_shader_index(index as i32).ok_or_else(|| {
+        CommandFileError::Malformed(format!("unknown blend mode {}", index))
+    })
+}
+
+fn read_duration<R>(reader: &mut R) -> Result<Duration, CommandFileError> where R: Read {
+    let secs = reader.read_u64::<LittleEndian>()?;
+    let nanos = reader.read_u32::<LittleEndian>()?;
+    if nanos >= 1_000_000_000 {
+        return Err(CommandFileError::Malformed(format!("invalid duration nanos {}", nanos)));
+    }
+    Ok(Duration::new(secs, nanos))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{COMMAND_FILE_VERSION, CommandFileError, CommandPlayer, CommandRecorder};
+    use super::TAG_ADD_IMAGE_DATA;
+    use crate::concurrent::executor::{Executor, SequentialExecutor};
+    use crate::concurrent::rayon::RayonExecutor;
+    use crate::cpu::renderer::SoftwareRenderer;
+    use crate::gpu_data::RenderCommand;
+    use crate::options::RenderOptions;
+    use crate::paint::{BlendMode, Paint};
+    use crate::scene::{ClipPath, Layer, PathObject, Scene};
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use rayon::ThreadPoolBuilder;
+    use std::sync::{Arc, Mutex};
+
+    fn rect_outline(rect: RectF) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(rect.origin());
+        contour.push_endpoint(rect.upper_right());
+        contour.push_endpoint(rect.lower_right());
+        contour.push_endpoint(rect.lower_left());
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+        let red = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 255 }));
+        let blue = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 0, b: 255, a: 128 }));
+
+        let clip_path = scene.push_clip_path(ClipPath::new(
+            rect_outline(RectF::new(Vector2F::default(), Vector2F::new(40.0, 64.0))),
+            String::new()));
+        let mut path = PathObject::new(
+            rect_outline(RectF::new(Vector2F::splat(4.0), Vector2F::splat(50.5))),
+            red,
+            String::new());
+        path.set_clip_path(Some(clip_path));
+        scene.push_path(path);
+
+        let mut layer = Layer::new(String::new());
+        layer.set_opacity(0.5);
+        scene.push_layer(layer);
+        let mut path = PathObject::new(
+            rect_outline(RectF::new(Vector2F::splat(20.0), Vector2F::splat(30.0))),
+            blue,
+            String::new());
+        path.set_blend_mode(BlendMode::Multiply);
+        scene.push_path(path);
+        scene.pop_layer();
+        scene
+    }
+
+    fn render(commands: &[RenderCommand]) -> Vec<u8> {
+        let mut renderer = SoftwareRenderer::new(Vector2I::splat(64));
+        renderer.begin_scene();
+        for command in commands {
+            renderer.render_command(command);
+        }
+        renderer.end_scene();
+        renderer.pixels().to_vec()
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        let commands = Arc::new(Mutex::new(vec![]));
+        let listener_commands = commands.clone();
+        let listener = move |command: RenderCommand| {
+            listener_commands.lock().unwrap().push(command)
+        };
+
+        let recorder = CommandRecorder::new(vec![]).unwrap().with_listener(Box::new(listener));
+        let scene = scene();
+        scene.build(RenderOptions::default(), Box::new(recorder.clone()), &SequentialExecutor);
+        scene.build(RenderOptions::default(), Box::new(recorder.clone()), &SequentialExecutor);
+        recorder.finish().unwrap();
+
+        let file = recorder.state.lock().unwrap().writer.clone();
+        let played: Vec<RenderCommand> =
+            CommandPlayer::new(&file[..]).unwrap().map(Result::unwrap).collect();
+        let commands = commands.lock().unwrap();
+        assert_eq!(played.len(), commands.len());
+        assert_eq!(format!("{:?}", played), format!("{:?}", *commands));
+
+        // Each build renders the same as it did the first time.
+        let first_build_len = commands.iter().position(|command| {
+            matches!(*command, RenderCommand::Finish { .. })
+        }).unwrap() + 1;
+        assert_eq!(render(&played[..first_build_len]), render(&commands[..first_build_len]));
+        assert_eq!(render(&played[first_build_len..]), render(&commands[first_build_len..]));
+    }
+
+    fn record<E>(scene: &Scene, executor: &E) -> Vec<u8> where E: Executor + Sync {
+        let options = RenderOptions { zero_timings: true, ..RenderOptions::default() };
+        let recorder = CommandRecorder::new(vec![]).unwrap();
+        scene.build(options, Box::new(recorder.clone()), executor);
+        recorder.finish().unwrap();
+        let file = recorder.state.lock().unwrap().writer.clone();
+        file
+    }
+
+    #[test]
+    fn test_deterministic_builds() {
+        let mut scene = scene();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        for index in 0..256 {
+            let origin = Vector2F::new((index % 16) as f32 * 3.7, (index / 16) as f32 * 3.3);
+            let rect = RectF::new(origin, Vector2F::new(7.5, 5.25));
+            scene.push_path(PathObject::new(rect_outline(rect), paint, String::new()));
+        }
+
+        let expected = record(&scene, &SequentialExecutor);
+        for &thread_count in &[1, 2, 4, 8] {
+            let thread_pool = ThreadPoolBuilder::new().num_threads(thread_count).build().unwrap();
+            let file = thread_pool.install(|| record(&scene, &RayonExecutor));
+            assert!(file == expected, "build differs with {} threads", thread_count);
+        }
+    }
+
+    #[test]
+    fn test_reject_other_files() {
+        let mut file = vec![];
+        scene().write_binary(&mut file).unwrap();
+        match CommandPlayer::new(&file[..]) {
+            Err(CommandFileError::NotACommandFile) => {}
+            _ => panic!("expected a command file error"),
+        }
+
+        let mut file = b"PFRC".to_vec();
+        file.extend_from_slice(&[2, 0, 0, 0]);
+        match CommandPlayer::new(&file[..]) {
+            Err(CommandFileError::UnsupportedVersion(2)) => {}
+            _ => panic!("expected a version error"),
+        }
+
+        file[4] = 1;
+        file.push(0xff);
+        let mut player = CommandPlayer::new(&file[..]).unwrap();
+        match player.next() {
+            Some(Err(CommandFileError::Malformed(_))) => {}
+            _ => panic!("expected a malformed command"),
+        }
+        assert!(player.next().is_none());
+
+        // Textures must be exactly as big as they say they are.
+        for &(size, texel_count) in &[((-1, 2), 0), ((2, 2), 15), ((2, 2), 17)] {
+            let mut file = b"PFRC".to_vec();
+            file.write_u32::<LittleEndian>(COMMAND_FILE_VERSION).unwrap();
+            file.push(TAG_ADD_IMAGE_DATA);
+            file.write_i32::<LittleEndian>(size.0).unwrap();
+            file.write_i32::<LittleEndian>(size.1).unwrap();
+            file.write_u32::<LittleEndian>(texel_count).unwrap();
+            file.resize(file.len() + texel_count as usize, 0);
+            match CommandPlayer::new(&file[..]).unwrap().next() {
+                Some(Err(CommandFileError::Malformed(_))) => {}
+                _ => panic!("expected a malformed texture"),
+            }
+        }
+    }
+}
@@ -10,13 +10,27 @@
 
 //! An abstraction over threading and parallelism systems such as Rayon.
 
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 /// An abstraction over threading and parallelism systems such as Rayon.
 pub trait Executor {
     /// Like the Rayon snippet:
     ///
     ///     (0..length).into_par_iter().flat_map(builder).collect()
+    ///
+    /// The results must be in index order however the work is divided up, so that the scene
+    /// builder produces the same commands with every executor.
     fn flatten_into_vector<T, F>(&self, length: usize, builder: F) -> Vec<T>
                                  where T: Send, F: Fn(usize) -> Vec<T> + Send + Sync;
+
+    /// Returns true once the work being done on this executor should be abandoned. Jobs check
+    /// this from time to time so that they can stop early. Once this returns true, it must keep
+    /// doing so.
+    #[inline]
+    fn is_cancelled(&self) -> bool {
+        false
+    }
 }
 
 pub struct SequentialExecutor;
@@ -27,3 +41,52 @@ impl Executor for SequentialExecutor {
         (0..length).into_iter().flat_map(builder).collect()
     }
 }
+
+/// A flag that asks the work it's handed to to stop early. Clones share the flag.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    #[inline]
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs jobs on another executor, reporting them as cancelled once the token is.
+pub struct CancellableExecutor<'a, E> where E: Executor {
+    executor: &'a E,
+    token: CancellationToken,
+}
+
+impl<'a, E> CancellableExecutor<'a, E> where E: Executor {
+    #[inline]
+    pub fn new(executor: &'a E, token: CancellationToken) -> CancellableExecutor<'a, E> {
+        CancellableExecutor { executor, token }
+    }
+}
+
+impl<'a, E> Executor for CancellableExecutor<'a, E> where E: Executor {
+    #[inline]
+    fn flatten_into_vector<T, F>(&self, length: usize, builder: F) -> Vec<T>
+                                 where T: Send, F: Fn(usize) -> Vec<T> + Send + Sync {
+        self.executor.flatten_into_vector(length, builder)
+    }
+
+    #[inline]
+    fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled() || self.executor.is_cancelled()
+    }
+}
@@ -19,13 +19,16 @@
 //!
 //! You don't need to use this API to use Pathfinder; it's only a convenience.
 
-use crate::concurrent::executor::Executor;
+use crate::concurrent::executor::{CancellableExecutor, CancellationToken, Executor};
 use crate::gpu::renderer::Renderer;
 use crate::gpu_data::RenderCommand;
 use crate::options::{RenderCommandListener, RenderOptions};
-use crate::scene::Scene;
+use crate::paint::{Paint, PaintId};
+use crate::scene::{PathObject, Scene};
 use pathfinder_geometry::basic::rect::RectF;
 use pathfinder_gpu::Device;
+use std::mem;
+use std::sync::Mutex;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
 
@@ -33,23 +36,28 @@ const MAX_MESSAGES_IN_FLIGHT: usize = 1024;
 
 pub struct SceneProxy {
     sender: Sender<MainToWorkerMsg>,
+    // The token of the last build requested.
+    last_build: Mutex<CancellationToken>,
 }
 
 impl SceneProxy {
-    pub fn new<E>(executor: E) -> SceneProxy where E: Executor + Send + 'static {
+    pub fn new<E>(executor: E) -> SceneProxy where E: Executor + Send + Sync + 'static {
         SceneProxy::from_scene(Scene::new(), executor)
     }
 
     pub fn from_scene<E>(scene: Scene, executor: E) -> SceneProxy
-                         where E: Executor + Send + 'static {
+                         where E: Executor + Send + Sync + 'static {
         let (main_to_worker_sender, main_to_worker_receiver) = mpsc::channel();
         thread::spawn(move || scene_thread(scene, executor, main_to_worker_receiver));
-        SceneProxy { sender: main_to_worker_sender }
+        SceneProxy {
+            sender: main_to_worker_sender,
+            last_build: Mutex::new(CancellationToken::new()),
+        }
     }
 
     #[inline]
     pub fn replace_scene(&self, new_scene: Scene) {
-        self.sender.send(MainToWorkerMsg::ReplaceScene(new_scene)).unwrap();
+        self.sender.send(MainToWorkerMsg::ReplaceScene(Box::new(new_scene))).unwrap();
     }
 
     #[inline]
@@ -58,18 +66,89 @@ impl SceneProxy {
     }
 
     #[inline]
+    pub fn push_path(&self, path: PathObject) {
+        self.sender.send(MainToWorkerMsg::PushPath(path)).unwrap();
+    }
+
+    /// See `Scene::remove_path()`.
+    #[inline]
+    pub fn remove_path(&self, index: usize) {
+        self.sender.send(MainToWorkerMsg::RemovePath(index)).unwrap();
+    }
+
+    /// See `Scene::replace_path()`.
+    #[inline]
+    pub fn replace_path(&self, index: usize, path: PathObject) {
+        self.sender.send(MainToWorkerMsg::ReplacePath(index, path)).unwrap();
+    }
+
+    /// See `Scene::move_path()`.
+    #[inline]
+    pub fn move_path(&self, from: usize, to: usize) {
+        self.sender.send(MainToWorkerMsg::MovePath(from, to)).unwrap();
+    }
+
+    /// Adds a paint to the scene, waiting for the scene thread to hand out its ID.
+    pub fn push_paint(&self, paint: &Paint) -> PaintId {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.send(MainToWorkerMsg::PushPaint((*paint).clone(), sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    /// See `Scene::replace_paint()`.
+    #[inline]
+    pub fn replace_paint(&self, paint_id: PaintId, new_paint: &Paint) {
+        self.sender.send(MainToWorkerMsg::ReplacePaint(paint_id, (*new_paint).clone())).unwrap();
+    }
+
+    /// Returns the bounds of the scene once the messages sent before this one have been handled.
+    pub fn bounds(&self) -> RectF {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.send(MainToWorkerMsg::GetBounds(sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    /// Returns the number of paths in the scene once the messages sent before this one have been
+    /// handled.
+    pub fn path_count(&self) -> usize {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.send(MainToWorkerMsg::GetPathCount(sender)).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    /// Queues a build of the scene as it will be once the messages sent before this one have
+    /// been handled.
+    ///
+    /// The new build supersedes any earlier one that hasn't finished yet, which is cancelled, so
+    /// that the scene thread doesn't fall behind when builds are requested faster than it can
+    /// finish them. The build can also be cancelled with the returned handle. Cancelled builds
+    /// stop as soon as they notice, and end with a `Finish` command that says so.
     pub fn build_with_listener(&self,
                                options: RenderOptions,
-                               listener: Box<dyn RenderCommandListener>) {
-        self.sender.send(MainToWorkerMsg::Build(options, listener)).unwrap();
+                               listener: Box<dyn RenderCommandListener>)
+                               -> BuildHandle {
+        let token = CancellationToken::new();
+        mem::replace(&mut *self.last_build.lock().unwrap(), token.clone()).cancel();
+        self.sender.send(MainToWorkerMsg::Build(options, listener, token.clone())).unwrap();
+        BuildHandle { token }
     }
 
+    /// Like `build_with_listener()`, but returns the commands as a stream. Dropping the stream
+    /// before the build finishes cancels the build.
     #[inline]
     pub fn build_with_stream(&self, options: RenderOptions) -> RenderCommandStream {
         let (sender, receiver) = mpsc::sync_channel(MAX_MESSAGES_IN_FLIGHT);
-        let listener = Box::new(move |command| sender.send(command).unwrap());
-        self.build_with_listener(options, listener);
-        RenderCommandStream::new(receiver)
+        // Nobody is listening anymore if the stream was dropped, which is fine.
+        let listener = Box::new(move |command| drop(sender.send(command)));
+        let handle = self.build_with_listener(options, listener);
+        RenderCommandStream::new(receiver, handle)
+    }
+
+    /// Cancels every build requested so far that hasn't finished yet. Since each build cancels
+    /// the ones before it, only the last one can still be going.
+    #[inline]
+    pub fn cancel_builds(&self) {
+        self.last_build.lock().unwrap().cancel()
     }
 
     /// A convenience method to build a scene and send the resulting commands
@@ -95,41 +174,102 @@ impl SceneProxy {
         self.sender.send(MainToWorkerMsg::GetSVG(sender)).unwrap();
         receiver.recv().unwrap()
     }
+
+    pub fn as_pdf(&self) -> Vec<u8> {
+        let (sender, receiver) = mpsc::channel();
+        self.sender.send(MainToWorkerMsg::GetPDF(sender)).unwrap();
+        receiver.recv().unwrap()
+    }
 }
 
 fn scene_thread<E>(mut scene: Scene,
                    executor: E,
                    main_to_worker_receiver: Receiver<MainToWorkerMsg>)
-                   where E: Executor {
+                   where E: Executor + Sync {
     while let Ok(msg) = main_to_worker_receiver.recv() {
         match msg {
-            MainToWorkerMsg::ReplaceScene(new_scene) => scene = new_scene,
+            MainToWorkerMsg::ReplaceScene(new_scene) => scene = *new_scene,
             MainToWorkerMsg::SetViewBox(new_view_box) => scene.set_view_box(new_view_box),
-            MainToWorkerMsg::Build(options, listener) => scene.build(options, listener, &executor),
+            MainToWorkerMsg::PushPath(path) => scene.push_path(path),
+            MainToWorkerMsg::RemovePath(index) => drop(scene.remove_path(index)),
+            MainToWorkerMsg::ReplacePath(index, path) => drop(scene.replace_path(index, path)),
+            MainToWorkerMsg::MovePath(from, to) => scene.move_path(from, to),
+            MainToWorkerMsg::PushPaint(paint, sender) => {
+                sender.send(scene.push_paint(&paint)).unwrap()
+            }
+            MainToWorkerMsg::ReplacePaint(paint_id, new_paint) => {
+                drop(scene.replace_paint(paint_id, &new_paint))
+            }
+            MainToWorkerMsg::GetBounds(sender) => sender.send(scene.bounds()).unwrap(),
+            MainToWorkerMsg::GetPathCount(sender) => sender.send(scene.path_count()).unwrap(),
+            MainToWorkerMsg::Build(options, listener, token) => {
+                scene.build(options, listener, &CancellableExecutor::new(&executor, token))
+            }
             MainToWorkerMsg::GetSVG(sender) => {
                 let mut bytes = vec![];
                 scene.write_svg(&mut bytes).unwrap();
                 sender.send(bytes).unwrap();
             }
+            MainToWorkerMsg::GetPDF(sender) => {
+                let mut bytes = vec![];
+                scene.write_pdf(&mut bytes).unwrap();
+                sender.send(bytes).unwrap();
+            }
         }
     }
 }
 
 enum MainToWorkerMsg {
-    ReplaceScene(Scene),
+    ReplaceScene(Box<Scene>),
     SetViewBox(RectF),
-    Build(RenderOptions, Box<dyn RenderCommandListener>),
+    PushPath(PathObject),
+    RemovePath(usize),
+    ReplacePath(usize, PathObject),
+    MovePath(usize, usize),
+    PushPaint(Paint, Sender<PaintId>),
+    ReplacePaint(PaintId, Paint),
+    GetBounds(Sender<RectF>),
+    GetPathCount(Sender<usize>),
+    Build(RenderOptions, Box<dyn RenderCommandListener>, CancellationToken),
     GetSVG(Sender<Vec<u8>>),
+    GetPDF(Sender<Vec<u8>>),
+}
+
+/// Cancels a build requested from a `SceneProxy`.
+#[derive(Clone)]
+pub struct BuildHandle {
+    token: CancellationToken,
+}
+
+impl BuildHandle {
+    #[inline]
+    pub fn cancel(&self) {
+        self.token.cancel()
+    }
 }
 
 pub struct RenderCommandStream {
     receiver: Receiver<RenderCommand>,
+    handle: BuildHandle,
     done: bool,
 }
 
 impl RenderCommandStream {
-    fn new(receiver: Receiver<RenderCommand>) -> RenderCommandStream {
-        RenderCommandStream { receiver, done: false }
+    fn new(receiver: Receiver<RenderCommand>, handle: BuildHandle) -> RenderCommandStream {
+        RenderCommandStream { receiver, handle, done: false }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> &BuildHandle {
+        &self.handle
+    }
+}
+
+impl Drop for RenderCommandStream {
+    fn drop(&mut self) {
+        if !self.done {
+            self.handle.cancel();
+        }
     }
 }
 
@@ -149,3 +289,122 @@ impl Iterator for RenderCommandStream {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::SceneProxy;
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::gpu_data::RenderCommand;
+    use crate::options::RenderOptions;
+    use crate::paint::Paint;
+    use crate::scene::{PathObject, Scene};
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::vector::Vector2F;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use std::sync::Mutex;
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    fn scene() -> Scene {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::splat(4.0));
+        contour.push_endpoint(Vector2F::new(28.0, 4.0));
+        contour.push_endpoint(Vector2F::splat(28.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(32.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        scene.push_path(PathObject::new(outline, paint, String::new()));
+        scene
+    }
+
+    // Returns whether the build was cancelled and whether it drew any tiles.
+    fn outcome<I>(commands: I) -> (bool, bool) where I: IntoIterator<Item = RenderCommand> {
+        let mut drew_tiles = false;
+        for command in commands {
+            match command {
+                RenderCommand::AlphaTile(..) | RenderCommand::SolidTile(_) => drew_tiles = true,
+                RenderCommand::Finish { cancelled, .. } => return (cancelled, drew_tiles),
+                _ => {}
+            }
+        }
+        panic!("build didn't finish")
+    }
+
+    // Requests a build that holds up the scene thread once it has started, until the returned
+    // sender is used.
+    fn held_build(scene_proxy: &SceneProxy) -> (Sender<()>, Receiver<RenderCommand>) {
+        let (release_sender, release_receiver) = mpsc::channel::<()>();
+        let (command_sender, command_receiver) = mpsc::channel();
+        let release_receiver = Mutex::new(release_receiver);
+        let command_sender = Mutex::new(command_sender);
+        let listener = move |command| {
+            let started = matches!(command, RenderCommand::Start { .. });
+            command_sender.lock().unwrap().send(command).unwrap();
+            if started {
+                release_receiver.lock().unwrap().recv().unwrap();
+            }
+        };
+        scene_proxy.build_with_listener(RenderOptions::default(), Box::new(listener));
+        (release_sender, command_receiver)
+    }
+
+    #[test]
+    fn test_cancel_builds() {
+        let scene_proxy = SceneProxy::from_scene(scene(), SequentialExecutor);
+
+        // Each build supersedes the ones before it, including the one that's running.
+        let (release, first) = held_build(&scene_proxy);
+        assert!(matches!(first.recv().unwrap(), RenderCommand::Start { .. }));
+        let second = scene_proxy.build_with_stream(RenderOptions::default());
+        let third = scene_proxy.build_with_stream(RenderOptions::default());
+        release.send(()).unwrap();
+        assert_eq!(outcome(first.iter()), (true, false));
+        assert_eq!(outcome(second), (true, false));
+        assert_eq!(outcome(third), (false, true));
+
+        // Cancelling builds stops the one that's running and the queued one alike.
+        let (release, first) = held_build(&scene_proxy);
+        assert!(matches!(first.recv().unwrap(), RenderCommand::Start { .. }));
+        let second = scene_proxy.build_with_stream(RenderOptions::default());
+        scene_proxy.cancel_builds();
+        release.send(()).unwrap();
+        assert_eq!(outcome(first.iter()), (true, false));
+        assert_eq!(outcome(second), (true, false));
+
+        let third = scene_proxy.build_with_stream(RenderOptions::default());
+        assert_eq!(outcome(third), (false, true));
+    }
+
+    #[test]
+    fn test_edit_paths() {
+        let scene_proxy = SceneProxy::from_scene(scene(), SequentialExecutor);
+        let path = scene().path(0).clone();
+        scene_proxy.push_path(path.clone());
+        scene_proxy.push_path(path.clone());
+        assert_eq!(scene_proxy.path_count(), 3);
+
+        scene_proxy.remove_path(0);
+        scene_proxy.move_path(1, 0);
+        assert_eq!(scene_proxy.path_count(), 2);
+        assert_eq!(scene_proxy.bounds(), RectF::new(Vector2F::default(), Vector2F::splat(28.0)));
+
+        let paint = scene_proxy.push_paint(&Paint::Color(ColorU::black()));
+        scene_proxy.replace_paint(paint, &Paint::Color(ColorU::transparent_black()));
+        assert_eq!(scene_proxy.push_paint(&Paint::Color(ColorU::transparent_black())), paint);
+
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::default());
+        contour.push_endpoint(Vector2F::new(64.0, 0.0));
+        contour.push_endpoint(Vector2F::splat(64.0));
+        contour.close();
+        let mut bigger = Outline::new();
+        bigger.push_contour(contour);
+        scene_proxy.replace_path(1, PathObject::new(bigger, paint, String::new()));
+        assert_eq!(scene_proxy.bounds(), RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+        assert_eq!(outcome(scene_proxy.build_with_stream(RenderOptions::default())), (false, true));
+    }
+}
@@ -0,0 +1,121 @@
+// pathfinder/renderer/src/cpu/area_lut.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The area lookup table used by the fill shader, computed on the CPU.
+//!
+//! This is the same table that `utils/area-lut` writes to `area-lut.png`, quantized to 8 bits
+//! the same way, so that the software renderer produces the same coverage as the GPU.
+
+use pathfinder_geometry::basic::vector::Vector2F;
+
+const AREA_LUT_WIDTH: usize = 256;
+const AREA_LUT_HEIGHT: usize = 256;
+
+pub(crate) struct AreaLUT {
+    texels: Vec<u8>,
+}
+
+impl AreaLUT {
+    pub(crate) fn new() -> AreaLUT {
+        let mut texels = Vec::with_capacity(AREA_LUT_WIDTH * AREA_LUT_HEIGHT);
+        for v in 0..AREA_LUT_HEIGHT {
+            for u in 0..AREA_LUT_WIDTH {
+                texels.push(compute_texel(u, v));
+            }
+        }
+        AreaLUT { texels }
+    }
+
+    /// Samples the table with bilinear filtering and clamp-to-edge addressing, like
+    /// `texture(uAreaLUT, tex_coord)` does.
+    pub(crate) fn sample(&self, tex_coord: Vector2F) -> f32 {
+        let x = tex_coord.x() * AREA_LUT_WIDTH as f32 - 0.5;
+        let y = tex_coord.y() * AREA_LUT_HEIGHT as f32 - 0.5;
+        let (x0, y0) = (f32::floor(x), f32::floor(y));
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let top = lerp(self.texel(x0, y0), self.texel(x0 + 1, y0), fx);
+        let bottom = lerp(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), fx);
+        lerp(top, bottom, fy)
+    }
+
+    #[inline]
+    fn texel(&self, x: i32, y: i32) -> f32 {
+        let x = x.max(0).min(AREA_LUT_WIDTH as i32 - 1) as usize;
+        let y = y.max(0).min(AREA_LUT_HEIGHT as i32 - 1) as usize;
+        self.texels[y * AREA_LUT_WIDTH + x] as f32 / 255.0
+    }
+}
+
+#[inline]
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn compute_texel(u: usize, v: usize) -> u8 {
+    if u == 0 {
+        return 255;
+    }
+    if u == AREA_LUT_WIDTH - 1 {
+        return 0;
+    }
+
+    let y = (u as f32 - (AREA_LUT_WIDTH / 2) as f32) / 16.0;
+    let dydx = -(v as f32) / 16.0;
+
+    let (x_left, x_right) = (-0.5, 0.5);
+    let (y_left, y_right) = (dydx * x_left + y, dydx * x_right + y);
+
+    let (p0, p1) = (Vector2F::new(x_left, y_left), Vector2F::new(x_right, y_right));
+    let p2 = solve_line_y(p0, p1, -0.5);
+    let p3 = Vector2F::new(p1.x(), -0.5);
+    let p4 = solve_line_y(p0, p1, 0.5);
+    let p7 = Vector2F::new(p1.x(), 0.5);
+
+    let alpha = if p0.y() > 0.5 {
+        if p1.y() < -0.5 {
+            // Case 0
+            area_tri(p0, p1) - area_tri(p2, p1) - area_rect(p0, p7) + area_tri(p0, p4)
+        } else if p1.y() < 0.5 {
+            // Case 6
+            area_tri(p0, p1) - area_rect(p0, p7) + area_tri(p0, p4)
+        } else {
+            // Case 3
+            0.0
+        }
+    } else if p0.y() > -0.5 {
+        if p1.y() < -0.5 {
+            // Case 1
+            area_tri(p0, p1) - area_tri(p2, p1) - area_rect(p0, p7)
+        } else {
+            // Case 4
+            area_tri(p0, p1) - area_rect(p0, p7)
+        }
+    } else {
+        // Case 2
+        -area_rect(p0, p7) + area_rect(p0, p3)
+    };
+
+    f32::round(alpha * 255.0) as u8
+}
+
+fn solve_line_y(p0: Vector2F, p1: Vector2F, y: f32) -> Vector2F {
+    let m = (p1.y() - p0.y()) / (p1.x() - p0.x());
+    Vector2F::new(p0.x() - (p0.y() - y) / m, y)
+}
+
+fn area_tri(p0: Vector2F, p1: Vector2F) -> f32 {
+    0.5 * (p1.x() - p0.x()) * (p0.y() - p1.y())
+}
+
+fn area_rect(p0: Vector2F, p1: Vector2F) -> f32 {
+    (p1.x() - p0.x()) * (p0.y() - p1.y())
+}
@@ -0,0 +1,1225 @@
+// pathfinder/renderer/src/cpu/renderer.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rasterizes the render command stream into an RGBA8 buffer in memory.
+//!
+//! This mirrors the GPU renderer stage by stage: fills are accumulated into per-tile coverage
+//! masks using the same area lookup table math as `fill.fs.glsl`, clip tiles are multiplied into
+//! per-tile clip masks, and alpha and solid tiles are then composited with the same blend
+//! functions the GPU renderer uses. Layers are drawn into their own buffers and composited onto
+//! the buffer beneath them when popped. Blend modes other than source-over follow
+//! `tile_blend.inc.glsl`.
+
+use crate::cpu::area_lut::AreaLUT;
+use crate::gpu::renderer::RenderStats;
+use crate::gpu_data::{AlphaTileBatchPrimitive, ClipTileBatchPrimitive};
+use crate::gpu_data::{CompositeTileBatchPrimitive, FillBatchPrimitive, ImageData, PaintData};
+use crate::gpu_data::{RenderCommand, SolidTileBatchPrimitive};
+use crate::paint::{GRADIENT_RAMP_LENGTH, PAINT_KIND_COLOR, PAINT_KIND_LINEAR_GRADIENT};
+use crate::paint::{BlendMode, PAINT_KIND_CONIC_GRADIENT, PAINT_KIND_PATTERN};
+use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
+use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+use pathfinder_geometry::color::{ColorF, ColorU};
+use pathfinder_geometry::util;
+use pathfinder_simd::default::F32x4;
+use std::f32::consts::PI;
+use std::mem;
+
+const MASK_TILE_LENGTH: usize = (TILE_WIDTH * TILE_HEIGHT) as usize;
+
+const EPSILON: f32 = 0.0001;
+
+pub struct SoftwareRenderer {
+    // Core data
+    framebuffer_size: Vector2I,
+    pixels: Vec<u8>,
+    area_lut: AreaLUT,
+    paint_data: Option<PaintData>,
+    image_data: Option<ImageData>,
+
+    // Rendering state
+    masks: Vec<f32>,
+    masks_flushed: bool,
+    clip_masks: Vec<f32>,
+    layer_stack: Vec<Vec<u8>>,
+
+    // Debug
+    pub stats: RenderStats,
+}
+
+impl SoftwareRenderer {
+    /// Creates a renderer that draws into a transparent black framebuffer of the given size.
+    pub fn new(framebuffer_size: Vector2I) -> SoftwareRenderer {
+        let pixel_count = framebuffer_size.x() as usize * framebuffer_size.y() as usize;
+        SoftwareRenderer {
+            framebuffer_size,
+            pixels: vec![0; pixel_count * 4],
+            area_lut: AreaLUT::new(),
+            paint_data: None,
+            image_data: None,
+
+            masks: vec![],
+            masks_flushed: false,
+            clip_masks: vec![],
+            layer_stack: vec![],
+
+            stats: RenderStats::default(),
+        }
+    }
+
+    pub fn begin_scene(&mut self) {
+        self.masks.clear();
+        self.masks_flushed = false;
+        self.clip_masks.clear();
+        self.stats = RenderStats::default();
+    }
+
+    pub fn render_command(&mut self, command: &RenderCommand) {
+        match *command {
+            RenderCommand::Start { path_count, .. } => self.stats.path_count = path_count,
+            RenderCommand::AddImageData(ref image_data) => {
+                self.image_data = Some((*image_data).clone())
+            }
+            RenderCommand::AddPaintData(ref paint_data) => {
+                self.paint_data = Some((*paint_data).clone())
+            }
+            RenderCommand::AddFills(ref fills) => {
+                if self.masks_flushed {
+                    // These fills start a new page of alpha tiles.
+                    self.masks.clear();
+                    self.masks_flushed = false;
+                }
+                self.stats.fill_count += fills.len();
+                for fill in fills {
+                    self.draw_fill(fill);
+                }
+            }
+            RenderCommand::FlushFills => self.masks_flushed = true,
+            RenderCommand::ClipTiles(ref clip_tiles) => {
+                for clip_tile in clip_tiles {
+                    self.draw_clip_tile(clip_tile);
+                }
+            }
+            RenderCommand::SolidTile(ref solid_tiles) => {
+                self.stats.solid_tile_count += solid_tiles.len();
+                for solid_tile in solid_tiles {
+                    self.draw_solid_tile(solid_tile);
+                }
+            }
+            RenderCommand::AlphaTile(ref alpha_tiles, blend_mode) => {
+                self.stats.alpha_tile_count += alpha_tiles.len();
+                for alpha_tile in alpha_tiles {
+                    self.draw_alpha_tile(alpha_tile, blend_mode);
+                }
+            }
+            RenderCommand::PushLayer => {
+                let layer_pixels = vec![0; self.pixels.len()];
+                self.layer_stack.push(mem::replace(&mut self.pixels, layer_pixels));
+            }
+            RenderCommand::PopLayer(ref composite_tiles, blend_mode) => {
+                let parent_pixels = self.layer_stack.pop().expect("No layer to pop!");
+                let layer_pixels = mem::replace(&mut self.pixels, parent_pixels);
+                for composite_tile in composite_tiles {
+                    self.draw_composite_tile(composite_tile, &layer_pixels, blend_mode);
+                }
+            }
+            RenderCommand::Finish { .. } => {}
+        }
+    }
+
+    pub fn end_scene(&mut self) {
+        debug_assert!(self.layer_stack.is_empty());
+    }
+
+    #[inline]
+    pub fn framebuffer_size(&self) -> Vector2I {
+        self.framebuffer_size
+    }
+
+    /// Returns the framebuffer contents as tightly packed RGBA8 rows, top row first.
+    #[inline]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    #[inline]
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+
+    pub fn pixel(&self, position: Vector2I) -> ColorU {
+        let offset = self.pixel_offset(position).expect("Pixel out of bounds!");
+        let pixel = &self.pixels[offset..(offset + 4)];
+        ColorU { r: pixel[0], g: pixel[1], b: pixel[2], a: pixel[3] }
+    }
+
+    pub fn clear(&mut self, color: ColorU) {
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    fn draw_fill(&mut self, fill: &FillBatchPrimitive) {
+        // Copy out of the packed struct before touching the fields.
+        let (px, subpx, alpha_tile_index) = (fill.px, fill.subpx, fill.alpha_tile_index);
+        let (px, subpx) = (px.0, subpx.0);
+        let from = Vector2F::new((px & 0xf) as f32 + (subpx & 0xff) as f32 / 255.0,
+                                 ((px >> 4) & 0xf) as f32 + ((subpx >> 8) & 0xff) as f32 / 255.0);
+        let to = Vector2F::new(((px >> 8) & 0xf) as f32 + ((subpx >> 16) & 0xff) as f32 / 255.0,
+                               ((px >> 12) & 0xf) as f32 + (subpx >> 24) as f32 / 255.0);
+
+        let mask_offset = alpha_tile_index as usize * MASK_TILE_LENGTH;
+        if self.masks.len() < mask_offset + MASK_TILE_LENGTH {
+            self.masks.resize(mask_offset + MASK_TILE_LENGTH, 0.0);
+        }
+
+        // Cover the same pixels as the quad that `fill.vs.glsl` emits.
+        let min_x = f32::floor(f32::min(from.x(), to.x())).max(0.0) as u32;
+        let max_x = f32::ceil(f32::max(from.x(), to.x())).min(TILE_WIDTH as f32) as u32;
+        let min_y = f32::floor(f32::min(from.y(), to.y())).max(0.0) as u32;
+
+        for y in min_y..TILE_HEIGHT {
+            for x in min_x..max_x {
+                let center = Vector2F::new(x as f32 + 0.5, y as f32 + 0.5);
+                let area = self.compute_fill_area(from - center, to - center);
+                self.masks[mask_offset + (y * TILE_WIDTH + x) as usize] += area;
+            }
+        }
+    }
+
+    // The body of `fill.fs.glsl`.
+    fn compute_fill_area(&self, from: Vector2F, to: Vector2F) -> f32 {
+        let (left, right) = if from.x() < to.x() { (from, to) } else { (to, from) };
+
+        let window = (util::clamp(from.x(), -0.5, 0.5), util::clamp(to.x(), -0.5, 0.5));
+        let dx = window.0 - window.1;
+        if dx == 0.0 {
+            return 0.0;
+        }
+
+        let offset = (window.0 + window.1) * 0.5 - left.x();
+        let t = offset / (right.x() - left.x());
+
+        let y = left.y() + (right.y() - left.y()) * t;
+        let d = (right.y() - left.y()) / (right.x() - left.x());
+
+        let tex_coord = Vector2F::new(y + 8.0, f32::abs(d * dx)).scale(1.0 / 16.0);
+        self.area_lut.sample(tex_coord) * dx
+    }
+
+    fn draw_clip_tile(&mut self, clip_tile: &ClipTileBatchPrimitive) {
+        let dest_offset = clip_tile.dest_tile_index as usize * MASK_TILE_LENGTH;
+        let src_offset = clip_tile.src_tile_index as usize * MASK_TILE_LENGTH;
+        let backdrop = clip_tile.src_backdrop as f32;
+
+        // The clip mask starts out fully covered.
+        if self.clip_masks.len() < dest_offset + MASK_TILE_LENGTH {
+            self.clip_masks.resize(dest_offset + MASK_TILE_LENGTH, 1.0);
+        }
+
+        // The body of `tile_clip.fs.glsl`, multiplied in with
+        // `BlendState::RGBDestColorAlphaZero`.
+        for index in 0..MASK_TILE_LENGTH {
+            let mask = self.masks.get(src_offset + index).cloned().unwrap_or(0.0);
+            let coverage = compute_coverage(mask + backdrop, clip_tile.src_fill_rule);
+            self.clip_masks[dest_offset + index] *= f32::min(coverage, 1.0);
+        }
+    }
+
+    fn draw_solid_tile(&mut self, solid_tile: &SolidTileBatchPrimitive) {
+        let tile_origin = Vector2I::new(solid_tile.tile_x as i32 * TILE_WIDTH as i32,
+                                        solid_tile.tile_y as i32 * TILE_HEIGHT as i32);
+
+        // Solid tiles are drawn with blending off.
+        for y in 0..TILE_HEIGHT as i32 {
+            for x in 0..TILE_WIDTH as i32 {
+                let position = tile_origin + Vector2I::new(x, y);
+                let offset = match self.pixel_offset(position) {
+                    None => continue,
+                    Some(offset) => offset,
+                };
+                let color = self.paint_color(solid_tile.paint_id, pixel_center(position));
+                for channel in 0..4 {
+                    self.pixels[offset + channel] = to_u8(color.0[channel]);
+                }
+            }
+        }
+    }
+
+    fn draw_alpha_tile(&mut self, alpha_tile: &AlphaTileBatchPrimitive, blend_mode: BlendMode) {
+        let tile_origin = alpha_tile.tile_coords().scale_xy(Vector2I::new(TILE_WIDTH as i32,
+                                                                          TILE_HEIGHT as i32));
+        let backdrop = alpha_tile.backdrop as f32;
+        let mask_offset = alpha_tile.tile_index as usize * MASK_TILE_LENGTH;
+        let clip_mask_offset = match alpha_tile.clip_tile_index {
+            u16::MAX => None,
+            clip_tile_index => Some(clip_tile_index as usize * MASK_TILE_LENGTH),
+        };
+
+        for y in 0..TILE_HEIGHT {
+            for x in 0..TILE_WIDTH {
+                let position = tile_origin + Vector2I::new(x as i32, y as i32);
+                let offset = match self.pixel_offset(position) {
+                    None => continue,
+                    Some(offset) => offset,
+                };
+
+                // The body of `tile_alpha.fs.glsl`.
+                let mask_index = mask_offset + (y * TILE_WIDTH + x) as usize;
+                let mask = self.masks.get(mask_index).cloned().unwrap_or(0.0);
+                let mut coverage = compute_coverage(mask + backdrop, alpha_tile.fill_rule);
+                if let Some(clip_mask_offset) = clip_mask_offset {
+                    let clip_mask_index = clip_mask_offset + (y * TILE_WIDTH + x) as usize;
+                    coverage *= self.clip_masks.get(clip_mask_index).cloned().unwrap_or(1.0);
+                }
+                let color = self.paint_color(alpha_tile.paint_id, pixel_center(position));
+                if !blend_mode.is_source_over() {
+                    let src_alpha = util::clamp(color.a(), 0.0, 1.0);
+                    let src = [
+                        util::clamp(color.r(), 0.0, 1.0) * src_alpha,
+                        util::clamp(color.g(), 0.0, 1.0) * src_alpha,
+                        util::clamp(color.b(), 0.0, 1.0) * src_alpha,
+                        src_alpha,
+                    ];
+                    blend_pixel(&mut self.pixels[offset..(offset + 4)], src, coverage, blend_mode);
+                    continue;
+                }
+
+                // `BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha`.
+                let src_alpha = util::clamp(color.a() * coverage, 0.0, 1.0);
+                let dest = &mut self.pixels[offset..(offset + 4)];
+                for (channel, dest_channel) in dest[0..3].iter_mut().enumerate() {
+                    let src = util::clamp(color.0[channel], 0.0, 1.0);
+                    let dst = *dest_channel as f32 / 255.0;
+                    *dest_channel = to_u8(src * src_alpha + dst * (1.0 - src_alpha));
+                }
+                dest[3] = to_u8(src_alpha + dest[3] as f32 / 255.0);
+            }
+        }
+    }
+
+    fn draw_composite_tile(&mut self,
+                           composite_tile: &CompositeTileBatchPrimitive,
+                           layer_pixels: &[u8],
+                           blend_mode: BlendMode) {
+        let tile_origin = Vector2I::new(composite_tile.tile_x as i32 * TILE_WIDTH as i32,
+                                        composite_tile.tile_y as i32 * TILE_HEIGHT as i32);
+        let opacity = composite_tile.opacity as f32 / 255.0;
+        let clip_mask_offset = match composite_tile.clip_tile_index {
+            u16::MAX => None,
+            clip_tile_index => Some(clip_tile_index as usize * MASK_TILE_LENGTH),
+        };
+
+        for y in 0..TILE_HEIGHT {
+            for x in 0..TILE_WIDTH {
+                let position = tile_origin + Vector2I::new(x as i32, y as i32);
+                let offset = match self.pixel_offset(position) {
+                    None => continue,
+                    Some(offset) => offset,
+                };
+
+                // The body of `tile_composite.fs.glsl`.
+                let mut alpha = opacity;
+                if let Some(clip_mask_offset) = clip_mask_offset {
+                    let clip_mask_index = clip_mask_offset + (y * TILE_WIDTH + x) as usize;
+                    alpha *= self.clip_masks.get(clip_mask_index).cloned().unwrap_or(1.0);
+                }
+
+                let src = &layer_pixels[offset..(offset + 4)];
+                if !blend_mode.is_source_over() {
+                    let src = [
+                        src[0] as f32 / 255.0,
+                        src[1] as f32 / 255.0,
+                        src[2] as f32 / 255.0,
+                        src[3] as f32 / 255.0,
+                    ];
+                    blend_pixel(&mut self.pixels[offset..(offset + 4)], src, alpha, blend_mode);
+                    continue;
+                }
+
+                // `BlendState::RGBOneAlphaOneMinusSrcAlpha`, since the layer is premultiplied.
+                let src_alpha = src[3] as f32 / 255.0 * alpha;
+                let dest = &mut self.pixels[offset..(offset + 4)];
+                for channel in 0..3 {
+                    let src = src[channel] as f32 / 255.0 * alpha;
+                    let dst = dest[channel] as f32 / 255.0;
+                    dest[channel] = to_u8(src + dst * (1.0 - src_alpha));
+                }
+                dest[3] = to_u8(src_alpha + dest[3] as f32 / 255.0);
+            }
+        }
+    }
+
+    // The body of `getPaintColor()` in `tile_paint.inc.glsl`.
+    fn paint_color(&self, paint_id: u32, position: Vector2F) -> ColorF {
+        let paint_data = match self.paint_data {
+            None => return ColorF::transparent_black(),
+            Some(ref paint_data) => paint_data,
+        };
+        let metadata = &paint_data.metadata[paint_id as usize];
+        let color_tex_coord = Vector2F::new(metadata.color_u, metadata.color_v);
+
+        if metadata.kind == PAINT_KIND_COLOR {
+            return self.sample_paint_texture(color_tex_coord);
+        }
+
+        let transform = &metadata.transform;
+        let position = Vector2F::new(
+            transform[0] * position.x() + transform[1] * position.y() + transform[4],
+            transform[2] * position.x() + transform[3] * position.y() + transform[5]);
+        if metadata.kind == PAINT_KIND_PATTERN {
+            let color = self.pattern_color(metadata.spread, &metadata.line, position);
+            return ColorF(color.0 * F32x4::new(1.0, 1.0, 1.0, metadata.radii[0]));
+        }
+
+        let from = Vector2F::new(metadata.line[0], metadata.line[1]);
+        let to = Vector2F::new(metadata.line[2], metadata.line[3]);
+
+        let t = if metadata.kind == PAINT_KIND_LINEAR_GRADIENT {
+            let vector = to - from;
+            (position - from).dot(vector) / vector.dot(vector)
+        } else if metadata.kind == PAINT_KIND_CONIC_GRADIENT {
+            let vector = position - from;
+            let t = (f32::atan2(vector.y(), vector.x()) - to.x()) / (2.0 * PI);
+            t - f32::floor(t)
+        } else {
+            match compute_radial_gradient_t(position,
+                                            from,
+                                            to,
+                                            metadata.radii[0],
+                                            metadata.radii[1]) {
+                None => return ColorF::transparent_black(),
+                Some(t) => t,
+            }
+        };
+
+        let t = if metadata.spread == 1.0 {
+            1.0 - f32::abs(t.rem_euclid(2.0) - 1.0)
+        } else if metadata.spread == 2.0 {
+            t - f32::floor(t)
+        } else {
+            util::clamp(t, 0.0, 1.0)
+        };
+
+        let ramp_offset = t * (GRADIENT_RAMP_LENGTH - 1) as f32 / paint_data.size.x() as f32;
+        self.sample_paint_texture(color_tex_coord + Vector2F::new(ramp_offset, 0.0))
+    }
+
+    // The body of `getPatternColor()` in `tile_paint.inc.glsl`.
+    fn pattern_color(&self, repeat: f32, image_rect: &[f32; 4], position: Vector2F) -> ColorF {
+        let image_data = match self.image_data {
+            None => return ColorF::transparent_black(),
+            Some(ref image_data) => image_data,
+        };
+        let image_origin = Vector2F::new(image_rect[0], image_rect[1]);
+        let image_size = Vector2F::new(image_rect[2], image_rect[3]);
+
+        let (repeat_x, repeat_y) = (repeat == 1.0 || repeat == 3.0, repeat >= 2.0);
+        let wrap = |value: f32, size: f32, repeats: bool| {
+            if repeats {
+                Some(value.rem_euclid(size))
+            } else if value < 0.0 || value > size {
+                None
+            } else {
+                Some(value)
+            }
+        };
+        let position = match (wrap(position.x(), image_size.x(), repeat_x),
+                              wrap(position.y(), image_size.y(), repeat_y)) {
+            (Some(x), Some(y)) => Vector2F::new(x, y),
+            _ => return ColorF::transparent_black(),
+        };
+
+        let position = position.max(Vector2F::splat(0.5))
+                               .min(image_size - Vector2F::splat(0.5));
+        let (atlas_position, atlas_size) = (image_origin + position, image_data.size.to_f32());
+        let tex_coord = Vector2F::new(atlas_position.x() / atlas_size.x(),
+                                      atlas_position.y() / atlas_size.y());
+        sample_texture(image_data.size, &image_data.texels, tex_coord)
+    }
+
+    fn sample_paint_texture(&self, tex_coord: Vector2F) -> ColorF {
+        let paint_data = self.paint_data.as_ref().unwrap();
+        sample_texture(paint_data.size, &paint_data.texels, tex_coord)
+    }
+
+    fn pixel_offset(&self, position: Vector2I) -> Option<usize> {
+        if position.x() < 0 || position.y() < 0 || position.x() >= self.framebuffer_size.x() ||
+                position.y() >= self.framebuffer_size.y() {
+            return None;
+        }
+        Some((position.y() as usize * self.framebuffer_size.x() as usize +
+              position.x() as usize) * 4)
+    }
+}
+
+// 0 = nonzero winding, 1 = even-odd, as in `tile_alpha.fs.glsl`.
+#[inline]
+fn compute_coverage(winding: f32, fill_rule: u8) -> f32 {
+    if fill_rule == 0 {
+        f32::abs(winding)
+    } else {
+        1.0 - f32::abs(1.0 - winding.rem_euclid(2.0))
+    }
+}
+
+// The body of `blendColors()` in `tile_blend.inc.glsl`, drawn with blending off. `src` is
+// premultiplied, and only `coverage` of the destination is replaced by the blended color.
+fn blend_pixel(dest: &mut [u8], src: [f32; 4], coverage: f32, blend_mode: BlendMode) {
+    let dest_color = [
+        dest[0] as f32 / 255.0,
+        dest[1] as f32 / 255.0,
+        dest[2] as f32 / 255.0,
+        dest[3] as f32 / 255.0,
+    ];
+    let blended = blend_mode.blend(src, dest_color);
+    let coverage = util::clamp(coverage, 0.0, 1.0);
+    for channel in 0..4 {
+        dest[channel] = to_u8(util::lerp(dest_color[channel], blended[channel], coverage));
+    }
+}
+
+/// Finds the largest `t` for which `position` lies on the circle interpolated between the start
+/// and end circles with a nonnegative radius.
+fn compute_radial_gradient_t(position: Vector2F,
+                             start_center: Vector2F,
+                             end_center: Vector2F,
+                             start_radius: f32,
+                             end_radius: f32)
+                             -> Option<f32> {
+    let center_delta = end_center - start_center;
+    let position_delta = position - start_center;
+    let radius_delta = end_radius - start_radius;
+
+    let a = center_delta.dot(center_delta) - radius_delta * radius_delta;
+    let b = position_delta.dot(center_delta) + start_radius * radius_delta;
+    let c = position_delta.dot(position_delta) - start_radius * start_radius;
+
+    let radius_is_valid = |t: f32| start_radius + t * radius_delta >= 0.0;
+    if f32::abs(a) < EPSILON {
+        if b == 0.0 {
+            return None;
+        }
+        let t = c / (2.0 * b);
+        return if radius_is_valid(t) { Some(t) } else { None };
+    }
+
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = f32::sqrt(discriminant);
+    let (t0, t1) = ((b + root) / a, (b - root) / a);
+    let (t_max, t_min) = (f32::max(t0, t1), f32::min(t0, t1));
+    if radius_is_valid(t_max) {
+        Some(t_max)
+    } else if radius_is_valid(t_min) {
+        Some(t_min)
+    } else {
+        None
+    }
+}
+
+// Bilinear sampling of an RGBA8 texture with clamp-to-edge addressing, like `texture()`.
+fn sample_texture(size: Vector2I, texels: &[u8], tex_coord: Vector2F) -> ColorF {
+    let texel = |x: i32, y: i32| {
+        let x = x.max(0).min(size.x() - 1) as usize;
+        let y = y.max(0).min(size.y() - 1) as usize;
+        let offset = (y * size.x() as usize + x) * 4;
+        let texel = &texels[offset..(offset + 4)];
+        ColorU { r: texel[0], g: texel[1], b: texel[2], a: texel[3] }.to_f32()
+    };
+
+    let position = tex_coord.scale_xy(size.to_f32()) - Vector2F::splat(0.5);
+    let origin = position.floor();
+    let fraction = position - origin;
+    let (x, y) = (origin.x() as i32, origin.y() as i32);
+    let top = texel(x, y).lerp(texel(x + 1, y), fraction.x());
+    let bottom = texel(x, y + 1).lerp(texel(x + 1, y + 1), fraction.x());
+    top.lerp(bottom, fraction.y())
+}
+
+#[inline]
+fn pixel_center(position: Vector2I) -> Vector2F {
+    position.to_f32() + Vector2F::splat(0.5)
+}
+
+#[inline]
+fn to_u8(value: f32) -> u8 {
+    f32::round(util::clamp(value, 0.0, 1.0) * 255.0) as u8
+}
+
+#[cfg(test)]
+mod test {
+    use super::SoftwareRenderer;
+    use crate::builder::MAX_ALPHA_TILES_PER_PAGE;
+    use crate::concurrent::executor::{Executor, SequentialExecutor};
+    use crate::gpu_data::{BuildError, RenderCommand};
+    use crate::options::{RenderOptions, RenderTransform};
+    use crate::paint::{BlendMode, ColorStop, Gradient, Image, MAX_TEXTURE_SIZE, Paint, PaintId};
+    use crate::paint::Pattern;
+    use crate::scene::{ClipPath, Layer, PathObject, Scene};
+    use pathfinder_geometry::basic::line_segment::LineSegment2F;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::transform2d::Transform2DF;
+    use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use std::mem;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn render(scene: &Scene, framebuffer_size: Vector2I) -> SoftwareRenderer {
+        render_with_options(scene, RenderOptions::default(), framebuffer_size)
+    }
+
+    fn render_with_options(scene: &Scene, options: RenderOptions, framebuffer_size: Vector2I)
+                           -> SoftwareRenderer {
+        let mut renderer = SoftwareRenderer::new(framebuffer_size);
+        renderer.begin_scene();
+        for command in &build(scene, options) {
+            renderer.render_command(command);
+        }
+        renderer.end_scene();
+        renderer
+    }
+
+    fn build(scene: &Scene, options: RenderOptions) -> Vec<RenderCommand> {
+        let commands = Arc::new(Mutex::new(vec![]));
+        let listener_commands = commands.clone();
+        let listener = move |command: RenderCommand| {
+            listener_commands.lock().unwrap().push(command)
+        };
+        scene.build(options, Box::new(listener), &SequentialExecutor);
+        let mut commands = commands.lock().unwrap();
+        mem::take(&mut *commands)
+    }
+
+    // Builds the scene and returns the error that the build finished with.
+    fn build_error(scene: &Scene) -> Option<BuildError> {
+        match build(scene, RenderOptions::default()).last() {
+            Some(&RenderCommand::Finish { cancelled: false, error, .. }) => error,
+            _ => panic!("The build didn't finish!"),
+        }
+    }
+
+    fn rect_contour(rect: RectF) -> Contour {
+        let mut contour = Contour::new();
+        contour.push_endpoint(rect.origin());
+        contour.push_endpoint(rect.upper_right());
+        contour.push_endpoint(rect.lower_right());
+        contour.push_endpoint(rect.lower_left());
+        contour.close();
+        contour
+    }
+
+    fn rect_outline(rect: RectF) -> Outline {
+        let mut outline = Outline::new();
+        outline.push_contour(rect_contour(rect));
+        outline
+    }
+
+    fn rect_path(rect: RectF, paint: PaintId) -> PathObject {
+        PathObject::new(rect_outline(rect), paint, String::new())
+    }
+
+    // Covers the left half of each tile in a grid of tiles starting at the origin, with a tall
+    // rectangle for each column.
+    fn half_tile_grid_outline(columns: i32, rows: i32) -> Outline {
+        let mut outline = Outline::new();
+        for column in 0..columns {
+            let rect = RectF::new(Vector2F::new(column as f32 * 16.0, 0.0),
+                                  Vector2F::new(8.0, rows as f32 * 16.0));
+            outline.push_contour(rect_contour(rect));
+        }
+        outline
+    }
+
+    #[test]
+    fn test_render_rect() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let rect = RectF::new(Vector2F::new(4.0, 4.0), Vector2F::new(40.5, 24.0));
+        let framebuffer_size = Vector2I::new(64, 64);
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(red));
+        scene.push_path(rect_path(rect, paint));
+        scene.set_view_box(RectF::new(Vector2F::default(), framebuffer_size.to_f32()));
+        scene.set_bounds(rect);
+
+        let renderer = render(&scene, framebuffer_size);
+        assert_eq!(renderer.pixel(Vector2I::new(0, 0)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(20, 8)), red);
+        assert_eq!(renderer.pixel(Vector2I::new(30, 26)), red);
+        assert_eq!(renderer.pixel(Vector2I::new(50, 20)), ColorU::transparent_black());
+
+        // The right edge cuts the pixel at x = 44 in half.
+        let edge = renderer.pixel(Vector2I::new(44, 20));
+        assert_eq!((edge.g, edge.b), (0, 0));
+        assert!(edge.r > 112 && edge.r < 144);
+        assert!(edge.a > 112 && edge.a < 144);
+    }
+
+    #[test]
+    fn test_render_linear_gradient() {
+        let rect = RectF::new(Vector2F::default(), Vector2F::new(64.0, 32.0));
+        let framebuffer_size = Vector2I::new(64, 32);
+
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::default(),
+                                                               Vector2F::new(64.0, 0.0)));
+        let white = ColorU { r: 255, g: 255, b: 255, a: 255 };
+        gradient.add_color_stop(ColorStop { offset: 0.0, color: ColorU::black() });
+        gradient.add_color_stop(ColorStop { offset: 1.0, color: white });
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Gradient(gradient));
+        scene.push_path(rect_path(rect, paint));
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        let renderer = render(&scene, framebuffer_size);
+        let (left, middle) = (renderer.pixel(Vector2I::new(0, 8)),
+                              renderer.pixel(Vector2I::new(32, 24)));
+        let right = renderer.pixel(Vector2I::new(63, 8));
+        assert!(left.r < 8 && left.a == 255);
+        assert!(middle.r > 120 && middle.r < 136 && middle.a == 255);
+        assert!(right.r > 247 && right.a == 255);
+        assert_eq!((middle.r, middle.g), (middle.g, middle.b));
+    }
+
+    #[test]
+    fn test_render_many_gradients() {
+        // More ramps than one per row of the tallest texture a GPU is likely to support.
+        let rect = RectF::new(Vector2F::default(), Vector2F::new(64.0, 32.0));
+        let gradient_count = 20000;
+
+        let mut scene = Scene::new();
+        let mut paints = vec![];
+        for gradient_index in 0..gradient_count {
+            let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::default(),
+                                                                   Vector2F::new(64.0, 0.0)));
+            let color = ColorU {
+                r: 255,
+                g: (gradient_index % 256) as u8,
+                b: (gradient_index / 256) as u8,
+                a: 255,
+            };
+            gradient.add_color_stop(ColorStop { offset: 0.0, color: ColorU::black() });
+            gradient.add_color_stop(ColorStop { offset: 1.0, color });
+            paints.push(scene.push_paint(&Paint::Gradient(gradient)));
+        }
+        let half_size = Vector2F::new(64.0, 16.0);
+        scene.push_path(rect_path(RectF::new(Vector2F::default(), half_size), paints[0]));
+        scene.push_path(rect_path(RectF::new(Vector2F::new(0.0, 16.0), half_size),
+                                  *paints.last().unwrap()));
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        let commands = build(&scene, RenderOptions::default());
+        let paint_data_size = commands.iter().filter_map(|command| {
+            match *command {
+                RenderCommand::AddPaintData(ref paint_data) => Some(paint_data.size),
+                _ => None,
+            }
+        }).next().unwrap();
+        assert!(paint_data_size.x() <= MAX_TEXTURE_SIZE);
+        assert!(paint_data_size.y() <= MAX_TEXTURE_SIZE);
+
+        // Neither end of either ramp picks up the colors of its neighbors.
+        let renderer = render(&scene, rect.size().to_i32());
+        let last_index = gradient_count - 1;
+        for &(y, g, b) in &[(8, 0, 0), (24, last_index % 256, last_index / 256)] {
+            let (left, right) = (renderer.pixel(Vector2I::new(0, y)),
+                                 renderer.pixel(Vector2I::new(63, y)));
+            assert!(left.r < 8 && left.g < 8 && left.b < 8 && left.a == 255);
+            assert!(right.r > 247 && right.a == 255);
+            assert!((right.g as i32 - g).abs() <= 2 && (right.b as i32 - b).abs() <= 2);
+        }
+    }
+
+    #[test]
+    fn test_render_conic_gradient() {
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(64.0));
+        let framebuffer_size = Vector2I::splat(64);
+
+        let mut gradient = Gradient::conic(Vector2F::splat(32.0), 0.0);
+        let white = ColorU { r: 255, g: 255, b: 255, a: 255 };
+        gradient.add_color_stop(ColorStop { offset: 0.0, color: ColorU::black() });
+        gradient.add_color_stop(ColorStop { offset: 1.0, color: white });
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Gradient(gradient));
+        scene.push_path(rect_path(rect, paint));
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        // The gradient starts just below the positive x axis and goes around through the bottom.
+        let renderer = render(&scene, framebuffer_size);
+        let red_at = |x, y| renderer.pixel(Vector2I::new(x, y)).r;
+        assert!(red_at(60, 32) < 8);
+        assert!(red_at(32, 60) > 56 && red_at(32, 60) < 72);
+        assert!(red_at(4, 32) > 120 && red_at(4, 32) < 136);
+        assert!(red_at(32, 4) > 183 && red_at(32, 4) < 199);
+        assert!(red_at(60, 31) > 247);
+    }
+
+    #[test]
+    fn test_render_pattern() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let green = ColorU { r: 0, g: 255, b: 0, a: 255 };
+        let blue = ColorU { r: 0, g: 0, b: 255, a: 255 };
+        let image = Image::new(Vector2I::splat(2), Arc::new(vec![red, green, blue, red]));
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(32.0));
+
+        // Scale each texel of the image up to 8x8 pixels, and repeat only horizontally.
+        let mut pattern = Pattern::new(image);
+        pattern.transform = Transform2DF::from_scale(Vector2F::splat(8.0));
+        pattern.repeat_y = false;
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Pattern(pattern));
+        scene.push_path(rect_path(rect, paint));
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        let renderer = render(&scene, Vector2I::splat(32));
+        assert_eq!(renderer.pixel(Vector2I::new(3, 3)), red);
+        assert_eq!(renderer.pixel(Vector2I::new(12, 3)), green);
+        assert_eq!(renderer.pixel(Vector2I::new(3, 12)), blue);
+        assert_eq!(renderer.pixel(Vector2I::new(19, 3)), red);
+        assert_eq!(renderer.pixel(Vector2I::new(28, 3)), green);
+        assert_eq!(renderer.pixel(Vector2I::new(3, 20)), ColorU::transparent_black());
+    }
+
+    #[test]
+    fn test_render_pattern_opacity() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let image = Image::new(Vector2I::splat(1), Arc::new(vec![red]));
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(16.0));
+
+        let mut pattern = Pattern::new(image);
+        pattern.opacity = 0.5;
+
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Pattern(pattern));
+        scene.push_path(rect_path(rect, paint));
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        let renderer = render(&scene, Vector2I::splat(16));
+        let pixel = renderer.pixel(Vector2I::new(8, 8));
+        assert_eq!((pixel.g, pixel.b), (0, 0));
+        assert!(pixel.r > 124 && pixel.r < 132);
+        assert!(pixel.a > 124 && pixel.a < 132);
+    }
+
+    #[test]
+    fn test_render_nested_clip_paths() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(64.0));
+
+        // The outer clip path covers the left half, and the inner one the top half, so only the
+        // top left quadrant is drawn. The edges fall in the middle of tiles.
+        let mut scene = Scene::new();
+        let outer_rect = RectF::new(Vector2F::default(), Vector2F::new(40.0, 64.0));
+        let outer = scene.push_clip_path(ClipPath::new(rect_outline(outer_rect), String::new()));
+        let inner_rect = RectF::new(Vector2F::default(), Vector2F::new(64.0, 24.0));
+        let mut inner = ClipPath::new(rect_outline(inner_rect), String::new());
+        inner.set_clip_path(Some(outer));
+        let inner = scene.push_clip_path(inner);
+
+        let paint = scene.push_paint(&Paint::Color(red));
+        let mut path = rect_path(rect, paint);
+        path.set_clip_path(Some(inner));
+        scene.push_path(path);
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        let renderer = render(&scene, Vector2I::splat(64));
+        assert_eq!(renderer.pixel(Vector2I::new(4, 4)), red);
+        assert_eq!(renderer.pixel(Vector2I::new(36, 20)), red);
+        assert_eq!(renderer.pixel(Vector2I::new(44, 4)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(4, 28)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(56, 56)), ColorU::transparent_black());
+    }
+
+    #[test]
+    fn test_render_layer_opacity() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(32.0));
+
+        // Two overlapping opaque paths in a half-transparent layer. Where they overlap, only the
+        // top path should show.
+        let mut scene = Scene::new();
+        let red_paint = scene.push_paint(&Paint::Color(red));
+        let blue_paint = scene.push_paint(&Paint::Color(ColorU { r: 0, g: 0, b: 255, a: 255 }));
+        let mut layer = Layer::new(String::new());
+        layer.set_opacity(0.5);
+        scene.push_layer(layer);
+        scene.push_path(rect_path(RectF::new(Vector2F::default(), Vector2F::new(24.0, 32.0)),
+                                  red_paint));
+        scene.push_path(rect_path(RectF::new(Vector2F::new(8.0, 0.0), Vector2F::new(24.0, 32.0)),
+                                  blue_paint));
+        scene.pop_layer();
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        let renderer = render(&scene, Vector2I::splat(32));
+        let (left, middle) = (renderer.pixel(Vector2I::new(4, 4)),
+                              renderer.pixel(Vector2I::new(16, 4)));
+        assert!(left.r > 120 && left.r < 136 && left.b == 0);
+        assert!(left.a > 120 && left.a < 136);
+        assert!(middle.b > 120 && middle.b < 136 && middle.r == 0);
+        assert!(middle.a > 120 && middle.a < 136);
+    }
+
+    #[test]
+    fn test_render_blend_modes() {
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(32.0));
+        let yellow = ColorU { r: 255, g: 255, b: 0, a: 255 };
+        let cyan = ColorU { r: 0, g: 255, b: 255, a: 255 };
+
+        // Multiply cyan into the left half of a yellow square, and then punch a hole through the
+        // right half with destination-out.
+        let mut scene = Scene::new();
+        let yellow_paint = scene.push_paint(&Paint::Color(yellow));
+        let cyan_paint = scene.push_paint(&Paint::Color(cyan));
+        scene.push_path(rect_path(rect, yellow_paint));
+        let mut multiply_path =
+            rect_path(RectF::new(Vector2F::default(), Vector2F::new(16.0, 32.0)), cyan_paint);
+        multiply_path.set_blend_mode(BlendMode::Multiply);
+        scene.push_path(multiply_path);
+        let mut erase_path =
+            rect_path(RectF::new(Vector2F::new(20.0, 0.0), Vector2F::new(12.0, 32.0)), cyan_paint);
+        erase_path.set_blend_mode(BlendMode::DestinationOut);
+        scene.push_path(erase_path);
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        let renderer = render(&scene, Vector2I::splat(32));
+        assert_eq!(renderer.pixel(Vector2I::new(4, 4)), ColorU { r: 0, g: 255, b: 0, a: 255 });
+        assert_eq!(renderer.pixel(Vector2I::new(18, 4)), yellow);
+        assert_eq!(renderer.pixel(Vector2I::new(26, 4)), ColorU::transparent_black());
+    }
+
+    #[test]
+    fn test_render_layer_that_clears_backdrop() {
+        let yellow = ColorU { r: 255, g: 255, b: 0, a: 255 };
+        let cyan = ColorU { r: 0, g: 255, b: 255, a: 255 };
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(32.0));
+
+        // A source-in path leaves the rest of the yellow square alone, while a source-in layer
+        // clears everything it doesn't draw to, within its clip path.
+        let build_scene = |in_layer: bool| {
+            let mut scene = Scene::new();
+            let yellow_paint = scene.push_paint(&Paint::Color(yellow));
+            let cyan_paint = scene.push_paint(&Paint::Color(cyan));
+            scene.push_path(rect_path(rect, yellow_paint));
+            let mut path = rect_path(RectF::new(Vector2F::splat(2.0), Vector2F::splat(12.0)),
+                                     cyan_paint);
+            if in_layer {
+                let clip_rect = RectF::new(Vector2F::default(), Vector2F::new(16.0, 32.0));
+                let clip_path = scene.push_clip_path(ClipPath::new(rect_outline(clip_rect),
+                                                                   String::new()));
+                let mut layer = Layer::new(String::new());
+                layer.set_blend_mode(BlendMode::SourceIn);
+                layer.set_clip_path(Some(clip_path));
+                scene.push_layer(layer);
+                scene.push_path(path);
+                scene.pop_layer();
+            } else {
+                path.set_blend_mode(BlendMode::SourceIn);
+                scene.push_path(path);
+            }
+            scene.set_view_box(rect);
+            scene.set_bounds(rect);
+            scene
+        };
+
+        let renderer = render(&build_scene(false), Vector2I::splat(32));
+        assert_eq!(renderer.pixel(Vector2I::new(8, 8)), cyan);
+        assert_eq!(renderer.pixel(Vector2I::new(1, 1)), yellow);
+        assert_eq!(renderer.pixel(Vector2I::new(8, 24)), yellow);
+
+        let renderer = render(&build_scene(true), Vector2I::splat(32));
+        assert_eq!(renderer.pixel(Vector2I::new(8, 8)), cyan);
+        assert_eq!(renderer.pixel(Vector2I::new(1, 1)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(8, 24)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(24, 24)), yellow);
+    }
+
+    #[test]
+    fn test_render_non_separable_blend_modes() {
+        let gray = ColorU { r: 128, g: 128, b: 128, a: 255 };
+        let blue = ColorU { r: 0, g: 0, b: 255, a: 255 };
+
+        // Blend blue into a gray square with each mode in its own column.
+        let rect = RectF::new(Vector2F::default(), Vector2F::new(64.0, 16.0));
+        let mut scene = Scene::new();
+        let gray_paint = scene.push_paint(&Paint::Color(gray));
+        let blue_paint = scene.push_paint(&Paint::Color(blue));
+        scene.push_path(rect_path(rect, gray_paint));
+        let blend_modes = [BlendMode::Hue, BlendMode::Saturation, BlendMode::Color,
+                           BlendMode::Luminosity];
+        for (column, &blend_mode) in blend_modes.iter().enumerate() {
+            let column_rect = RectF::new(Vector2F::new(column as f32 * 16.0, 0.0),
+                                         Vector2F::splat(16.0));
+            let mut path = rect_path(column_rect, blue_paint);
+            path.set_blend_mode(blend_mode);
+            scene.push_path(path);
+        }
+        scene.set_view_box(rect);
+        scene.set_bounds(rect);
+
+        // Gray has no hue or saturation to give, and blue is dark.
+        let renderer = render(&scene, Vector2I::new(64, 16));
+        assert_eq!(renderer.pixel(Vector2I::new(8, 8)), gray);
+        assert_eq!(renderer.pixel(Vector2I::new(24, 8)), gray);
+        assert_eq!(renderer.pixel(Vector2I::new(40, 8)), ColorU { r: 112, g: 112, b: 255, a: 255 });
+        assert_eq!(renderer.pixel(Vector2I::new(56, 8)), ColorU { r: 28, g: 28, b: 28, a: 255 });
+    }
+
+    #[test]
+    fn test_render_retained_scene() {
+        // Rebuilding a scene reuses the tiles of the paths and clip paths that didn't change,
+        // even if the scene moved by whole tiles. This must match building from scratch.
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let green = ColorU { r: 0, g: 255, b: 0, a: 255 };
+        let blue = ColorU { r: 0, g: 0, b: 255, a: 255 };
+        let framebuffer_size = Vector2I::splat(64);
+
+        let build_scene = |rects: &[(RectF, ColorU)]| {
+            let mut scene = Scene::new();
+            let clip_rect = RectF::new(Vector2F::new(16.0, 20.5), Vector2F::new(28.25, 24.0));
+            let clip_path = scene.push_clip_path(ClipPath::new(rect_outline(clip_rect),
+                                                               String::new()));
+            for &(rect, color) in rects {
+                let paint = scene.push_paint(&Paint::Color(color));
+                let mut path = rect_path(rect, paint);
+                path.set_clip_path(Some(clip_path));
+                scene.push_path(path);
+            }
+            scene.set_view_box(RectF::new(Vector2F::default(), framebuffer_size.to_f32()));
+            scene.set_bounds(RectF::new(Vector2F::splat(16.0), Vector2F::splat(32.0)));
+            scene
+        };
+
+        let rects = [
+            (RectF::new(Vector2F::new(17.5, 18.25), Vector2F::new(20.0, 12.5)), red),
+            (RectF::new(Vector2F::new(30.75, 24.5), Vector2F::new(14.0, 16.0)), blue),
+        ];
+        let mut scene = build_scene(&rects);
+        render(&scene, framebuffer_size);
+
+        let new_rect = RectF::new(Vector2F::new(20.25, 33.5), Vector2F::new(9.0, 10.75));
+        let paint = scene.push_paint(&Paint::Color(green));
+        let mut path = rect_path(new_rect, paint);
+        path.set_clip_path(scene.path(1).clip_path());
+        scene.replace_path(1, path);
+        let expected_scene = build_scene(&[rects[0], (new_rect, green)]);
+        assert_eq!(render(&scene, framebuffer_size).pixels(),
+                   render(&expected_scene, framebuffer_size).pixels());
+
+        let translation = Transform2DF::from_translation(Vector2F::new(16.0, -16.0));
+        let options = RenderOptions {
+            transform: RenderTransform::Transform2D(translation),
+            ..RenderOptions::default()
+        };
+        let expected_scene = build_scene(&[rects[0], (new_rect, green)]);
+        assert_eq!(render_with_options(&scene, options.clone(), framebuffer_size).pixels(),
+                   render_with_options(&expected_scene, options, framebuffer_size).pixels());
+    }
+
+    #[test]
+    fn test_render_after_cancelled_build() {
+        // A build cancelled partway through tiling a path mustn't leave that path's unfinished
+        // tiles behind for the next build to reuse.
+        let framebuffer_size = Vector2I::splat(64);
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let rect = RectF::new(Vector2F::new(4.5, 6.25), Vector2F::new(52.0, 50.5));
+        scene.push_path(rect_path(rect, paint));
+        scene.set_view_box(RectF::new(Vector2F::default(), framebuffer_size.to_f32()));
+        let expected_pixels = render(&scene.clone(), framebuffer_size).into_pixels();
+
+        for checks in 0..8 {
+            let scene = scene.clone();
+            let executor = CancelAfter { checks_left: AtomicUsize::new(checks) };
+            scene.build(RenderOptions::default(), Box::new(|_| {}), &executor);
+            assert_eq!(render(&scene, framebuffer_size).pixels(), &expected_pixels[..]);
+        }
+    }
+
+    // Reports the build as cancelled once it has checked a given number of times.
+    struct CancelAfter {
+        checks_left: AtomicUsize,
+    }
+
+    impl Executor for CancelAfter {
+        fn flatten_into_vector<T, F>(&self, length: usize, builder: F) -> Vec<T>
+                                     where T: Send, F: Fn(usize) -> Vec<T> + Send + Sync {
+            SequentialExecutor.flatten_into_vector(length, builder)
+        }
+
+        fn is_cancelled(&self) -> bool {
+            let checks_left = self.checks_left.load(Ordering::Relaxed);
+            if checks_left == 0 {
+                return true;
+            }
+            self.checks_left.store(checks_left - 1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    #[test]
+    fn test_render_many_alpha_tiles() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let blue = ColorU { r: 0, g: 0, b: 255, a: 255 };
+        let framebuffer_size = Vector2I::new(16, 16);
+
+        // Every path covers half of the same tile, so each one needs an alpha tile of its own,
+        // more than fit in one page of the mask framebuffer.
+        let mut scene = Scene::new();
+        let blue_paint = scene.push_paint(&Paint::Color(blue));
+        let blue_rect = RectF::new(Vector2F::new(8.0, 0.0), Vector2F::new(8.0, 16.0));
+        for _ in 0..(MAX_ALPHA_TILES_PER_PAGE + 16) {
+            scene.push_path(rect_path(blue_rect, blue_paint));
+        }
+
+        // The clip path's tile is in the first page and the red path's in the second.
+        let clip_rect = RectF::new(Vector2F::default(), Vector2F::new(16.0, 8.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(rect_outline(clip_rect),
+                                                           String::new()));
+        let red_paint = scene.push_paint(&Paint::Color(red));
+        let mut red_path =
+            rect_path(RectF::new(Vector2F::default(), Vector2F::new(8.0, 16.0)), red_paint);
+        red_path.set_clip_path(Some(clip_path));
+        scene.push_path(red_path);
+        scene.set_view_box(RectF::new(Vector2F::default(), framebuffer_size.to_f32()));
+
+        let renderer = render(&scene, framebuffer_size);
+        assert_eq!(renderer.pixel(Vector2I::new(4, 4)), red);
+        assert_eq!(renderer.pixel(Vector2I::new(4, 12)), ColorU::transparent_black());
+        assert_eq!(renderer.pixel(Vector2I::new(12, 4)), blue);
+        assert_eq!(renderer.pixel(Vector2I::new(12, 12)), blue);
+    }
+
+    #[test]
+    fn test_render_path_with_many_alpha_tiles() {
+        let red = ColorU { r: 255, g: 0, b: 0, a: 255 };
+        let framebuffer_size = Vector2I::new(32, 257 * 16);
+
+        // The path covers the left half of every tile in a grid, so it has an alpha tile for
+        // each. The alpha tiles of the last rows go in the second page of the mask framebuffer.
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(red));
+        scene.push_path(PathObject::new(half_tile_grid_outline(256, 257), paint, String::new()));
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::new(256.0 * 16.0,
+                                                                         257.0 * 16.0)));
+
+        let renderer = render(&scene, framebuffer_size);
+        for &y in &[4, 12, 255 * 16 + 4, 256 * 16 + 12] {
+            assert_eq!(renderer.pixel(Vector2I::new(4, y)), red);
+            assert_eq!(renderer.pixel(Vector2I::new(12, y)), ColorU::transparent_black());
+            assert_eq!(renderer.pixel(Vector2I::new(20, y)), red);
+        }
+    }
+
+    #[test]
+    fn test_too_many_clip_tiles() {
+        // Each tile of the clip path needs a tile of the clip mask, and there aren't enough.
+        let mut scene = Scene::new();
+        let clip_path = scene.push_clip_path(ClipPath::new(half_tile_grid_outline(256, 256),
+                                                           String::new()));
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let mut path = rect_path(RectF::new(Vector2F::default(), Vector2F::splat(4096.0)), paint);
+        path.set_clip_path(Some(clip_path));
+        scene.push_path(path);
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(4096.0)));
+
+        let commands = Arc::new(Mutex::new(vec![]));
+        let listener_commands = commands.clone();
+        let listener = move |command: RenderCommand| {
+            listener_commands.lock().unwrap().push(command)
+        };
+        scene.build(RenderOptions::default(), Box::new(listener), &SequentialExecutor);
+
+        let commands = commands.lock().unwrap();
+        assert!(!commands.iter().any(|command| {
+            matches!(*command, RenderCommand::AlphaTile(..) | RenderCommand::SolidTile(_))
+        }));
+        match commands.last() {
+            Some(&RenderCommand::Finish { cancelled: false, error, .. }) => {
+                assert_eq!(error, Some(BuildError::TooManyClipTiles))
+            }
+            _ => panic!("The build didn't finish!"),
+        }
+    }
+
+    #[test]
+    fn test_too_many_paints() {
+        // The ramps of this many gradients fill more rows than the paint texture can have.
+        let mut scene = Scene::new();
+        for gradient_index in 0..70000 {
+            let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::default(),
+                                                                   Vector2F::new(64.0, 0.0)));
+            gradient.add_color_stop(ColorStop { offset: gradient_index as f32 / 70000.0,
+                                                color: ColorU::black() });
+            scene.push_paint(&Paint::Gradient(gradient));
+        }
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+        assert_eq!(build_error(&scene), Some(BuildError::TooManyPaints));
+    }
+
+    #[test]
+    fn test_images_too_large() {
+        let width = MAX_TEXTURE_SIZE + 1;
+        let image = Image::new(Vector2I::new(width, 1),
+                               Arc::new(vec![ColorU::black(); width as usize]));
+        let mut scene = Scene::new();
+        scene.push_paint(&Paint::Pattern(Pattern::new(image)));
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+        assert_eq!(build_error(&scene), Some(BuildError::ImagesTooLarge));
+    }
+
+    #[test]
+    fn test_build_profile() {
+        let framebuffer_size = Vector2I::new(64, 64);
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+
+        // The visible path covers the middle 2x2 tiles and half of each tile around them.
+        scene.push_path(rect_path(RectF::new(Vector2F::splat(8.0), Vector2F::splat(48.0)), paint));
+        scene.push_path(rect_path(RectF::new(Vector2F::splat(128.0), Vector2F::splat(16.0)),
+                                  paint));
+        scene.set_view_box(RectF::new(Vector2F::default(), framebuffer_size.to_f32()));
+
+        let profile = Arc::new(Mutex::new(None));
+        let listener_profile = profile.clone();
+        let listener = move |command: RenderCommand| {
+            if let RenderCommand::Finish { profile, .. } = command {
+                *listener_profile.lock().unwrap() = Some(profile);
+            }
+        };
+        scene.build(RenderOptions::default(), Box::new(listener), &SequentialExecutor);
+
+        let profile = profile.lock().unwrap().take().unwrap();
+        assert_eq!(profile.paths.len(), 2);
+        assert!(profile.paths[0].fill_count > 0);
+        assert_eq!(profile.paths[0].alpha_tile_count, 12);
+        assert_eq!(profile.paths[0].solid_tile_count, 4);
+
+        // The other path is out of view, so it isn't built at all.
+        assert_eq!(profile.paths[1].fill_count, 0);
+        assert_eq!(profile.paths[1].alpha_tile_count, 0);
+        assert_eq!(profile.paths[1].solid_tile_count, 0);
+        assert_eq!(profile.paths[1].tiling_time, Default::default());
+    }
+}
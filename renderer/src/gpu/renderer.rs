@@ -9,8 +9,10 @@
 // except according to those terms.
 
 use crate::gpu::debug::DebugUIPresenter;
-use crate::gpu_data::{AlphaTileBatchPrimitive, FillBatchPrimitive, PaintData};
+use crate::gpu_data::{AlphaTileBatchPrimitive, ClipTileBatchPrimitive};
+use crate::gpu_data::{CompositeTileBatchPrimitive, FillBatchPrimitive, ImageData, PaintData};
 use crate::gpu_data::{RenderCommand, SolidTileBatchPrimitive};
+use crate::paint::{BlendMode, PAINT_METADATA_TEXELS_PER_PAINT, PAINT_METADATA_TEXTURE_WIDTH};
 use crate::post::DefringingKernel;
 use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
 use pathfinder_geometry::basic::vector::{Vector2I, Vector4F};
@@ -39,8 +41,10 @@ const MASK_FRAMEBUFFER_HEIGHT: i32 = TILE_HEIGHT as i32 * 256;
 
 // TODO(pcwalton): Replace with `mem::size_of` calls?
 const FILL_INSTANCE_SIZE: usize = 8;
-const SOLID_TILE_INSTANCE_SIZE: usize = 10;
-const MASK_TILE_INSTANCE_SIZE: usize = 12;
+const SOLID_TILE_INSTANCE_SIZE: usize = 12;
+const MASK_TILE_INSTANCE_SIZE: usize = 20;
+const CLIP_TILE_INSTANCE_SIZE: usize = 6;
+const COMPOSITE_TILE_INSTANCE_SIZE: usize = 8;
 
 const MAX_FILLS_PER_BATCH: usize = 0x4000;
 
@@ -67,7 +71,16 @@ where
     quad_vertex_indices_buffer: D::Buffer,
     fill_vertex_array: FillVertexArray<D>,
     mask_framebuffer: D::Framebuffer,
+    clip_program: ClipProgram<D>,
+    clip_vertex_array: ClipVertexArray<D>,
+    clip_framebuffer: D::Framebuffer,
+    composite_program: CompositeProgram<D>,
+    composite_vertex_array: CompositeVertexArray<D>,
+    layer_framebuffers: Vec<D::Framebuffer>,
+    blend_dest_texture: Option<(TextureFormat, D::Texture)>,
     paint_texture: Option<D::Texture>,
+    paint_metadata_texture: Option<D::Texture>,
+    image_atlas_texture: Option<D::Texture>,
 
     // Postprocessing shader
     postprocess_source_framebuffer: Option<D::Framebuffer>,
@@ -85,6 +98,8 @@ where
 
     // Rendering state
     mask_framebuffer_cleared: bool,
+    clip_framebuffer_cleared: bool,
+    layer_depth: usize,
     buffered_fills: Vec<FillBatchPrimitive>,
 
     // Debug
@@ -109,6 +124,8 @@ where
         dest_framebuffer: DestFramebuffer<D>,
     ) -> Renderer<D> {
         let fill_program = FillProgram::new(&device, resources);
+        let clip_program = ClipProgram::new(&device, resources);
+        let composite_program = CompositeProgram::new(&device, resources);
 
         let solid_multicolor_tile_program = SolidTileMulticolorProgram::new(&device, resources);
         let alpha_multicolor_tile_program = AlphaTileMulticolorProgram::new(&device, resources);
@@ -143,6 +160,18 @@ where
             &quad_vertex_positions_buffer,
             &quad_vertex_indices_buffer,
         );
+        let clip_vertex_array = ClipVertexArray::new(
+            &device,
+            &clip_program,
+            &quad_vertex_positions_buffer,
+            &quad_vertex_indices_buffer,
+        );
+        let composite_vertex_array = CompositeVertexArray::new(
+            &device,
+            &composite_program,
+            &quad_vertex_positions_buffer,
+            &quad_vertex_indices_buffer,
+        );
         let alpha_multicolor_tile_vertex_array = AlphaTileVertexArray::new(
             &device,
             &alpha_multicolor_tile_program.alpha_tile_program,
@@ -186,6 +215,9 @@ where
         let mask_framebuffer_texture =
             device.create_texture(TextureFormat::R16F, mask_framebuffer_size);
         let mask_framebuffer = device.create_framebuffer(mask_framebuffer_texture);
+        let clip_framebuffer_texture =
+            device.create_texture(TextureFormat::R16F, mask_framebuffer_size);
+        let clip_framebuffer = device.create_framebuffer(clip_framebuffer_texture);
 
         let window_size = dest_framebuffer.window_size(&device);
         let debug_ui_presenter = DebugUIPresenter::new(&device, resources, window_size);
@@ -208,7 +240,16 @@ where
             quad_vertex_indices_buffer,
             fill_vertex_array,
             mask_framebuffer,
+            clip_program,
+            clip_vertex_array,
+            clip_framebuffer,
+            composite_program,
+            composite_vertex_array,
+            layer_framebuffers: vec![],
+            blend_dest_texture: None,
             paint_texture: None,
+            paint_metadata_texture: None,
+            image_atlas_texture: None,
 
             postprocess_source_framebuffer: None,
             postprocess_program,
@@ -228,6 +269,8 @@ where
             debug_ui_presenter,
 
             mask_framebuffer_cleared: false,
+            clip_framebuffer_cleared: false,
+            layer_depth: 0,
             buffered_fills: vec![],
 
             render_mode: RenderMode::default(),
@@ -244,6 +287,8 @@ where
         self.init_postprocessing_framebuffer();
 
         self.mask_framebuffer_cleared = false;
+        self.clip_framebuffer_cleared = false;
+        self.layer_depth = 0;
         self.stats = RenderStats::default();
     }
 
@@ -255,11 +300,20 @@ where
                 }
                 self.stats.path_count = path_count;
             }
+            RenderCommand::AddImageData(ref image_data) => self.upload_image_data(image_data),
             RenderCommand::AddPaintData(ref paint_data) => self.upload_paint_data(paint_data),
             RenderCommand::AddFills(ref fills) => self.add_fills(fills),
             RenderCommand::FlushFills => {
-                self.begin_composite_timer_query();
+                if self.current_timers.stage_1.is_none() {
+                    self.begin_composite_timer_query();
+                }
                 self.draw_buffered_fills();
+                // The next page of fills starts from a clear mask.
+                self.mask_framebuffer_cleared = false;
+            }
+            RenderCommand::ClipTiles(ref clip_tiles) => {
+                self.upload_clip_tiles(clip_tiles);
+                self.draw_clip_tiles(clip_tiles.len() as u32);
             }
             RenderCommand::SolidTile(ref solid_tiles) => {
                 let count = solid_tiles.len();
@@ -267,11 +321,16 @@ where
                 self.upload_solid_tiles(solid_tiles);
                 self.draw_solid_tiles(count as u32);
             }
-            RenderCommand::AlphaTile(ref alpha_tiles) => {
+            RenderCommand::AlphaTile(ref alpha_tiles, blend_mode) => {
                 let count = alpha_tiles.len();
                 self.stats.alpha_tile_count += count;
                 self.upload_alpha_tiles(alpha_tiles);
-                self.draw_alpha_tiles(count as u32);
+                self.draw_alpha_tiles(count as u32, blend_mode);
+            }
+            RenderCommand::PushLayer => self.push_layer(),
+            RenderCommand::PopLayer(ref composite_tiles, blend_mode) => {
+                self.upload_composite_tiles(composite_tiles);
+                self.pop_layer(composite_tiles.len() as u32, blend_mode);
             }
             RenderCommand::Finish { .. } => {}
         }
@@ -363,6 +422,21 @@ where
         &self.quad_vertex_indices_buffer
     }
 
+    fn upload_image_data(&mut self, image_data: &ImageData) {
+        match self.image_atlas_texture {
+            Some(ref image_atlas_texture) if
+                self.device.texture_size(image_atlas_texture) == image_data.size => {}
+            _ => {
+                let texture = self.device.create_texture(TextureFormat::RGBA8, image_data.size);
+                self.image_atlas_texture = Some(texture)
+            }
+        }
+
+        self.device.upload_to_texture(self.image_atlas_texture.as_ref().unwrap(),
+                                      image_data.size,
+                                      &image_data.texels);
+    }
+
     fn upload_paint_data(&mut self, paint_data: &PaintData) {
         match self.paint_texture {
             Some(ref paint_texture) if
@@ -376,6 +450,32 @@ where
         self.device.upload_to_texture(self.paint_texture.as_ref().unwrap(),
                                       paint_data.size,
                                       &paint_data.texels);
+
+        let texel_count = cmp::max(paint_data.metadata.len() as i32, 1) *
+            PAINT_METADATA_TEXELS_PER_PAINT;
+        let metadata_size = Vector2I::new(PAINT_METADATA_TEXTURE_WIDTH,
+                                          (texel_count + PAINT_METADATA_TEXTURE_WIDTH - 1) /
+                                          PAINT_METADATA_TEXTURE_WIDTH);
+        match self.paint_metadata_texture {
+            Some(ref paint_metadata_texture) if
+                self.device.texture_size(paint_metadata_texture) == metadata_size => {}
+            _ => {
+                let texture = self.device.create_texture(TextureFormat::RGBA32F, metadata_size);
+                self.paint_metadata_texture = Some(texture)
+            }
+        }
+
+        let mut metadata_bytes = Vec::with_capacity(metadata_size.x() as usize *
+                                                    metadata_size.y() as usize * 16);
+        for metadata in &paint_data.metadata {
+            for value in metadata.to_f32_array().iter() {
+                metadata_bytes.extend_from_slice(&value.to_bits().to_ne_bytes());
+            }
+        }
+        metadata_bytes.resize(metadata_bytes.capacity(), 0);
+        self.device.upload_to_texture(self.paint_metadata_texture.as_ref().unwrap(),
+                                      metadata_size,
+                                      &metadata_bytes);
     }
 
     fn upload_solid_tiles(&mut self, solid_tiles: &[SolidTileBatchPrimitive]) {
@@ -396,6 +496,24 @@ where
         );
     }
 
+    fn upload_clip_tiles(&mut self, clip_tiles: &[ClipTileBatchPrimitive]) {
+        self.device.allocate_buffer(
+            &self.clip_vertex_array.vertex_buffer,
+            BufferData::Memory(clip_tiles),
+            BufferTarget::Vertex,
+            BufferUploadMode::Dynamic,
+        );
+    }
+
+    fn upload_composite_tiles(&mut self, composite_tiles: &[CompositeTileBatchPrimitive]) {
+        self.device.allocate_buffer(
+            &self.composite_vertex_array.vertex_buffer,
+            BufferData::Memory(composite_tiles),
+            BufferTarget::Vertex,
+            BufferUploadMode::Dynamic,
+        );
+    }
+
     fn clear_mask_framebuffer(&mut self) {
         self.device.bind_framebuffer(&self.mask_framebuffer);
 
@@ -481,8 +599,119 @@ where
         self.buffered_fills.clear()
     }
 
-    fn draw_alpha_tiles(&mut self, count: u32) {
+    fn draw_clip_tiles(&mut self, count: u32) {
+        self.device.bind_framebuffer(&self.clip_framebuffer);
+
+        // The clip mask starts out fully covered, and each clip tile is multiplied into it.
+        if !self.clip_framebuffer_cleared {
+            // TODO(pcwalton): Only clear the appropriate portion?
+            self.device.clear(&ClearParams {
+                color: Some(ColorF::white()),
+                ..ClearParams::default()
+            });
+            self.clip_framebuffer_cleared = true;
+        }
+
+        self.device.bind_vertex_array(&self.clip_vertex_array.vertex_array);
+        self.device.use_program(&self.clip_program.program);
+        self.device.set_uniform(
+            &self.clip_program.framebuffer_size_uniform,
+            UniformData::Vec2(
+                I32x4::new(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT, 0, 0).to_f32x4(),
+            ),
+        );
+        self.device.set_uniform(
+            &self.clip_program.tile_size_uniform,
+            UniformData::Vec2(I32x4::new(TILE_WIDTH as i32, TILE_HEIGHT as i32, 0, 0).to_f32x4()),
+        );
+        self.device
+            .bind_texture(self.device.framebuffer_texture(&self.mask_framebuffer), 0);
+        self.device.set_uniform(
+            &self.clip_program.stencil_texture_uniform,
+            UniformData::TextureUnit(0),
+        );
+        let render_state = RenderState {
+            blend: BlendState::RGBDestColorAlphaZero,
+            ..RenderState::default()
+        };
+        self.device.draw_elements_instanced(Primitive::Triangles, 6, count, &render_state);
+    }
+
+    fn push_layer(&mut self) {
+        // Layer framebuffers are kept around from frame to frame and reused.
+        let layer_size = self.draw_viewport().size();
+        match self.layer_framebuffers.get(self.layer_depth) {
+            Some(framebuffer) if
+                self.device.texture_size(self.device.framebuffer_texture(framebuffer)) ==
+                    layer_size => {}
+            _ => {
+                let texture = self.device.create_texture(TextureFormat::RGBA8, layer_size);
+                let framebuffer = self.device.create_framebuffer(texture);
+                if self.layer_depth < self.layer_framebuffers.len() {
+                    self.layer_framebuffers[self.layer_depth] = framebuffer;
+                } else {
+                    self.layer_framebuffers.push(framebuffer);
+                }
+            }
+        }
+
+        self.layer_depth += 1;
+        self.bind_draw_framebuffer();
+        self.device.clear(&ClearParams {
+            color: Some(ColorF::transparent_black()),
+            ..ClearParams::default()
+        });
+    }
+
+    fn pop_layer(&mut self, count: u32, blend_mode: BlendMode) {
+        self.layer_depth -= 1;
         self.bind_draw_framebuffer();
+        let dest_rect = self.copy_blend_dest_texture(blend_mode);
+
+        let layer_texture =
+            self.device.framebuffer_texture(&self.layer_framebuffers[self.layer_depth]);
+
+        self.device.bind_vertex_array(&self.composite_vertex_array.vertex_array);
+        self.device.use_program(&self.composite_program.program);
+        self.device.set_uniform(
+            &self.composite_program.framebuffer_size_uniform,
+            UniformData::Vec2(self.draw_viewport().size().to_f32().0),
+        );
+        self.device.set_uniform(
+            &self.composite_program.tile_size_uniform,
+            UniformData::Vec2(I32x4::new(TILE_WIDTH as i32, TILE_HEIGHT as i32, 0, 0).to_f32x4()),
+        );
+        self.device.bind_texture(layer_texture, 0);
+        self.device.set_uniform(
+            &self.composite_program.layer_uniform,
+            UniformData::TextureUnit(0),
+        );
+        self.device
+            .bind_texture(self.device.framebuffer_texture(&self.clip_framebuffer), 1);
+        self.device.set_uniform(
+            &self.composite_program.clip_mask_uniform,
+            UniformData::TextureUnit(1),
+        );
+        self.device.set_uniform(
+            &self.composite_program.clip_mask_size_uniform,
+            UniformData::Vec2(
+                I32x4::new(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT, 0, 0).to_f32x4(),
+            ),
+        );
+        self.set_blend_uniforms(&self.composite_program.blend_uniforms, blend_mode, dest_rect, 2);
+
+        // The layer is premultiplied.
+        let render_state = RenderState {
+            blend: blend_state(blend_mode, BlendState::RGBOneAlphaOneMinusSrcAlpha),
+            stencil: self.stencil_state(),
+            ..RenderState::default()
+        };
+        self.device.draw_elements_instanced(Primitive::Triangles, 6, count, &render_state);
+    }
+
+    fn draw_alpha_tiles(&mut self, count: u32, blend_mode: BlendMode) {
+        self.bind_draw_framebuffer();
+        let dest_rect = self.copy_blend_dest_texture(blend_mode);
 
         let alpha_tile_vertex_array = self.alpha_tile_vertex_array();
         let alpha_tile_program = self.alpha_tile_program();
@@ -510,18 +739,30 @@ where
                 I32x4::new(MASK_FRAMEBUFFER_WIDTH, MASK_FRAMEBUFFER_HEIGHT, 0, 0).to_f32x4(),
             ),
         );
+        self.device
+            .bind_texture(self.device.framebuffer_texture(&self.clip_framebuffer), 4);
+        self.device.set_uniform(
+            &alpha_tile_program.clip_mask_uniform,
+            UniformData::TextureUnit(4),
+        );
+        self.set_blend_uniforms(&alpha_tile_program.blend_uniforms, blend_mode, dest_rect, 5);
 
         match self.render_mode {
             RenderMode::Multicolor => {
-                let paint_texture = self.paint_texture.as_ref().unwrap();
-                self.device.bind_texture(paint_texture, 1);
+                self.device.bind_texture(self.paint_texture.as_ref().unwrap(), 1);
                 self.device.set_uniform(
                     &self.alpha_multicolor_tile_program.paint_texture_uniform,
                     UniformData::TextureUnit(1),
                 );
+                self.device.bind_texture(self.paint_metadata_texture.as_ref().unwrap(), 2);
+                self.device.set_uniform(
+                    &self.alpha_multicolor_tile_program.paint_metadata_uniform,
+                    UniformData::TextureUnit(2),
+                );
+                self.device.bind_texture(self.image_atlas_texture.as_ref().unwrap(), 3);
                 self.device.set_uniform(
-                    &self.alpha_multicolor_tile_program.paint_texture_size_uniform,
-                    UniformData::Vec2(self.device.texture_size(paint_texture).0.to_f32x4())
+                    &self.alpha_multicolor_tile_program.image_atlas_uniform,
+                    UniformData::TextureUnit(3),
                 );
             }
             RenderMode::Monochrome { .. } if self.postprocessing_needed() => {
@@ -544,13 +785,67 @@ where
             UniformData::Vec2(F32x4::default()),
         );
         let render_state = RenderState {
-            blend: BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha,
+            blend: blend_state(blend_mode, BlendState::RGBSrcAlphaAlphaOneMinusSrcAlpha),
             stencil: self.stencil_state(),
             ..RenderState::default()
         };
         self.device.draw_elements_instanced(Primitive::Triangles, 6, count, &render_state);
     }
 
+    /// Copies the area of the draw framebuffer that tiles are drawn to into the blend
+    /// destination texture, if the blend mode needs to read the destination. Returns that area.
+    ///
+    /// This must be called with the draw framebuffer bound.
+    fn copy_blend_dest_texture(&mut self, blend_mode: BlendMode) -> RectI {
+        let dest_rect = if self.layer_depth > 0 || self.postprocessing_needed() {
+            RectI::new(Vector2I::default(), self.draw_viewport().size())
+        } else {
+            self.main_viewport()
+        };
+        if blend_mode.is_source_over() {
+            return dest_rect;
+        }
+
+        // The copy must have the same components as the framebuffer it's copied from.
+        let format = if self.layer_depth == 0 && self.postprocessing_needed() {
+            TextureFormat::R8
+        } else {
+            TextureFormat::RGBA8
+        };
+        match self.blend_dest_texture {
+            Some((texture_format, ref texture)) if texture_format == format &&
+                self.device.texture_size(texture) == dest_rect.size() => {}
+            _ => {
+                let texture = self.device.create_texture(format, dest_rect.size());
+                self.blend_dest_texture = Some((format, texture));
+            }
+        }
+
+        let (_, ref texture) = *self.blend_dest_texture.as_ref().unwrap();
+        self.device.copy_framebuffer_to_texture(texture, dest_rect);
+        dest_rect
+    }
+
+    fn set_blend_uniforms(&self,
+                          blend_uniforms: &BlendUniforms<D>,
+                          blend_mode: BlendMode,
+                          dest_rect: RectI,
+                          texture_unit: u32) {
+        self.device.set_uniform(&blend_uniforms.blend_mode_uniform,
+                                UniformData::Int(blend_mode.to_shader_index()));
+        if let Some((_, ref blend_dest_texture)) = self.blend_dest_texture {
+            self.device.bind_texture(blend_dest_texture, texture_unit);
+        }
+        self.device.set_uniform(&blend_uniforms.dest_uniform,
+                                UniformData::TextureUnit(texture_unit));
+        let (dest_origin, dest_size) = (dest_rect.origin(), dest_rect.size());
+        self.device.set_uniform(&blend_uniforms.dest_rect_uniform,
+                                UniformData::Vec4(I32x4::new(dest_origin.x(),
+                                                             dest_origin.y(),
+                                                             dest_size.x(),
+                                                             dest_size.y()).to_f32x4()));
+    }
+
     fn draw_solid_tiles(&mut self, count: u32) {
         self.bind_draw_framebuffer();
 
@@ -571,19 +866,26 @@ where
 
         match self.render_mode {
             RenderMode::Multicolor => {
-                let paint_texture = self.paint_texture.as_ref().unwrap();
-                self.device.bind_texture(paint_texture, 0);
+                self.device.bind_texture(self.paint_texture.as_ref().unwrap(), 0);
                 self.device.set_uniform(
                     &self
                         .solid_multicolor_tile_program
                         .paint_texture_uniform,
                     UniformData::TextureUnit(0),
                 );
+                self.device.bind_texture(self.paint_metadata_texture.as_ref().unwrap(), 1);
+                self.device.set_uniform(
+                    &self
+                        .solid_multicolor_tile_program
+                        .paint_metadata_uniform,
+                    UniformData::TextureUnit(1),
+                );
+                self.device.bind_texture(self.image_atlas_texture.as_ref().unwrap(), 2);
                 self.device.set_uniform(
                     &self
                         .solid_multicolor_tile_program
-                        .paint_texture_size_uniform,
-                    UniformData::Vec2(self.device.texture_size(paint_texture).0.to_f32x4())
+                        .image_atlas_uniform,
+                    UniformData::TextureUnit(2),
                 );
             }
             RenderMode::Monochrome { .. } if self.postprocessing_needed() => {
@@ -799,7 +1101,9 @@ where
     }
 
     pub fn bind_draw_framebuffer(&self) {
-        if self.postprocessing_needed() {
+        if self.layer_depth > 0 {
+            self.device.bind_framebuffer(&self.layer_framebuffers[self.layer_depth - 1]);
+        } else if self.postprocessing_needed() {
             self.device
                 .bind_framebuffer(self.postprocess_source_framebuffer.as_ref().unwrap());
         } else {
@@ -1033,8 +1337,10 @@ where
         let tile_origin_attr = device.get_vertex_attr(&alpha_tile_program.program, "TileOrigin");
         let backdrop_attr = device.get_vertex_attr(&alpha_tile_program.program, "Backdrop");
         let tile_index_attr = device.get_vertex_attr(&alpha_tile_program.program, "TileIndex");
-        let color_tex_coord_attr = device.get_vertex_attr(&alpha_tile_program.program,
-                                                          "ColorTexCoord");
+        let paint_id_attr = device.get_vertex_attr(&alpha_tile_program.program, "PaintId");
+        let fill_rule_attr = device.get_vertex_attr(&alpha_tile_program.program, "FillRule");
+        let clip_tile_index_attr =
+            device.get_vertex_attr(&alpha_tile_program.program, "ClipTileIndex");
 
         // NB: The object must be of type `I16`, not `U16`, to work around a macOS Radeon
         // driver bug.
@@ -1071,23 +1377,183 @@ where
             class: VertexAttrClass::Int,
             attr_type: VertexAttrType::I16,
             stride: MASK_TILE_INSTANCE_SIZE,
-            offset: 6,
+            offset: 4,
             divisor: 1,
         });
-        device.configure_vertex_attr(&color_tex_coord_attr, &VertexAttrDescriptor {
-            size: 2,
-            class: VertexAttrClass::FloatNorm,
+        device.configure_vertex_attr(&clip_tile_index_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
             attr_type: VertexAttrType::U16,
             stride: MASK_TILE_INSTANCE_SIZE,
+            offset: 6,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&paint_id_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U32,
+            stride: MASK_TILE_INSTANCE_SIZE,
             offset: 8,
             divisor: 1,
         });
+        device.configure_vertex_attr(&fill_rule_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U8,
+            stride: MASK_TILE_INSTANCE_SIZE,
+            offset: 16,
+            divisor: 1,
+        });
         device.bind_buffer(quad_vertex_indices_buffer, BufferTarget::Index);
 
         AlphaTileVertexArray { vertex_array, vertex_buffer }
     }
 }
 
+struct ClipVertexArray<D>
+where
+    D: Device,
+{
+    vertex_array: D::VertexArray,
+    vertex_buffer: D::Buffer,
+}
+
+impl<D> ClipVertexArray<D>
+where
+    D: Device,
+{
+    fn new(
+        device: &D,
+        clip_program: &ClipProgram<D>,
+        quad_vertex_positions_buffer: &D::Buffer,
+        quad_vertex_indices_buffer: &D::Buffer,
+    ) -> ClipVertexArray<D> {
+        let (vertex_array, vertex_buffer) = (device.create_vertex_array(), device.create_buffer());
+
+        let tess_coord_attr = device.get_vertex_attr(&clip_program.program, "TessCoord");
+        let dest_tile_index_attr = device.get_vertex_attr(&clip_program.program, "DestTileIndex");
+        let src_tile_index_attr = device.get_vertex_attr(&clip_program.program, "SrcTileIndex");
+        let src_backdrop_attr = device.get_vertex_attr(&clip_program.program, "SrcBackdrop");
+        let src_fill_rule_attr = device.get_vertex_attr(&clip_program.program, "SrcFillRule");
+
+        device.bind_vertex_array(&vertex_array);
+        device.use_program(&clip_program.program);
+        device.bind_buffer(quad_vertex_positions_buffer, BufferTarget::Vertex);
+        device.configure_vertex_attr(&tess_coord_attr, &VertexAttrDescriptor {
+            size: 2,
+            class: VertexAttrClass::Float,
+            attr_type: VertexAttrType::U8,
+            stride: 0,
+            offset: 0,
+            divisor: 0,
+        });
+        device.bind_buffer(&vertex_buffer, BufferTarget::Vertex);
+        device.configure_vertex_attr(&dest_tile_index_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U16,
+            stride: CLIP_TILE_INSTANCE_SIZE,
+            offset: 0,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&src_tile_index_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U16,
+            stride: CLIP_TILE_INSTANCE_SIZE,
+            offset: 2,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&src_backdrop_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::I8,
+            stride: CLIP_TILE_INSTANCE_SIZE,
+            offset: 4,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&src_fill_rule_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U8,
+            stride: CLIP_TILE_INSTANCE_SIZE,
+            offset: 5,
+            divisor: 1,
+        });
+        device.bind_buffer(quad_vertex_indices_buffer, BufferTarget::Index);
+
+        ClipVertexArray { vertex_array, vertex_buffer }
+    }
+}
+
+struct CompositeVertexArray<D>
+where
+    D: Device,
+{
+    vertex_array: D::VertexArray,
+    vertex_buffer: D::Buffer,
+}
+
+impl<D> CompositeVertexArray<D>
+where
+    D: Device,
+{
+    fn new(
+        device: &D,
+        composite_program: &CompositeProgram<D>,
+        quad_vertex_positions_buffer: &D::Buffer,
+        quad_vertex_indices_buffer: &D::Buffer,
+    ) -> CompositeVertexArray<D> {
+        let (vertex_array, vertex_buffer) = (device.create_vertex_array(), device.create_buffer());
+
+        let tess_coord_attr = device.get_vertex_attr(&composite_program.program, "TessCoord");
+        let tile_origin_attr = device.get_vertex_attr(&composite_program.program, "TileOrigin");
+        let clip_tile_index_attr =
+            device.get_vertex_attr(&composite_program.program, "ClipTileIndex");
+        let opacity_attr = device.get_vertex_attr(&composite_program.program, "Opacity");
+
+        device.bind_vertex_array(&vertex_array);
+        device.use_program(&composite_program.program);
+        device.bind_buffer(quad_vertex_positions_buffer, BufferTarget::Vertex);
+        device.configure_vertex_attr(&tess_coord_attr, &VertexAttrDescriptor {
+            size: 2,
+            class: VertexAttrClass::Float,
+            attr_type: VertexAttrType::U8,
+            stride: 0,
+            offset: 0,
+            divisor: 0,
+        });
+        device.bind_buffer(&vertex_buffer, BufferTarget::Vertex);
+        device.configure_vertex_attr(&tile_origin_attr, &VertexAttrDescriptor {
+            size: 2,
+            class: VertexAttrClass::Float,
+            attr_type: VertexAttrType::I16,
+            stride: COMPOSITE_TILE_INSTANCE_SIZE,
+            offset: 0,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&clip_tile_index_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U16,
+            stride: COMPOSITE_TILE_INSTANCE_SIZE,
+            offset: 4,
+            divisor: 1,
+        });
+        device.configure_vertex_attr(&opacity_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::FloatNorm,
+            attr_type: VertexAttrType::U8,
+            stride: COMPOSITE_TILE_INSTANCE_SIZE,
+            offset: 6,
+            divisor: 1,
+        });
+        device.bind_buffer(quad_vertex_indices_buffer, BufferTarget::Index);
+
+        CompositeVertexArray { vertex_array, vertex_buffer }
+    }
+}
+
 struct SolidTileVertexArray<D>
 where
     D: Device,
@@ -1110,8 +1576,7 @@ where
 
         let tess_coord_attr = device.get_vertex_attr(&solid_tile_program.program, "TessCoord");
         let tile_origin_attr = device.get_vertex_attr(&solid_tile_program.program, "TileOrigin");
-        let color_tex_coord_attr = device.get_vertex_attr(&solid_tile_program.program,
-                                                          "ColorTexCoord");
+        let paint_id_attr = device.get_vertex_attr(&solid_tile_program.program, "PaintId");
 
         // NB: The object must be of type short, not unsigned short, to work around a macOS
         // Radeon driver bug.
@@ -1135,10 +1600,10 @@ where
             offset: 0,
             divisor: 1,
         });
-        device.configure_vertex_attr(&color_tex_coord_attr, &VertexAttrDescriptor {
-            size: 2,
-            class: VertexAttrClass::FloatNorm,
-            attr_type: VertexAttrType::U16,
+        device.configure_vertex_attr(&paint_id_attr, &VertexAttrDescriptor {
+            size: 1,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::U32,
             stride: SOLID_TILE_INSTANCE_SIZE,
             offset: 4,
             divisor: 1,
@@ -1177,6 +1642,71 @@ where
     }
 }
 
+struct ClipProgram<D>
+where
+    D: Device,
+{
+    program: D::Program,
+    framebuffer_size_uniform: D::Uniform,
+    tile_size_uniform: D::Uniform,
+    stencil_texture_uniform: D::Uniform,
+}
+
+impl<D> ClipProgram<D>
+where
+    D: Device,
+{
+    fn new(device: &D, resources: &dyn ResourceLoader) -> ClipProgram<D> {
+        let program = device.create_program(resources, "tile_clip");
+        let framebuffer_size_uniform = device.get_uniform(&program, "FramebufferSize");
+        let tile_size_uniform = device.get_uniform(&program, "TileSize");
+        let stencil_texture_uniform = device.get_uniform(&program, "StencilTexture");
+        ClipProgram {
+            program,
+            framebuffer_size_uniform,
+            tile_size_uniform,
+            stencil_texture_uniform,
+        }
+    }
+}
+
+struct CompositeProgram<D>
+where
+    D: Device,
+{
+    program: D::Program,
+    framebuffer_size_uniform: D::Uniform,
+    tile_size_uniform: D::Uniform,
+    layer_uniform: D::Uniform,
+    clip_mask_uniform: D::Uniform,
+    clip_mask_size_uniform: D::Uniform,
+    blend_uniforms: BlendUniforms<D>,
+}
+
+impl<D> CompositeProgram<D>
+where
+    D: Device,
+{
+    fn new(device: &D, resources: &dyn ResourceLoader) -> CompositeProgram<D> {
+        let program = device.create_program(resources, "tile_composite");
+        let framebuffer_size_uniform = device.get_uniform(&program, "FramebufferSize");
+        let tile_size_uniform = device.get_uniform(&program, "TileSize");
+        let layer_uniform = device.get_uniform(&program, "Layer");
+        let clip_mask_uniform = device.get_uniform(&program, "ClipMask");
+        let clip_mask_size_uniform = device.get_uniform(&program, "ClipMaskSize");
+        let blend_uniforms = BlendUniforms::new(device, &program);
+        CompositeProgram {
+            program,
+            framebuffer_size_uniform,
+            tile_size_uniform,
+            layer_uniform,
+            clip_mask_uniform,
+            clip_mask_size_uniform,
+            blend_uniforms,
+        }
+    }
+}
+
 struct SolidTileProgram<D>
 where
     D: Device,
@@ -1216,7 +1746,8 @@ where
 {
     solid_tile_program: SolidTileProgram<D>,
     paint_texture_uniform: D::Uniform,
-    paint_texture_size_uniform: D::Uniform,
+    paint_metadata_uniform: D::Uniform,
+    image_atlas_uniform: D::Uniform,
 }
 
 impl<D> SolidTileMulticolorProgram<D>
@@ -1227,12 +1758,14 @@ where
         let solid_tile_program = SolidTileProgram::new(device, "tile_solid_multicolor", resources);
         let paint_texture_uniform =
             device.get_uniform(&solid_tile_program.program, "PaintTexture");
-        let paint_texture_size_uniform =
-            device.get_uniform(&solid_tile_program.program, "PaintTextureSize");
+        let paint_metadata_uniform =
+            device.get_uniform(&solid_tile_program.program, "PaintMetadata");
+        let image_atlas_uniform = device.get_uniform(&solid_tile_program.program, "ImageAtlas");
         SolidTileMulticolorProgram {
             solid_tile_program,
             paint_texture_uniform,
-            paint_texture_size_uniform,
+            paint_metadata_uniform,
+            image_atlas_uniform,
         }
     }
 }
@@ -1268,7 +1801,9 @@ where
     tile_size_uniform: D::Uniform,
     stencil_texture_uniform: D::Uniform,
     stencil_texture_size_uniform: D::Uniform,
+    clip_mask_uniform: D::Uniform,
     view_box_origin_uniform: D::Uniform,
+    blend_uniforms: BlendUniforms<D>,
 }
 
 impl<D> AlphaTileProgram<D>
@@ -1286,25 +1821,52 @@ where
         let tile_size_uniform = device.get_uniform(&program, "TileSize");
         let stencil_texture_uniform = device.get_uniform(&program, "StencilTexture");
         let stencil_texture_size_uniform = device.get_uniform(&program, "StencilTextureSize");
+        let clip_mask_uniform = device.get_uniform(&program, "ClipMask");
         let view_box_origin_uniform = device.get_uniform(&program, "ViewBoxOrigin");
+        let blend_uniforms = BlendUniforms::new(device, &program);
         AlphaTileProgram {
             program,
             framebuffer_size_uniform,
             tile_size_uniform,
             stencil_texture_uniform,
             stencil_texture_size_uniform,
+            clip_mask_uniform,
             view_box_origin_uniform,
+            blend_uniforms,
         }
     }
 }
 
+/// The uniforms of `tile_blend.inc.glsl`.
+struct BlendUniforms<D>
+where
+    D: Device,
+{
+    blend_mode_uniform: D::Uniform,
+    dest_uniform: D::Uniform,
+    dest_rect_uniform: D::Uniform,
+}
+
+impl<D> BlendUniforms<D>
+where
+    D: Device,
+{
+    fn new(device: &D, program: &D::Program) -> BlendUniforms<D> {
+        let blend_mode_uniform = device.get_uniform(program, "BlendMode");
+        let dest_uniform = device.get_uniform(program, "Dest");
+        let dest_rect_uniform = device.get_uniform(program, "DestRect");
+        BlendUniforms { blend_mode_uniform, dest_uniform, dest_rect_uniform }
+    }
+}
+
 struct AlphaTileMulticolorProgram<D>
 where
     D: Device,
 {
     alpha_tile_program: AlphaTileProgram<D>,
     paint_texture_uniform: D::Uniform,
-    paint_texture_size_uniform: D::Uniform,
+    paint_metadata_uniform: D::Uniform,
+    image_atlas_uniform: D::Uniform,
 }
 
 impl<D> AlphaTileMulticolorProgram<D>
@@ -1315,12 +1877,14 @@ where
         let alpha_tile_program = AlphaTileProgram::new(device, "tile_alpha_multicolor", resources);
         let paint_texture_uniform =
             device.get_uniform(&alpha_tile_program.program, "PaintTexture");
-        let paint_texture_size_uniform =
-            device.get_uniform(&alpha_tile_program.program, "PaintTextureSize");
+        let paint_metadata_uniform =
+            device.get_uniform(&alpha_tile_program.program, "PaintMetadata");
+        let image_atlas_uniform = device.get_uniform(&alpha_tile_program.program, "ImageAtlas");
         AlphaTileMulticolorProgram {
             alpha_tile_program,
             paint_texture_uniform,
-            paint_texture_size_uniform,
+            paint_metadata_uniform,
+            image_atlas_uniform,
         }
     }
 }
@@ -1665,3 +2229,12 @@ impl Add<RenderTime> for RenderTime {
         }
     }
 }
+
+// Blend modes other than source-over are computed in the shader and drawn with blending off.
+fn blend_state(blend_mode: BlendMode, source_over_blend_state: BlendState) -> BlendState {
+    if blend_mode.is_source_over() {
+        source_over_blend_state
+    } else {
+        BlendState::Off
+    }
+}
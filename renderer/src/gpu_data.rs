@@ -11,35 +11,154 @@
 //! Packed data ready to be sent to the GPU.
 
 use crate::options::BoundingQuad;
+use crate::paint::BlendMode;
 use crate::tile_map::DenseTileMap;
 use pathfinder_geometry::basic::line_segment::{LineSegmentU4, LineSegmentU8};
 use pathfinder_geometry::basic::vector::Vector2I;
-use pathfinder_geometry::basic::rect::RectF;
 use std::fmt::{Debug, Formatter, Result as DebugResult};
 use std::time::Duration;
 
+/// A tiled outline. Its alpha tile indices are local to the object, and its tiles are where the
+/// outline was when it was tiled.
 #[derive(Debug)]
 pub(crate) struct BuiltObject {
-    pub bounds: RectF,
-    pub fills: Vec<FillBatchPrimitive>,
-    pub alpha_tiles: Vec<AlphaTileBatchPrimitive>,
+    pub fills: Vec<ObjectFill>,
     pub tiles: DenseTileMap<TileObjectPrimitive>,
+    /// The number of alpha tiles allocated so far. There can be more than fit in one page of the
+    /// mask framebuffer.
+    pub alpha_tile_count: u32,
+}
+
+/// A fill of a built object, before the object's alpha tiles are given pages.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ObjectFill {
+    pub px: LineSegmentU4,
+    pub subpx: LineSegmentU8,
+    pub alpha_tile_index: u32,
 }
 
 pub enum RenderCommand {
     Start { path_count: usize, bounding_quad: BoundingQuad },
+    AddImageData(ImageData),
     AddPaintData(PaintData),
     AddFills(Vec<FillBatchPrimitive>),
+    /// Finishes drawing the fills of one page of alpha tiles into the mask. Fills added after
+    /// this start a new page, reusing the same alpha tile indices.
     FlushFills,
-    AlphaTile(Vec<AlphaTileBatchPrimitive>),
+    ClipTiles(Vec<ClipTileBatchPrimitive>),
+    /// Draws alpha tiles with a blend mode. Unless the mode is source-over, the tiles of a batch
+    /// all belong to one path and so never overlap.
+    AlphaTile(Vec<AlphaTileBatchPrimitive>, BlendMode),
     SolidTile(Vec<SolidTileBatchPrimitive>),
-    Finish { build_time: Duration },
+    /// Directs subsequent tiles to a new transparent offscreen layer.
+    PushLayer,
+    /// Composites the current layer onto the one beneath it, one tile at a time.
+    PopLayer(Vec<CompositeTileBatchPrimitive>, BlendMode),
+    /// Ends the commands of a build. If the build was cancelled, or failed with an error, no tiles
+    /// were sent, and the profile is empty.
+    Finish {
+        build_time: Duration,
+        profile: BuildProfile,
+        cancelled: bool,
+        error: Option<BuildError>,
+    },
+}
+
+/// Why a build of a scene failed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BuildError {
+    /// The clip paths of the scene straddle more tiles than fit in the clip mask.
+    TooManyClipTiles,
+    /// The solid colors and gradient ramps of the scene don't fit in the paint texture, or
+    /// their metadata doesn't fit in the paint metadata texture.
+    TooManyPaints,
+    /// The images of the patterns of the scene don't fit in the image atlas.
+    ImagesTooLarge,
+}
+
+/// How the time of one build of a scene was spent, and what each path produced.
+///
+/// The phases that run in parallel report the time summed over all threads, so they can add up
+/// to more than the build time.
+#[derive(Clone, Debug, Default)]
+pub struct BuildProfile {
+    /// Time spent transforming, clipping, and making outlines monotonic.
+    pub apply_render_options_time: Duration,
+    /// Time spent generating fills and tiles from the prepared outlines.
+    pub tiling_time: Duration,
+    /// Time spent culling tiles against clip paths and the Z-buffer.
+    pub culling_time: Duration,
+    /// Time spent assigning alpha tiles to pages and batching tiles into render commands.
+    pub packing_time: Duration,
+    /// Indexed by path.
+    pub paths: Vec<PathStats>,
+}
+
+/// What building one path produced.
+///
+/// Paths outside the view aren't built, so everything is zero for them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathStats {
+    pub fill_count: u32,
+    /// The number of alpha tiles drawn, after those hidden behind solid tiles are culled.
+    pub alpha_tile_count: u32,
+    pub solid_tile_count: u32,
+    /// Time spent preparing and tiling the outline. This is zero if the tiles were reused from
+    /// the last build.
+    pub tiling_time: Duration,
 }
 
 #[derive(Clone, Debug)]
 pub struct PaintData {
     pub size: Vector2I,
     pub texels: Vec<u8>,
+    /// Indexed by paint ID.
+    pub metadata: Vec<PaintMetadata>,
+}
+
+/// The atlas of images that patterns sample from, as RGBA8 texels.
+#[derive(Clone, Debug)]
+pub struct ImageData {
+    pub size: Vector2I,
+    pub texels: Vec<u8>,
+}
+
+/// Describes how to compute the color of a paint at a pixel.
+///
+/// On the GPU, this is uploaded as four consecutive RGBA32F texels per paint.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct PaintMetadata {
+    /// Normalized coordinates of the color in the paint texture, or of the first texel of the
+    /// gradient ramp.
+    pub color_u: f32,
+    pub color_v: f32,
+    /// 0 for a solid color, 1 for a linear gradient, 2 for a radial gradient, 3 for a pattern.
+    pub kind: f32,
+    /// For gradients, 0 for pad, 1 for reflect, 2 for repeat. For patterns, 1 if the pattern
+    /// repeats horizontally plus 2 if it repeats vertically.
+    pub spread: f32,
+    /// The affine transform from device pixels to gradient or pattern space, in the order `m11,
+    /// m12, m21, m22, m31, m32`.
+    pub transform: [f32; 6],
+    /// The start and end radii of a radial gradient. For a pattern, the first is its opacity.
+    pub radii: [f32; 2],
+    /// The gradient line, in gradient space, or the origin and size of the image of a pattern in
+    /// the image atlas, in texels.
+    pub line: [f32; 4],
+}
+
+impl PaintMetadata {
+    /// Returns the metadata as the contents of its four texels.
+    pub fn to_f32_array(&self) -> [f32; 16] {
+        let (t, r, l) = (&self.transform, &self.radii, &self.line);
+        [
+            self.color_u, self.color_v, self.kind, self.spread,
+            t[0], t[1], t[2], t[3],
+            t[4], t[5], r[0], r[1],
+            l[0], l[1], l[2], l[3],
+        ]
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -53,8 +172,8 @@ pub struct FillObjectPrimitive {
 #[derive(Clone, Copy, Debug)]
 #[repr(C)]
 pub struct TileObjectPrimitive {
-    /// If `u16::MAX`, then this is a solid tile.
-    pub alpha_tile_index: u16,
+    /// If `u32::MAX`, then this is a solid tile.
+    pub alpha_tile_index: u32,
     pub backdrop: i8,
 }
 
@@ -72,9 +191,8 @@ pub struct FillBatchPrimitive {
 pub struct SolidTileBatchPrimitive {
     pub tile_x: i16,
     pub tile_y: i16,
-    pub origin_u: u16,
-    pub origin_v: u16,
-    pub object_index: u16,
+    pub paint_id: u32,
+    pub object_index: u32,
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -84,27 +202,69 @@ pub struct AlphaTileBatchPrimitive {
     pub tile_y_lo: u8,
     pub tile_hi: u8,
     pub backdrop: i8,
-    pub object_index: u16,
+    /// The tile of the mask framebuffer, within the current page of fills.
     pub tile_index: u16,
-    pub origin_u: u16,
-    pub origin_v: u16,
+    /// The tile of the clip mask to multiply coverage by, or `u16::MAX` if unclipped.
+    pub clip_tile_index: u16,
+    pub paint_id: u32,
+    pub object_index: u32,
+    /// 0 for the nonzero winding rule, 1 for the even-odd rule.
+    pub fill_rule: u8,
+    pub pad: [u8; 3],
+}
+
+/// Multiplies a tile of the clip mask by the coverage of a tile of a clip path.
+///
+/// The clip mask starts out fully covered, and each tile of a clip path and of the clip paths
+/// enclosing it is multiplied in.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct ClipTileBatchPrimitive {
+    pub dest_tile_index: u16,
+    pub src_tile_index: u16,
+    pub src_backdrop: i8,
+    /// 0 for the nonzero winding rule, 1 for the even-odd rule.
+    pub src_fill_rule: u8,
+}
+
+/// Composites a tile of a layer onto the layer beneath it.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct CompositeTileBatchPrimitive {
+    pub tile_x: i16,
+    pub tile_y: i16,
+    /// The tile of the clip mask to multiply the layer by, or `u16::MAX` if unclipped.
+    pub clip_tile_index: u16,
+    /// The opacity of the layer, from 0 to 255.
+    pub opacity: u8,
+    pub pad: u8,
 }
 
 impl Debug for RenderCommand {
     fn fmt(&self, formatter: &mut Formatter) -> DebugResult {
         match *self {
             RenderCommand::Start { .. } => write!(formatter, "Start"),
+            RenderCommand::AddImageData(ref image_data) => {
+                write!(formatter, "AddImageData({}x{})", image_data.size.x(), image_data.size.y())
+            }
             RenderCommand::AddPaintData(ref paint_data) => {
                 write!(formatter, "AddPaintData({}x{})", paint_data.size.x(), paint_data.size.y())
             }
             RenderCommand::AddFills(ref fills) => write!(formatter, "AddFills(x{})", fills.len()),
             RenderCommand::FlushFills => write!(formatter, "FlushFills"),
-            RenderCommand::AlphaTile(ref tiles) => {
-                write!(formatter, "AlphaTile(x{})", tiles.len())
+            RenderCommand::ClipTiles(ref tiles) => {
+                write!(formatter, "ClipTiles(x{})", tiles.len())
+            }
+            RenderCommand::AlphaTile(ref tiles, blend_mode) => {
+                write!(formatter, "AlphaTile(x{}, {:?})", tiles.len(), blend_mode)
             }
             RenderCommand::SolidTile(ref tiles) => {
                 write!(formatter, "SolidTile(x{})", tiles.len())
             }
+            RenderCommand::PushLayer => write!(formatter, "PushLayer"),
+            RenderCommand::PopLayer(ref tiles, blend_mode) => {
+                write!(formatter, "PopLayer(x{}, {:?})", tiles.len(), blend_mode)
+            }
             RenderCommand::Finish { .. } => write!(formatter, "Finish"),
         }
     }
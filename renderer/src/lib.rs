@@ -12,17 +12,27 @@
 
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
+pub mod command_file;
 pub mod concurrent;
+pub mod cpu;
 pub mod gpu;
 pub mod gpu_data;
 pub mod options;
 pub mod paint;
 pub mod post;
 pub mod scene;
+pub mod scene_file;
 
+mod build_cache;
 mod builder;
+mod clip;
+mod pdf_export;
 mod sorted_vector;
+mod spatial_index;
+mod svg_export;
 mod tile_map;
 mod tiles;
 mod z_buffer;
@@ -36,6 +36,12 @@ pub struct RenderOptions {
     pub transform: RenderTransform,
     pub dilation: Vector2F,
     pub subpixel_aa_enabled: bool,
+    /// Reports all timings in the `Finish` command as zero.
+    ///
+    /// Apart from the timings, the commands of a build depend only on the scene and the options,
+    /// and never on the executor or the thread count. With this set, two builds of the same scene
+    /// produce exactly the same commands, so that recordings of them can be compared.
+    pub zero_timings: bool,
 }
 
 impl RenderOptions {
@@ -44,6 +50,7 @@ impl RenderOptions {
             transform: self.transform.prepare(bounds),
             dilation: self.dilation,
             subpixel_aa_enabled: self.subpixel_aa_enabled,
+            zero_timings: self.zero_timings,
         }
     }
 }
@@ -123,6 +130,7 @@ pub(crate) struct PreparedRenderOptions {
     pub(crate) transform: PreparedRenderTransform,
     pub(crate) dilation: Vector2F,
     pub(crate) subpixel_aa_enabled: bool,
+    pub(crate) zero_timings: bool,
 }
 
 impl PreparedRenderOptions {
@@ -133,6 +141,22 @@ impl PreparedRenderOptions {
             _ => [Vector4F::default(); 4],
         }
     }
+
+    /// Returns the affine transform from scene coordinates to device pixels.
+    ///
+    /// For perspective transforms, this is the identity.
+    pub(crate) fn transform_2d(&self) -> Transform2DF {
+        let mut transform = match self.transform {
+            PreparedRenderTransform::Transform2D(transform) => transform,
+            PreparedRenderTransform::None | PreparedRenderTransform::Perspective { .. } => {
+                Transform2DF::default()
+            }
+        };
+        if self.subpixel_aa_enabled {
+            transform = transform.post_mul(&Transform2DF::from_scale(Vector2F::new(3.0, 1.0)))
+        }
+        transform
+    }
 }
 
 pub(crate) type BoundingQuad = [Vector4F; 4];
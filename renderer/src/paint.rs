@@ -8,50 +8,930 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::gpu_data::PaintData;
+use crate::gpu_data::{BuildError, ImageData, PaintData, PaintMetadata};
 use crate::scene::Scene;
-use pathfinder_geometry::basic::vector::Vector2I;
+use hashbrown::HashMap;
+use pathfinder_geometry::basic::line_segment::LineSegment2F;
+use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+use pathfinder_geometry::basic::transform2d::Transform2DF;
 use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::util;
+use serde::de::Error as DeserializeError;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
-const PAINT_TEXTURE_WIDTH: i32 = 256;
-const PAINT_TEXTURE_HEIGHT: i32 = 256;
+/// The largest width or height of the paint, paint metadata, and image atlas textures. Nearly
+/// every GPU supports textures at least this large.
+pub(crate) const MAX_TEXTURE_SIZE: i32 = 8192;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub struct Paint {
+const PAINT_TEXTURE_WIDTH: i32 = 2048;
+
+/// The number of texels in the ramp of each gradient. Ramps are packed several to a row of the
+/// paint texture.
+pub(crate) const GRADIENT_RAMP_LENGTH: i32 = 256;
+const GRADIENT_RAMPS_PER_ROW: i32 = PAINT_TEXTURE_WIDTH / GRADIENT_RAMP_LENGTH;
+
+// Each paint occupies four RGBA32F texels of the paint metadata texture.
+pub(crate) const PAINT_METADATA_TEXELS_PER_PAINT: i32 = 4;
+pub(crate) const PAINT_METADATA_TEXTURE_WIDTH: i32 = 2048;
+
+pub(crate) const PAINT_KIND_COLOR: f32 = 0.0;
+pub(crate) const PAINT_KIND_LINEAR_GRADIENT: f32 = 1.0;
+pub(crate) const PAINT_KIND_RADIAL_GRADIENT: f32 = 2.0;
+pub(crate) const PAINT_KIND_PATTERN: f32 = 3.0;
+pub(crate) const PAINT_KIND_CONIC_GRADIENT: f32 = 4.0;
+
+/// The width of the image atlas, unless a wider image forces it to be larger.
+const IMAGE_ATLAS_WIDTH: i32 = 2048;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Paint {
+    Color(ColorU),
+    Gradient(Gradient),
+    Pattern(Pattern),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PaintId(pub u32);
+
+/// A linear, radial, or conic gradient.
+///
+/// The gradient geometry is specified in its own coordinate space, which `transform` maps into
+/// scene coordinates.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Gradient {
+    pub geometry: GradientGeometry,
+    pub spread: SpreadMode,
+    pub transform: Transform2DF,
+    stops: Vec<ColorStop>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GradientGeometry {
+    /// A gradient that varies along the line from the start point to the end point.
+    Linear(LineSegment2F),
+    /// A two-point conical gradient between the circle centered at the start of `line` with
+    /// radius `start_radius` and the circle centered at the end of `line` with radius
+    /// `end_radius`, as in `CanvasRenderingContext2D.createRadialGradient()`.
+    ///
+    /// An SVG `<radialGradient>` is the special case in which the start circle is the focal
+    /// point.
+    Radial { line: LineSegment2F, start_radius: f32, end_radius: f32 },
+    /// A gradient that sweeps once around the center, starting at `angle` radians from the
+    /// positive x axis and going the same way as positive angles, as in
+    /// `CanvasRenderingContext2D.createConicGradient()`.
+    Conic { center: Vector2F, angle: f32 },
+}
+
+/// What to do outside the `[0, 1]` range of the gradient.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum SpreadMode {
+    /// Extend the colors of the first and last stops.
+    Pad,
+    /// Mirror the gradient every other repetition.
+    Reflect,
+    /// Repeat the gradient.
+    Repeat,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ColorStop {
+    pub offset: f32,
     pub color: ColorU,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub struct PaintId(pub u16);
+/// An RGBA bitmap for use in patterns.
+///
+/// Images are compared by identity, not by contents: clones of an image are equal, but two images
+/// created separately from the same pixels are not. Clone an image to reuse it so that it is only
+/// uploaded once.
+#[derive(Clone, Debug)]
+pub struct Image {
+    size: Vector2I,
+    pixels: Arc<Vec<ColorU>>,
+    is_opaque: bool,
+    is_fully_transparent: bool,
+}
 
-impl Paint {
+/// A paint that fills with an image, optionally repeated.
+///
+/// The image occupies the rectangle from the origin to its size in pattern space, which
+/// `transform` maps into scene coordinates. Along an axis that does not repeat, the area outside
+/// the image is transparent. `opacity` scales the alpha of every pixel of the image.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pattern {
+    pub image: Image,
+    pub transform: Transform2DF,
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+    pub opacity: f32,
+}
+
+/// How a path or layer is combined with what has already been drawn beneath it.
+///
+/// These are the Porter-Duff operators and the blend modes of the W3C Compositing and Blending
+/// specification, which back canvas `globalCompositeOperation` and SVG `mix-blend-mode`.
+///
+/// A path only affects the area that it covers, whatever its mode. So does a layer, unless its
+/// mode clears the backdrop (see `clears_backdrop()`): such a layer is composited over the whole
+/// view box, or as much of it as the clip path of the layer lets through, and so clears
+/// everything outside what it draws. This is how canvas composites every shape.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum BlendMode {
+    SourceOver,
+    SourceIn,
+    SourceOut,
+    SourceAtop,
+    DestinationOver,
+    DestinationIn,
+    DestinationOut,
+    DestinationAtop,
+    Xor,
+    Lighter,
+    Copy,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl Default for BlendMode {
     #[inline]
-    pub fn is_opaque(&self) -> bool {
-        self.color.a == 255
+    fn default() -> BlendMode {
+        BlendMode::SourceOver
+    }
+}
+
+impl BlendMode {
+    /// Whether this mode can be rendered with the fixed-function source-over blend equation.
+    #[inline]
+    pub fn is_source_over(self) -> bool {
+        self == BlendMode::SourceOver
+    }
+
+    /// Whether compositing with this mode clears the backdrop wherever the source is
+    /// transparent. These are `SourceIn`, `SourceOut`, `DestinationIn`, `DestinationAtop`, and
+    /// `Copy`.
+    #[inline]
+    pub fn clears_backdrop(self) -> bool {
+        matches!(self,
+                 BlendMode::SourceIn | BlendMode::SourceOut | BlendMode::DestinationIn |
+                 BlendMode::DestinationAtop | BlendMode::Copy)
+    }
+
+    /// The number that identifies this mode to the shaders.
+    #[inline]
+    pub(crate) fn to_shader_index(self) -> i32 {
+        self as i32
+    }
+
+    pub(crate) fn from_shader_index(index: i32) -> Option<BlendMode> {
+        const BLEND_MODES: [BlendMode; 26] = [
+            BlendMode::SourceOver, BlendMode::SourceIn, BlendMode::SourceOut,
+            BlendMode::SourceAtop, BlendMode::DestinationOver, BlendMode::DestinationIn,
+            BlendMode::DestinationOut, BlendMode::DestinationAtop, BlendMode::Xor,
+            BlendMode::Lighter, BlendMode::Copy, BlendMode::Multiply, BlendMode::Screen,
+            BlendMode::Overlay, BlendMode::Darken, BlendMode::Lighten, BlendMode::ColorDodge,
+            BlendMode::ColorBurn, BlendMode::HardLight, BlendMode::SoftLight,
+            BlendMode::Difference, BlendMode::Exclusion, BlendMode::Hue, BlendMode::Saturation,
+            BlendMode::Color, BlendMode::Luminosity,
+        ];
+        BLEND_MODES.get(index as usize).cloned()
+    }
+
+    /// Combines the premultiplied color `dest` with the premultiplied color `src`, as the blend
+    /// shader does.
+    pub(crate) fn blend(self, src: [f32; 4], dest: [f32; 4]) -> [f32; 4] {
+        let (src_alpha, dest_alpha) = (src[3], dest[3]);
+        let (src_factor, dest_factor) = self.porter_duff_factors(src_alpha, dest_alpha);
+
+        let mut src_color = [src[0], src[1], src[2]];
+        if self.is_blend_function() && src_alpha > 0.0 && dest_alpha > 0.0 {
+            // Mix the blended color in proportion to the destination's coverage.
+            let mut cs = [0.0; 3];
+            let mut cd = [0.0; 3];
+            for channel in 0..3 {
+                cs[channel] = src[channel] / src_alpha;
+                cd[channel] = dest[channel] / dest_alpha;
+            }
+            let blended = self.blend_color([cs[0].min(1.0), cs[1].min(1.0), cs[2].min(1.0)],
+                                           [cd[0].min(1.0), cd[1].min(1.0), cd[2].min(1.0)]);
+            for channel in 0..3 {
+                src_color[channel] =
+                    src_alpha * ((1.0 - dest_alpha) * cs[channel] + dest_alpha * blended[channel]);
+            }
+        }
+
+        let mut result = [0.0; 4];
+        for channel in 0..3 {
+            result[channel] = util::clamp(src_color[channel] * src_factor +
+                                          dest[channel] * dest_factor,
+                                          0.0,
+                                          1.0);
+        }
+        result[3] = util::clamp(src_alpha * src_factor + dest_alpha * dest_factor, 0.0, 1.0);
+        result
     }
 
+    // Whether this mode mixes in a blend function B(Cb, Cs) rather than only weighting the source
+    // and destination.
     #[inline]
+    fn is_blend_function(self) -> bool {
+        self as i32 >= BlendMode::Multiply as i32
+    }
+
+    // The blend function, on unpremultiplied colors.
+    fn blend_color(self, src: [f32; 3], dest: [f32; 3]) -> [f32; 3] {
+        match self {
+            BlendMode::Hue => set_lum(set_sat(src, sat(dest)), lum(dest)),
+            BlendMode::Saturation => set_lum(set_sat(dest, sat(src)), lum(dest)),
+            BlendMode::Color => set_lum(src, lum(dest)),
+            BlendMode::Luminosity => set_lum(dest, lum(src)),
+            _ => {
+                [
+                    self.blend_channel(src[0], dest[0]),
+                    self.blend_channel(src[1], dest[1]),
+                    self.blend_channel(src[2], dest[2]),
+                ]
+            }
+        }
+    }
+
+    fn porter_duff_factors(self, src_alpha: f32, dest_alpha: f32) -> (f32, f32) {
+        match self {
+            BlendMode::SourceIn => (dest_alpha, 0.0),
+            BlendMode::SourceOut => (1.0 - dest_alpha, 0.0),
+            BlendMode::SourceAtop => (dest_alpha, 1.0 - src_alpha),
+            BlendMode::DestinationOver => (1.0 - dest_alpha, 1.0),
+            BlendMode::DestinationIn => (0.0, src_alpha),
+            BlendMode::DestinationOut => (0.0, 1.0 - src_alpha),
+            BlendMode::DestinationAtop => (1.0 - dest_alpha, src_alpha),
+            BlendMode::Xor => (1.0 - dest_alpha, 1.0 - src_alpha),
+            BlendMode::Lighter => (1.0, 1.0),
+            BlendMode::Copy => (1.0, 0.0),
+            _ => (1.0, 1.0 - src_alpha),
+        }
+    }
+
+    fn blend_channel(self, src: f32, dest: f32) -> f32 {
+        match self {
+            BlendMode::Multiply => src * dest,
+            BlendMode::Screen => src + dest - src * dest,
+            BlendMode::Overlay => BlendMode::HardLight.blend_channel(dest, src),
+            BlendMode::Darken => f32::min(src, dest),
+            BlendMode::Lighten => f32::max(src, dest),
+            BlendMode::ColorDodge => {
+                if dest == 0.0 {
+                    0.0
+                } else if src >= 1.0 {
+                    1.0
+                } else {
+                    f32::min(1.0, dest / (1.0 - src))
+                }
+            }
+            BlendMode::ColorBurn => {
+                if dest >= 1.0 {
+                    1.0
+                } else if src == 0.0 {
+                    0.0
+                } else {
+                    1.0 - f32::min(1.0, (1.0 - dest) / src)
+                }
+            }
+            BlendMode::HardLight => {
+                if src <= 0.5 {
+                    BlendMode::Multiply.blend_channel(2.0 * src, dest)
+                } else {
+                    BlendMode::Screen.blend_channel(2.0 * src - 1.0, dest)
+                }
+            }
+            BlendMode::SoftLight => {
+                if src <= 0.5 {
+                    dest - (1.0 - 2.0 * src) * dest * (1.0 - dest)
+                } else {
+                    let d = if dest <= 0.25 {
+                        ((16.0 * dest - 12.0) * dest + 4.0) * dest
+                    } else {
+                        f32::sqrt(dest)
+                    };
+                    dest + (2.0 * src - 1.0) * (d - dest)
+                }
+            }
+            BlendMode::Difference => f32::abs(src - dest),
+            BlendMode::Exclusion => src + dest - 2.0 * src * dest,
+            _ => src,
+        }
+    }
+}
+
+// The helper functions of the non-separable blend modes.
+
+#[inline]
+fn lum(color: [f32; 3]) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+#[inline]
+fn sat(color: [f32; 3]) -> f32 {
+    f32::max(color[0], f32::max(color[1], color[2])) -
+        f32::min(color[0], f32::min(color[1], color[2]))
+}
+
+fn set_lum(color: [f32; 3], new_lum: f32) -> [f32; 3] {
+    let delta = new_lum - lum(color);
+    let color = [color[0] + delta, color[1] + delta, color[2] + delta];
+
+    // Bring the channels back into range while keeping the luminosity.
+    let lum = lum(color);
+    let min = f32::min(color[0], f32::min(color[1], color[2]));
+    let max = f32::max(color[0], f32::max(color[1], color[2]));
+    let mut result = color;
+    for channel in &mut result {
+        if min < 0.0 {
+            *channel = lum + (*channel - lum) * lum / (lum - min);
+        }
+        if max > 1.0 {
+            *channel = lum + (*channel - lum) * (1.0 - lum) / (max - lum);
+        }
+    }
+    result
+}
+
+fn set_sat(color: [f32; 3], new_sat: f32) -> [f32; 3] {
+    let min = f32::min(color[0], f32::min(color[1], color[2]));
+    let max = f32::max(color[0], f32::max(color[1], color[2]));
+    if max <= min {
+        return [0.0; 3];
+    }
+    let mut result = [0.0; 3];
+    for channel in 0..3 {
+        result[channel] = (color[channel] - min) * new_sat / (max - min);
+    }
+    result
+}
+
+impl Paint {
+    pub fn is_opaque(&self) -> bool {
+        match *self {
+            Paint::Color(color) => color.a == 255,
+            Paint::Gradient(ref gradient) => gradient.is_opaque(),
+            Paint::Pattern(ref pattern) => pattern.is_opaque(),
+        }
+    }
+
     pub fn is_fully_transparent(&self) -> bool {
-        self.color.is_fully_transparent()
+        match *self {
+            Paint::Color(color) => color.is_fully_transparent(),
+            Paint::Gradient(ref gradient) => {
+                gradient.stops.iter().all(|stop| stop.color.is_fully_transparent())
+            }
+            Paint::Pattern(ref pattern) => pattern.image.is_fully_transparent,
+        }
+    }
+
+    /// Moves the paint along with paths that are transformed. Colors are unaffected.
+    pub fn transform(&mut self, transform: &Transform2DF) {
+        match *self {
+            Paint::Color(_) => {}
+            Paint::Gradient(ref mut gradient) => {
+                gradient.transform = gradient.transform.then(transform)
+            }
+            Paint::Pattern(ref mut pattern) => {
+                pattern.transform = pattern.transform.then(transform)
+            }
+        }
+    }
+}
+
+impl Gradient {
+    #[inline]
+    pub fn new(geometry: GradientGeometry) -> Gradient {
+        Gradient {
+            geometry,
+            spread: SpreadMode::Pad,
+            transform: Transform2DF::default(),
+            stops: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn linear(line: LineSegment2F) -> Gradient {
+        Gradient::new(GradientGeometry::Linear(line))
+    }
+
+    #[inline]
+    pub fn radial(line: LineSegment2F, start_radius: f32, end_radius: f32) -> Gradient {
+        Gradient::new(GradientGeometry::Radial { line, start_radius, end_radius })
+    }
+
+    #[inline]
+    pub fn conic(center: Vector2F, angle: f32) -> Gradient {
+        Gradient::new(GradientGeometry::Conic { center, angle })
+    }
+
+    /// Adds a color stop, keeping the stops sorted by offset. Stops with equal offsets keep the
+    /// order in which they were added, which produces a hard transition.
+    pub fn add_color_stop(&mut self, stop: ColorStop) {
+        let index = self.stops
+                        .iter()
+                        .position(|other| other.offset > stop.offset)
+                        .unwrap_or(self.stops.len());
+        self.stops.insert(index, stop);
+    }
+
+    #[inline]
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    /// Returns the color of the gradient at `t`, which is clamped to `[0, 1]`.
+    pub fn sample(&self, t: f32) -> ColorU {
+        let (first_stop, last_stop) = match (self.stops.first(), self.stops.last()) {
+            (Some(first_stop), Some(last_stop)) => (first_stop, last_stop),
+            _ => return ColorU::transparent_black(),
+        };
+
+        let t = util::clamp(t, 0.0, 1.0);
+        let upper_index = match self.stops.iter().position(|stop| stop.offset > t) {
+            None => return last_stop.color,
+            Some(0) => return first_stop.color,
+            Some(upper_index) => upper_index,
+        };
+
+        let (lower_stop, upper_stop) = (&self.stops[upper_index - 1], &self.stops[upper_index]);
+        let denom = upper_stop.offset - lower_stop.offset;
+        if denom == 0.0 {
+            return upper_stop.color;
+        }
+        let ratio = (t - lower_stop.offset) / denom;
+        lower_stop.color.to_f32().lerp(upper_stop.color.to_f32(), ratio).to_u8()
+    }
+
+    fn is_opaque(&self) -> bool {
+        if !self.stops.iter().all(|stop| stop.color.a == 255) {
+            return false;
+        }
+
+        // Two-point conical gradients leave areas uncovered unless one circle contains the other.
+        match self.geometry {
+            GradientGeometry::Linear(_) | GradientGeometry::Conic { .. } => true,
+            GradientGeometry::Radial { line, start_radius, end_radius } => {
+                line.vector().length() <= f32::abs(end_radius - start_radius)
+            }
+        }
+    }
+}
+
+// Floats are compared by their bits, just as they are hashed, so that equality agrees with hashing
+// even for `-0.0` and NaN.
+impl PartialEq for Gradient {
+    fn eq(&self, other: &Gradient) -> bool {
+        self.geometry_bits() == other.geometry_bits() &&
+            self.spread == other.spread &&
+            transform_bits(&self.transform) == transform_bits(&other.transform) &&
+            self.stops.iter().map(ColorStop::bits).eq(other.stops.iter().map(ColorStop::bits))
     }
 }
 
+impl Eq for Gradient {}
+
+impl Hash for Gradient {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.geometry_bits().hash(state);
+        self.spread.hash(state);
+        transform_bits(&self.transform).hash(state);
+        for stop in &self.stops {
+            stop.bits().hash(state);
+        }
+    }
+}
+
+impl Gradient {
+    fn geometry_bits(&self) -> (u8, [u32; 6]) {
+        match self.geometry {
+            GradientGeometry::Linear(line) => {
+                (0, float_bits([line.from_x(), line.from_y(), line.to_x(), line.to_y(), 0.0, 0.0]))
+            }
+            GradientGeometry::Radial { line, start_radius, end_radius } => {
+                (1, float_bits([line.from_x(), line.from_y(), line.to_x(), line.to_y(),
+                                start_radius, end_radius]))
+            }
+            GradientGeometry::Conic { center, angle } => {
+                (2, float_bits([center.x(), center.y(), angle, 0.0, 0.0, 0.0]))
+            }
+        }
+    }
+}
+
+impl ColorStop {
+    #[inline]
+    fn bits(&self) -> (u32, ColorU) {
+        (self.offset.to_bits(), self.color)
+    }
+}
+
+impl Image {
+    pub fn new(size: Vector2I, pixels: Arc<Vec<ColorU>>) -> Image {
+        assert_eq!(size.x() as usize * size.y() as usize, pixels.len());
+        let is_opaque = pixels.iter().all(|pixel| pixel.a == 255);
+        let is_fully_transparent = pixels.iter().all(|pixel| pixel.is_fully_transparent());
+        Image { size, pixels, is_opaque, is_fully_transparent }
+    }
+
+    #[inline]
+    pub fn size(&self) -> Vector2I {
+        self.size
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &Arc<Vec<ColorU>> {
+        &self.pixels
+    }
+}
+
+impl PartialEq for Image {
+    #[inline]
+    fn eq(&self, other: &Image) -> bool {
+        self.size == other.size && Arc::ptr_eq(&self.pixels, &other.pixels)
+    }
+}
+
+impl Eq for Image {}
+
+impl Hash for Image {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.size.x().hash(state);
+        self.size.y().hash(state);
+        (&*self.pixels as *const Vec<ColorU>).hash(state);
+    }
+}
+
+// Images serialize as their size and pixels. The cached opacity flags are recomputed on load.
+impl Serialize for Image {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut image = serializer.serialize_struct("Image", 2)?;
+        image.serialize_field("size", &self.size)?;
+        image.serialize_field("pixels", &*self.pixels)?;
+        image.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Image {
+    fn deserialize<D>(deserializer: D) -> Result<Image, D::Error> where D: Deserializer<'de> {
+        #[derive(Deserialize)]
+        #[serde(rename = "Image")]
+        struct ImageData {
+            size: Vector2I,
+            pixels: Vec<ColorU>,
+        }
+
+        let ImageData { size, pixels } = ImageData::deserialize(deserializer)?;
+        if size.x() < 0 || size.y() < 0 ||
+                size.x() as usize * size.y() as usize != pixels.len() {
+            return Err(DeserializeError::custom("image size doesn't match its pixels"));
+        }
+        Ok(Image::new(size, Arc::new(pixels)))
+    }
+}
+
+impl Pattern {
+    /// Creates a pattern that repeats the image in both directions.
+    #[inline]
+    pub fn new(image: Image) -> Pattern {
+        Pattern {
+            image,
+            transform: Transform2DF::default(),
+            repeat_x: true,
+            repeat_y: true,
+            opacity: 1.0,
+        }
+    }
+
+    fn is_opaque(&self) -> bool {
+        self.image.is_opaque && self.repeat_x && self.repeat_y && self.opacity == 1.0
+    }
+}
+
+// Like gradients, patterns compare floats by their bits so that equality agrees with hashing.
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.image == other.image &&
+            transform_bits(&self.transform) == transform_bits(&other.transform) &&
+            self.repeat_x == other.repeat_x &&
+            self.repeat_y == other.repeat_y &&
+            self.opacity.to_bits() == other.opacity.to_bits()
+    }
+}
+
+impl Eq for Pattern {}
+
+impl Hash for Pattern {
+    fn hash<H>(&self, state: &mut H) where H: Hasher {
+        self.image.hash(state);
+        transform_bits(&self.transform).hash(state);
+        self.repeat_x.hash(state);
+        self.repeat_y.hash(state);
+        self.opacity.to_bits().hash(state);
+    }
+}
+
+fn transform_bits(transform: &Transform2DF) -> [u32; 6] {
+    float_bits([transform.m11(), transform.m12(),
+                transform.m21(), transform.m22(),
+                transform.translation().x(), transform.translation().y()])
+}
+
+fn float_bits(values: [f32; 6]) -> [u32; 6] {
+    let mut bits = [0; 6];
+    for (bits, value) in bits.iter_mut().zip(values.iter()) {
+        *bits = value.to_bits();
+    }
+    bits
+}
+
 impl Scene {
-    pub fn build_paint_data(&self) -> PaintData {
-        let size = Vector2I::new(PAINT_TEXTURE_WIDTH, PAINT_TEXTURE_HEIGHT);
+    /// Builds the paint texture, the per-paint metadata, and the atlas of pattern images.
+    ///
+    /// `render_transform` is the transform from scene coordinates to device pixels, which
+    /// gradients and patterns need in order to be evaluated per pixel. Fails if any of the
+    /// textures would be taller or wider than `MAX_TEXTURE_SIZE`.
+    pub fn build_paint_data(&self, render_transform: &Transform2DF)
+                            -> Result<(PaintData, ImageData), BuildError> {
+        // Solid colors are packed one texel per paint, and gradient ramps follow them, several
+        // to a row.
+        let paint_count = self.paints.len();
+        let gradient_count = self.paints.iter().filter(|paint| {
+            match **paint {
+                Paint::Gradient(_) => true,
+                Paint::Color(_) | Paint::Pattern(_) => false,
+            }
+        }).count();
+        let color_row_count = paint_count.div_ceil(PAINT_TEXTURE_WIDTH as usize);
+        let ramp_row_count = gradient_count.div_ceil(GRADIENT_RAMPS_PER_ROW as usize);
+        let metadata_texel_count = paint_count * PAINT_METADATA_TEXELS_PER_PAINT as usize;
+        let metadata_row_count =
+            metadata_texel_count.div_ceil(PAINT_METADATA_TEXTURE_WIDTH as usize);
+        if color_row_count + ramp_row_count > MAX_TEXTURE_SIZE as usize ||
+                metadata_row_count > MAX_TEXTURE_SIZE as usize {
+            return Err(BuildError::TooManyPaints);
+        }
+
+        let color_row_count = color_row_count as i32;
+        let size = Vector2I::new(PAINT_TEXTURE_WIDTH,
+                                 cmp::max(color_row_count + ramp_row_count as i32, 1));
         let mut texels = vec![0; size.x() as usize * size.y() as usize * 4];
+        let mut metadata = Vec::with_capacity(paint_count);
+        let (image_data, image_origins) = self.build_image_atlas()?;
+        let mut next_ramp_index = 0;
+
         for (paint_index, paint) in self.paints.iter().enumerate() {
-            texels[paint_index * 4 + 0] = paint.color.r;
-            texels[paint_index * 4 + 1] = paint.color.g;
-            texels[paint_index * 4 + 2] = paint.color.b;
-            texels[paint_index * 4 + 3] = paint.color.a;
+            match *paint {
+                Paint::Color(color) => {
+                    put_texel(&mut texels, paint_index, color);
+                    let tex_coords = Vector2I::new(paint_index as i32 % PAINT_TEXTURE_WIDTH,
+                                                   paint_index as i32 / PAINT_TEXTURE_WIDTH);
+                    metadata.push(PaintMetadata::color(texel_center(tex_coords, size)));
+                }
+                Paint::Gradient(ref gradient) => {
+                    let ramp_origin =
+                        Vector2I::new(next_ramp_index % GRADIENT_RAMPS_PER_ROW *
+                                      GRADIENT_RAMP_LENGTH,
+                                      color_row_count + next_ramp_index / GRADIENT_RAMPS_PER_ROW);
+                    let ramp_start = ramp_origin.y() as usize * size.x() as usize +
+                        ramp_origin.x() as usize;
+                    for ramp_index in 0..GRADIENT_RAMP_LENGTH {
+                        let t = ramp_index as f32 / (GRADIENT_RAMP_LENGTH - 1) as f32;
+                        let color = gradient.sample(t);
+                        put_texel(&mut texels, ramp_start + ramp_index as usize, color);
+                    }
+
+                    let ramp_tex_coords = texel_center(ramp_origin, size);
+                    metadata.push(PaintMetadata::gradient(gradient,
+                                                          render_transform,
+                                                          ramp_tex_coords));
+                    next_ramp_index += 1;
+                }
+                Paint::Pattern(ref pattern) => {
+                    let image_origin = image_origins[&pattern_image_key(pattern)];
+                    metadata.push(PaintMetadata::pattern(pattern, render_transform, image_origin));
+                }
+            }
         }
-        PaintData { size, texels }
+
+        Ok((PaintData { size, texels, metadata }, image_data))
+    }
+
+    /// Packs the images of all patterns into rows of an atlas. Each distinct image is stored
+    /// once.
+    fn build_image_atlas(&self) -> Result<(ImageData, HashMap<usize, Vector2I>), BuildError> {
+        let mut images = vec![];
+        let mut image_origins = HashMap::new();
+        for paint in &self.paints {
+            if let Paint::Pattern(ref pattern) = *paint {
+                if !image_origins.contains_key(&pattern_image_key(pattern)) {
+                    image_origins.insert(pattern_image_key(pattern), Vector2I::default());
+                    images.push(&pattern.image);
+                }
+            }
+        }
+
+        if images.is_empty() {
+            return Ok((ImageData { size: Vector2I::splat(1), texels: vec![0; 4] }, image_origins));
+        }
+
+        let total_width = images.iter().map(|image| image.size.x()).sum();
+        let widest = images.iter().map(|image| image.size.x()).max().unwrap();
+        let atlas_width = cmp::max(widest, cmp::min(total_width, IMAGE_ATLAS_WIDTH));
+
+        let (mut next_origin, mut row_height) = (Vector2I::default(), 0);
+        for image in &images {
+            if next_origin.x() + image.size.x() > atlas_width {
+                next_origin = Vector2I::new(0, next_origin.y() + row_height);
+                row_height = 0;
+            }
+            image_origins.insert(image_key(image), next_origin);
+            next_origin += Vector2I::new(image.size.x(), 0);
+            row_height = cmp::max(row_height, image.size.y());
+        }
+
+        let size = Vector2I::new(atlas_width, cmp::max(next_origin.y() + row_height, 1));
+        if size.x() > MAX_TEXTURE_SIZE || size.y() > MAX_TEXTURE_SIZE {
+            return Err(BuildError::ImagesTooLarge);
+        }
+
+        let mut texels = vec![0; size.x() as usize * size.y() as usize * 4];
+        for image in &images {
+            let origin = image_origins[&image_key(image)];
+            for y in 0..image.size.y() {
+                let dest_row = (origin.y() + y) as usize * size.x() as usize + origin.x() as usize;
+                let src_row = y as usize * image.size.x() as usize;
+                for x in 0..image.size.x() as usize {
+                    put_texel(&mut texels, dest_row + x, image.pixels[src_row + x]);
+                }
+            }
+        }
+
+        Ok((ImageData { size, texels }, image_origins))
     }
 }
 
-pub(crate) fn paint_id_to_tex_coords(paint_id: PaintId) -> Vector2I {
-    let tex_coords = Vector2I::new(paint_id.0 as i32 % PAINT_TEXTURE_WIDTH,
-                                     paint_id.0 as i32 / PAINT_TEXTURE_WIDTH);
-    tex_coords.scale(256) + Vector2I::new(128, 128)
+impl PaintMetadata {
+    fn color(tex_coords: Vector2F) -> PaintMetadata {
+        PaintMetadata {
+            color_u: tex_coords.x(),
+            color_v: tex_coords.y(),
+            kind: PAINT_KIND_COLOR,
+            transform: [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            ..PaintMetadata::default()
+        }
+    }
+
+    fn gradient(gradient: &Gradient, render_transform: &Transform2DF, ramp_tex_coords: Vector2F)
+                -> PaintMetadata {
+        // Conic gradients pass the center and the start angle in place of the line.
+        let line_array = |line: LineSegment2F| {
+            [line.from_x(), line.from_y(), line.to_x(), line.to_y()]
+        };
+        let (kind, line, radii) = match gradient.geometry {
+            GradientGeometry::Linear(line) => {
+                (PAINT_KIND_LINEAR_GRADIENT, line_array(line), [0.0, 0.0])
+            }
+            GradientGeometry::Radial { line, start_radius, end_radius } => {
+                (PAINT_KIND_RADIAL_GRADIENT, line_array(line), [start_radius, end_radius])
+            }
+            GradientGeometry::Conic { center, angle } => {
+                (PAINT_KIND_CONIC_GRADIENT, [center.x(), center.y(), angle, 0.0], [0.0, 0.0])
+            }
+        };
+
+        PaintMetadata {
+            color_u: ramp_tex_coords.x(),
+            color_v: ramp_tex_coords.y(),
+            kind,
+            spread: match gradient.spread {
+                SpreadMode::Pad => 0.0,
+                SpreadMode::Reflect => 1.0,
+                SpreadMode::Repeat => 2.0,
+            },
+            transform: pixel_to_paint_transform(render_transform, &gradient.transform),
+            radii,
+            line,
+        }
+    }
+
+    fn pattern(pattern: &Pattern, render_transform: &Transform2DF, image_origin: Vector2I)
+               -> PaintMetadata {
+        let image_size = pattern.image.size;
+        PaintMetadata {
+            kind: PAINT_KIND_PATTERN,
+            spread: match (pattern.repeat_x, pattern.repeat_y) {
+                (false, false) => 0.0,
+                (true, false) => 1.0,
+                (false, true) => 2.0,
+                (true, true) => 3.0,
+            },
+            transform: pixel_to_paint_transform(render_transform, &pattern.transform),
+            radii: [pattern.opacity, 0.0],
+            line: [
+                image_origin.x() as f32,
+                image_origin.y() as f32,
+                image_size.x() as f32,
+                image_size.y() as f32,
+            ],
+            ..PaintMetadata::default()
+        }
+    }
+}
+
+// Builds the transform from device pixels to the space of a gradient or pattern by transforming
+// the basis vectors.
+fn pixel_to_paint_transform(render_transform: &Transform2DF, paint_transform: &Transform2DF)
+                            -> [f32; 6] {
+    let inverse_render_transform = render_transform.inverse();
+    let inverse_paint_transform = paint_transform.inverse();
+    let to_paint_space = |point: Vector2F| {
+        inverse_paint_transform.transform_point(inverse_render_transform.transform_point(point))
+    };
+    let origin = to_paint_space(Vector2F::default());
+    let x_axis = to_paint_space(Vector2F::new(1.0, 0.0)) - origin;
+    let y_axis = to_paint_space(Vector2F::new(0.0, 1.0)) - origin;
+    [x_axis.x(), y_axis.x(), x_axis.y(), y_axis.y(), origin.x(), origin.y()]
+}
+
+// Images are compared by identity, so their pixel buffers identify them.
+fn image_key(image: &Image) -> usize {
+    &*image.pixels as *const Vec<ColorU> as usize
+}
+
+#[inline]
+fn pattern_image_key(pattern: &Pattern) -> usize {
+    image_key(&pattern.image)
+}
+
+fn put_texel(texels: &mut [u8], index: usize, color: ColorU) {
+    texels[(index * 4)..(index * 4 + 4)].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+}
+
+fn texel_center(tex_coords: Vector2I, texture_size: Vector2I) -> Vector2F {
+    Vector2F::new((tex_coords.x() as f32 + 0.5) / texture_size.x() as f32,
+                  (tex_coords.y() as f32 + 0.5) / texture_size.y() as f32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ColorStop, Gradient, Image, Paint, Pattern};
+    use hashbrown::HashSet;
+    use pathfinder_geometry::basic::line_segment::LineSegment2F;
+    use pathfinder_geometry::basic::transform2d::Transform2DF;
+    use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+    use pathfinder_geometry::color::ColorU;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_gradient_equality_agrees_with_hashing() {
+        let line = LineSegment2F::new(Vector2F::default(), Vector2F::new(1.0, 0.0));
+        let mut gradient = Gradient::linear(line);
+        gradient.add_color_stop(ColorStop { offset: 0.0, color: ColorU::black() });
+        let mut negative_zero_gradient = Gradient::linear(line);
+        negative_zero_gradient.add_color_stop(ColorStop { offset: -0.0, color: ColorU::black() });
+        assert_ne!(gradient, negative_zero_gradient);
+
+        let nan_gradient = Gradient::radial(line, f32::NAN, 1.0);
+        assert_eq!(nan_gradient, nan_gradient.clone());
+
+        let paints: HashSet<Paint> = vec![gradient, negative_zero_gradient, nan_gradient.clone(),
+                                          nan_gradient].into_iter().map(Paint::Gradient).collect();
+        assert_eq!(paints.len(), 3);
+    }
+
+    #[test]
+    fn test_pattern_equality_agrees_with_hashing() {
+        let image = Image::new(Vector2I::splat(1), Arc::new(vec![ColorU::black()]));
+        let pattern = Pattern::new(image);
+        let mut negative_zero_pattern = pattern.clone();
+        negative_zero_pattern.transform = Transform2DF::from_translation(Vector2F::splat(-0.0));
+        assert_ne!(pattern, negative_zero_pattern);
+
+        let mut nan_pattern = pattern.clone();
+        nan_pattern.opacity = f32::NAN;
+        assert_eq!(nan_pattern, nan_pattern.clone());
+
+        let paints: HashSet<Paint> = vec![pattern, negative_zero_pattern, nan_pattern.clone(),
+                                          nan_pattern].into_iter().map(Paint::Pattern).collect();
+        assert_eq!(paints.len(), 3);
+    }
 }
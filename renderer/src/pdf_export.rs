@@ -0,0 +1,695 @@
+// pathfinder/renderer/src/pdf_export.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Writes scenes out as single-page PDF 1.4 documents.
+//!
+//! The page is the view box, with one scene unit to a point. Paths are filled with the color of
+//! their paint or with a shading for linear and radial gradients, and translucency and blend
+//! modes are set with graphics state parameter dictionaries. Each layer is drawn as an isolated
+//! transparency group, so that its opacity, clip path, and blend mode apply to its paths as a
+//! whole.
+
+use crate::paint::{BlendMode, ColorStop, Gradient, GradientGeometry, Paint};
+use crate::scene::{ClipPathId, PathObject, Scene};
+use hashbrown::HashMap;
+use pathfinder_geometry::basic::vector::Vector2F;
+use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
+use pathfinder_geometry::outline::Outline;
+use pathfinder_geometry::segment::SegmentKind;
+use std::io::{self, Write};
+use std::mem;
+
+// The objects that come before the graphics states, which are followed by the shadings and then
+// the layer groups.
+const CATALOG_OBJECT: usize = 1;
+const PAGES_OBJECT: usize = 2;
+const PAGE_OBJECT: usize = 3;
+const RESOURCES_OBJECT: usize = 4;
+const CONTENTS_OBJECT: usize = 5;
+const FIRST_GRAPHICS_STATE_OBJECT: usize = 6;
+
+// The alpha and the blend mode that a graphics state parameter dictionary sets.
+type GraphicsState = (f32, &'static str);
+
+// What a content stream starts with, and what a transparency group is reset to.
+const INITIAL_GRAPHICS_STATE: GraphicsState = (1.0, "Normal");
+
+// The resources that the page and the layer groups share.
+#[derive(Default)]
+struct PdfResources {
+    graphics_states: Vec<GraphicsState>,
+    // The paints that shadings are made from, and the shading of each.
+    shading_paints: Vec<u32>,
+    shadings: HashMap<u32, usize>,
+    // The content streams of the layers.
+    groups: Vec<Vec<u8>>,
+}
+
+impl Scene {
+    /// Writes the scene as a PDF document with one page, the size of the view box.
+    ///
+    /// Not everything has a PDF equivalent:
+    ///
+    /// * Patterns are drawn in black.
+    ///
+    /// * Conic gradients are drawn in the color at their start.
+    ///
+    /// * Reflected and repeated gradients are drawn padded instead.
+    ///
+    /// * Gradients whose stops don't all have the same alpha are drawn opaque.
+    ///
+    /// * The Porter-Duff operators other than `SourceOver` are drawn as `SourceOver`, in paths
+    ///   and layers alike.
+    pub fn write_pdf<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        let mut resources = PdfResources::default();
+        let contents = self.pdf_contents(&mut resources)?;
+        let first_shading_object = FIRST_GRAPHICS_STATE_OBJECT + resources.graphics_states.len();
+        let first_group_object = first_shading_object + resources.shading_paints.len();
+
+        let mut document = PdfWriter { bytes: vec![], object_offsets: vec![] };
+        // The comment of high bytes marks the file as binary for tools that care.
+        document.bytes.extend_from_slice(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+
+        document.begin_object(CATALOG_OBJECT);
+        writeln!(document.bytes, "<< /Type /Catalog /Pages {} 0 R >>", PAGES_OBJECT)?;
+        document.end_object();
+
+        document.begin_object(PAGES_OBJECT);
+        writeln!(document.bytes, "<< /Type /Pages /Kids [{} 0 R] /Count 1 >>", PAGE_OBJECT)?;
+        document.end_object();
+
+        let view_box = self.view_box();
+        document.begin_object(PAGE_OBJECT);
+        writeln!(document.bytes,
+                 "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R \
+                  /Resources {} 0 R >>",
+                 PAGES_OBJECT,
+                 view_box.size().x(),
+                 view_box.size().y(),
+                 CONTENTS_OBJECT,
+                 RESOURCES_OBJECT)?;
+        document.end_object();
+
+        document.begin_object(RESOURCES_OBJECT);
+        write!(document.bytes, "<< /ExtGState <<")?;
+        for index in 0..resources.graphics_states.len() {
+            write!(document.bytes, " /GS{} {} 0 R", index, FIRST_GRAPHICS_STATE_OBJECT + index)?;
+        }
+        write!(document.bytes, " >> /Shading <<")?;
+        for index in 0..resources.shading_paints.len() {
+            write!(document.bytes, " /Sh{} {} 0 R", index, first_shading_object + index)?;
+        }
+        write!(document.bytes, " >> /XObject <<")?;
+        for index in 0..resources.groups.len() {
+            write!(document.bytes, " /X{} {} 0 R", index, first_group_object + index)?;
+        }
+        writeln!(document.bytes, " >> >>")?;
+        document.end_object();
+
+        document.begin_object(CONTENTS_OBJECT);
+        writeln!(document.bytes, "<< /Length {} >>", contents.len())?;
+        document.write_stream(&contents)?;
+        document.end_object();
+
+        for (index, &(alpha, blend_mode)) in resources.graphics_states.iter().enumerate() {
+            document.begin_object(FIRST_GRAPHICS_STATE_OBJECT + index);
+            writeln!(document.bytes, "<< /Type /ExtGState /ca {} /BM /{} >>", alpha, blend_mode)?;
+            document.end_object();
+        }
+
+        for (index, &paint_index) in resources.shading_paints.iter().enumerate() {
+            document.begin_object(first_shading_object + index);
+            match self.paints[paint_index as usize] {
+                Paint::Gradient(ref gradient) => write_pdf_shading(&mut document.bytes, gradient)?,
+                Paint::Color(_) | Paint::Pattern(_) => unreachable!(),
+            }
+            document.end_object();
+        }
+
+        // The groups are drawn in the coordinate system of the scene, which the page sets up.
+        for (index, group) in resources.groups.iter().enumerate() {
+            document.begin_object(first_group_object + index);
+            writeln!(document.bytes,
+                     "<< /Type /XObject /Subtype /Form /BBox [{} {} {} {}] \
+                      /Group << /S /Transparency /I true >> /Resources {} 0 R /Length {} >>",
+                     view_box.min_x(),
+                     view_box.min_y(),
+                     view_box.max_x(),
+                     view_box.max_y(),
+                     RESOURCES_OBJECT,
+                     group.len())?;
+            document.write_stream(group)?;
+            document.end_object();
+        }
+
+        document.finish()?;
+        writer.write_all(&document.bytes)
+    }
+
+    // Returns the content stream of the page, adding the resources that it uses. The paths in
+    // layers go into the content streams of their groups instead.
+    fn pdf_contents(&self, resources: &mut PdfResources) -> io::Result<Vec<u8>> {
+        let mut contents = vec![];
+
+        // Flip the view box so that its upper left corner is at the top of the page. (Subtracting
+        // from zero keeps a zero offset from being written as `-0`.)
+        let view_box = self.view_box();
+        writeln!(contents,
+                 "1 0 0 -1 {} {} cm",
+                 0.0 - view_box.origin().x(),
+                 view_box.origin().y() + view_box.size().y())?;
+
+        // The layers being drawn, innermost last, with the contents and the graphics state
+        // outside each.
+        let mut open_layers = vec![];
+        let mut graphics_state = INITIAL_GRAPHICS_STATE;
+        for (path_index, path_object) in self.paths.iter().enumerate() {
+            let path_index = path_index as u32;
+            self.close_pdf_layers(&mut contents,
+                                  &mut graphics_state,
+                                  &mut open_layers,
+                                  resources,
+                                  path_index)?;
+            for (layer_index, layer) in self.layers.iter().enumerate() {
+                let path_range = layer.path_range();
+                if path_range.start != path_index || path_range.end == path_index {
+                    continue;
+                }
+                let outer_contents = mem::take(&mut contents);
+                let outer_graphics_state = mem::replace(&mut graphics_state,
+                                                        INITIAL_GRAPHICS_STATE);
+                open_layers.push((layer_index, outer_contents, outer_graphics_state));
+            }
+
+            self.write_pdf_path_object(&mut contents, &mut graphics_state, resources, path_object)?;
+        }
+        self.close_pdf_layers(&mut contents,
+                              &mut graphics_state,
+                              &mut open_layers,
+                              resources,
+                              self.paths.len() as u32)?;
+        Ok(contents)
+    }
+
+    // Turns the layers that end before the given path into groups, and draws each group in the
+    // contents outside it.
+    fn close_pdf_layers(&self,
+                        contents: &mut Vec<u8>,
+                        graphics_state: &mut GraphicsState,
+                        open_layers: &mut Vec<(usize, Vec<u8>, GraphicsState)>,
+                        resources: &mut PdfResources,
+                        path_index: u32)
+                        -> io::Result<()> {
+        while let Some(&(layer_index, _, _)) = open_layers.last() {
+            let layer = &self.layers[layer_index];
+            if layer.path_range().end > path_index {
+                break;
+            }
+
+            let (_, outer_contents, outer_graphics_state) = open_layers.pop().unwrap();
+            let group_index = resources.groups.len();
+            resources.groups.push(mem::replace(contents, outer_contents));
+            *graphics_state = outer_graphics_state;
+
+            writeln!(contents, "q")?;
+            self.write_pdf_clip_path(contents, layer.clip_path())?;
+            let layer_graphics_state = (layer.opacity(), pdf_blend_mode(layer.blend_mode()));
+            if layer_graphics_state != INITIAL_GRAPHICS_STATE {
+                let index = resources.graphics_state(layer_graphics_state);
+                writeln!(contents, "/GS{} gs", index)?;
+            }
+            writeln!(contents, "/X{} Do", group_index)?;
+            writeln!(contents, "Q")?;
+        }
+        Ok(())
+    }
+
+    fn write_pdf_path_object(&self,
+                             contents: &mut Vec<u8>,
+                             graphics_state: &mut GraphicsState,
+                             resources: &mut PdfResources,
+                             path_object: &PathObject)
+                             -> io::Result<()> {
+        let paint_index = path_object.paint().0;
+        let (color, shading) = match self.paints[paint_index as usize] {
+            Paint::Color(color) => (color, None),
+            Paint::Gradient(ref gradient) if has_pdf_equivalent(gradient) => {
+                let stops = gradient.stops();
+                if stops.is_empty() {
+                    return Ok(());
+                }
+                let alpha = if stops.iter().all(|stop| stop.color.a == stops[0].color.a) {
+                    stops[0].color.a
+                } else {
+                    255
+                };
+                let color = ColorU { a: alpha, ..ColorU::black() };
+                (color, Some((resources.shading(paint_index), gradient)))
+            }
+            Paint::Gradient(ref gradient) => (gradient.sample(0.0), None),
+            Paint::Pattern(_) => (ColorU::black(), None),
+        };
+
+        // Paths with clip paths or shadings are drawn in a saved graphics state, which is
+        // restored afterward along with the parameters that the path set.
+        let saved_graphics_state = *graphics_state;
+        let saved = path_object.clip_path().is_some() || shading.is_some();
+        if saved {
+            writeln!(contents, "q")?;
+            self.write_pdf_clip_path(contents, path_object.clip_path())?;
+        }
+
+        let path_graphics_state = (color.a as f32 / 255.0,
+                                   pdf_blend_mode(path_object.blend_mode()));
+        if path_graphics_state != *graphics_state {
+            let index = resources.graphics_state(path_graphics_state);
+            writeln!(contents, "/GS{} gs", index)?;
+            *graphics_state = path_graphics_state;
+        }
+
+        match shading {
+            None => {
+                let color = color.to_f32();
+                writeln!(contents, "{} {} {} rg", color.r(), color.g(), color.b())?;
+                write_pdf_path(contents, path_object.outline())?;
+                match path_object.fill_rule() {
+                    FillRule::Winding => writeln!(contents, "f")?,
+                    FillRule::EvenOdd => writeln!(contents, "f*")?,
+                }
+            }
+            Some((shading_index, gradient)) => {
+                // A shading fills the whole clipping region, so clip to the path first.
+                write_pdf_path(contents, path_object.outline())?;
+                match path_object.fill_rule() {
+                    FillRule::Winding => writeln!(contents, "W n")?,
+                    FillRule::EvenOdd => writeln!(contents, "W* n")?,
+                }
+                if !gradient.transform.is_identity() {
+                    let (transform, translation) = (&gradient.transform,
+                                                    gradient.transform.translation());
+                    writeln!(contents,
+                             "{} {} {} {} {} {} cm",
+                             transform.m11(),
+                             transform.m21(),
+                             transform.m12(),
+                             transform.m22(),
+                             translation.x(),
+                             translation.y())?;
+                }
+                writeln!(contents, "/Sh{} sh", shading_index)?;
+            }
+        }
+
+        if saved {
+            writeln!(contents, "Q")?;
+            *graphics_state = saved_graphics_state;
+        }
+        Ok(())
+    }
+
+    // Intersects the clipping region with a clip path and its ancestors.
+    fn write_pdf_clip_path(&self, contents: &mut Vec<u8>, mut clip_path_id: Option<ClipPathId>)
+                           -> io::Result<()> {
+        while let Some(ClipPathId(clip_path_index)) = clip_path_id {
+            let clip_path = &self.clip_paths[clip_path_index as usize];
+            write_pdf_path(contents, clip_path.outline())?;
+            match clip_path.fill_rule() {
+                FillRule::Winding => writeln!(contents, "W n")?,
+                FillRule::EvenOdd => writeln!(contents, "W* n")?,
+            }
+            clip_path_id = clip_path.clip_path();
+        }
+        Ok(())
+    }
+}
+
+impl PdfResources {
+    fn graphics_state(&mut self, graphics_state: GraphicsState) -> usize {
+        match self.graphics_states.iter().position(|&other| other == graphics_state) {
+            Some(index) => index,
+            None => {
+                self.graphics_states.push(graphics_state);
+                self.graphics_states.len() - 1
+            }
+        }
+    }
+
+    fn shading(&mut self, paint_index: u32) -> usize {
+        let shading_paints = &mut self.shading_paints;
+        *self.shadings.entry(paint_index).or_insert_with(|| {
+            shading_paints.push(paint_index);
+            shading_paints.len() - 1
+        })
+    }
+}
+
+struct PdfWriter {
+    bytes: Vec<u8>,
+    // Indexed by object number minus one.
+    object_offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn begin_object(&mut self, object: usize) {
+        debug_assert_eq!(object, self.object_offsets.len() + 1);
+        self.object_offsets.push(self.bytes.len());
+        self.bytes.extend_from_slice(format!("{} 0 obj\n", object).as_bytes());
+    }
+
+    fn end_object(&mut self) {
+        self.bytes.extend_from_slice(b"endobj\n");
+    }
+
+    fn write_stream(&mut self, stream: &[u8]) -> io::Result<()> {
+        writeln!(self.bytes, "stream")?;
+        self.bytes.extend_from_slice(stream);
+        writeln!(self.bytes, "endstream")
+    }
+
+    // Writes the cross-reference table and the trailer. Every entry in the table is exactly 20
+    // bytes long, line ending included.
+    fn finish(&mut self) -> io::Result<()> {
+        let xref_offset = self.bytes.len();
+        writeln!(self.bytes, "xref")?;
+        writeln!(self.bytes, "0 {}", self.object_offsets.len() + 1)?;
+        write!(self.bytes, "0000000000 65535 f\r\n")?;
+        for offset in &self.object_offsets {
+            write!(self.bytes, "{:010} 00000 n\r\n", offset)?;
+        }
+        writeln!(self.bytes, "trailer")?;
+        writeln!(self.bytes,
+                 "<< /Size {} /Root {} 0 R >>",
+                 self.object_offsets.len() + 1,
+                 CATALOG_OBJECT)?;
+        writeln!(self.bytes, "startxref")?;
+        writeln!(self.bytes, "{}", xref_offset)?;
+        writeln!(self.bytes, "%%EOF")
+    }
+}
+
+fn has_pdf_equivalent(gradient: &Gradient) -> bool {
+    match gradient.geometry {
+        GradientGeometry::Linear(_) | GradientGeometry::Radial { .. } => true,
+        GradientGeometry::Conic { .. } => false,
+    }
+}
+
+// Writes an axial or radial shading, in the coordinate space of the gradient. Both are extended
+// past their ends, as gradients are padded.
+fn write_pdf_shading(bytes: &mut Vec<u8>, gradient: &Gradient) -> io::Result<()> {
+    match gradient.geometry {
+        GradientGeometry::Linear(line) => {
+            write!(bytes,
+                   "<< /ShadingType 2 /ColorSpace /DeviceRGB /Coords [{} {} {} {}]",
+                   line.from_x(),
+                   line.from_y(),
+                   line.to_x(),
+                   line.to_y())?;
+        }
+        GradientGeometry::Radial { line, start_radius, end_radius } => {
+            write!(bytes,
+                   "<< /ShadingType 3 /ColorSpace /DeviceRGB /Coords [{} {} {} {} {} {}]",
+                   line.from_x(),
+                   line.from_y(),
+                   start_radius,
+                   line.to_x(),
+                   line.to_y(),
+                   end_radius)?;
+        }
+        GradientGeometry::Conic { .. } => unreachable!(),
+    }
+    write!(bytes, " /Extend [true true] /Function ")?;
+    write_pdf_gradient_function(bytes, gradient.stops())?;
+    writeln!(bytes, " >>")
+}
+
+// Writes a function that interpolates between each pair of adjacent stops, stitched together.
+// The colors of the first and last stops extend to the ends of the gradient.
+fn write_pdf_gradient_function(bytes: &mut Vec<u8>, stops: &[ColorStop]) -> io::Result<()> {
+    let mut stops = stops.to_vec();
+    let (first_stop, last_stop) = (stops[0], stops[stops.len() - 1]);
+    if first_stop.offset > 0.0 {
+        stops.insert(0, ColorStop { offset: 0.0, ..first_stop });
+    }
+    if last_stop.offset < 1.0 || stops.len() == 1 {
+        stops.push(ColorStop { offset: 1.0, ..last_stop });
+    }
+
+    write!(bytes, "<< /FunctionType 3 /Domain [0 1] /Functions [")?;
+    for pair in stops.windows(2) {
+        let (color0, color1) = (pair[0].color.to_f32(), pair[1].color.to_f32());
+        write!(bytes,
+               " << /FunctionType 2 /Domain [0 1] /C0 [{} {} {}] /C1 [{} {} {}] /N 1 >>",
+               color0.r(),
+               color0.g(),
+               color0.b(),
+               color1.r(),
+               color1.g(),
+               color1.b())?;
+    }
+    write!(bytes, " ] /Bounds [")?;
+    for stop in &stops[1..(stops.len() - 1)] {
+        write!(bytes, " {}", stop.offset)?;
+    }
+    write!(bytes, " ] /Encode [")?;
+    for _ in 1..stops.len() {
+        write!(bytes, " 0 1")?;
+    }
+    write!(bytes, " ] >>")
+}
+
+// Writes the path construction operators for an outline. PDF has no quadratic Béziers, so those
+// are written as the equivalent cubics.
+fn write_pdf_path(contents: &mut Vec<u8>, outline: &Outline) -> io::Result<()> {
+    for contour in outline.contours() {
+        if contour.is_empty() {
+            continue;
+        }
+
+        let mut segments: Vec<_> = contour.iter().collect();
+        // The last segment of a closed contour is the line back to the start, which `h` draws.
+        if contour.is_closed() {
+            segments.pop();
+        }
+
+        let start = contour.position_of(0);
+        writeln!(contents, "{} {} m", start.x(), start.y())?;
+        for segment in segments {
+            let (from, to) = (segment.baseline.from(), segment.baseline.to());
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => writeln!(contents, "{} {} l", to.x(), to.y())?,
+                SegmentKind::Quadratic => {
+                    let ctrl = segment.ctrl.from();
+                    let ctrl0 = from + (ctrl - from).scale(2.0 / 3.0);
+                    let ctrl1 = to + (ctrl - to).scale(2.0 / 3.0);
+                    write_pdf_curve(contents, ctrl0, ctrl1, to)?;
+                }
+                SegmentKind::Cubic => {
+                    write_pdf_curve(contents, segment.ctrl.from(), segment.ctrl.to(), to)?;
+                }
+            }
+        }
+        if contour.is_closed() {
+            writeln!(contents, "h")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_pdf_curve(contents: &mut Vec<u8>, ctrl0: Vector2F, ctrl1: Vector2F, to: Vector2F)
+                   -> io::Result<()> {
+    writeln!(contents,
+             "{} {} {} {} {} {} c",
+             ctrl0.x(),
+             ctrl0.y(),
+             ctrl1.x(),
+             ctrl1.y(),
+             to.x(),
+             to.y())
+}
+
+// Blend modes that PDF has no equivalent for are drawn normally.
+fn pdf_blend_mode(blend_mode: BlendMode) -> &'static str {
+    match blend_mode {
+        BlendMode::Multiply => "Multiply",
+        BlendMode::Screen => "Screen",
+        BlendMode::Overlay => "Overlay",
+        BlendMode::Darken => "Darken",
+        BlendMode::Lighten => "Lighten",
+        BlendMode::ColorDodge => "ColorDodge",
+        BlendMode::ColorBurn => "ColorBurn",
+        BlendMode::HardLight => "HardLight",
+        BlendMode::SoftLight => "SoftLight",
+        BlendMode::Difference => "Difference",
+        BlendMode::Exclusion => "Exclusion",
+        BlendMode::Hue => "Hue",
+        BlendMode::Saturation => "Saturation",
+        BlendMode::Color => "Color",
+        BlendMode::Luminosity => "Luminosity",
+        BlendMode::SourceOver | BlendMode::SourceIn | BlendMode::SourceOut |
+        BlendMode::SourceAtop | BlendMode::DestinationOver | BlendMode::DestinationIn |
+        BlendMode::DestinationOut | BlendMode::DestinationAtop | BlendMode::Xor |
+        BlendMode::Lighter | BlendMode::Copy => "Normal",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::paint::{BlendMode, ColorStop, Gradient, Paint};
+    use crate::scene::{ClipPath, Layer, PathObject, Scene};
+    use pathfinder_geometry::basic::line_segment::LineSegment2F;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::transform2d::Transform2DF;
+    use pathfinder_geometry::basic::vector::Vector2F;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use std::str;
+
+    #[test]
+    fn test_write_pdf() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(1.0, 2.0));
+        contour.push_quadratic(Vector2F::new(4.0, 2.0), Vector2F::new(4.0, 5.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::new(-10.0, 0.0), Vector2F::new(200.0, 100.0)));
+        let translucent = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 0, a: 51 }));
+        let opaque = scene.push_paint(&Paint::Color(ColorU::black()));
+        scene.push_path(PathObject::new(outline.clone(), translucent, String::new()));
+        let clip_path = scene.push_clip_path(ClipPath::new(outline.clone(), String::new()));
+        let mut path = PathObject::new(outline.clone(), opaque, String::new());
+        path.set_fill_rule(FillRule::EvenOdd);
+        path.set_clip_path(Some(clip_path));
+        path.set_blend_mode(BlendMode::Multiply);
+        scene.push_path(path);
+        scene.push_path(PathObject::new(outline, translucent, String::new()));
+
+        let mut bytes = vec![];
+        scene.write_pdf(&mut bytes).unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/MediaBox [0 0 200 100]"));
+        assert!(text.contains("<< /ExtGState << /GS0 6 0 R /GS1 7 0 R >> /Shading << >> \
+                               /XObject << >> >>"));
+        assert!(text.contains("<< /Type /ExtGState /ca 0.2 /BM /Normal >>"));
+        assert!(text.contains("<< /Type /ExtGState /ca 1 /BM /Multiply >>"));
+
+        let path = "1 2 m\n3 2 4 3 4 5 c\nh\n";
+        let contents = format!("1 0 0 -1 10 100 cm\n\
+                                /GS0 gs\n1 0 0 rg\n{path}f\n\
+                                q\n{path}W n\n/GS1 gs\n0 0 0 rg\n{path}f*\nQ\n\
+                                1 0 0 rg\n{path}f\n",
+                               path = path);
+        assert!(text.contains(&format!("<< /Length {} >>\nstream\n{}endstream\n",
+                                       contents.len(),
+                                       contents)));
+
+        check_xref(&bytes, 7);
+    }
+
+    #[test]
+    fn test_write_pdf_gradients_and_layers() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::new(200.0, 100.0)));
+        let mut gradient = Gradient::radial(LineSegment2F::new(Vector2F::splat(1.0),
+                                                               Vector2F::splat(2.0)),
+                                            3.0,
+                                            4.0);
+        gradient.add_color_stop(ColorStop { offset: 0.25, color: ColorU::black() });
+        gradient.add_color_stop(ColorStop {
+            offset: 0.5,
+            color: ColorU { r: 255, g: 0, b: 0, a: 255 },
+        });
+        gradient.transform = Transform2DF::from_translation(Vector2F::new(5.0, 6.0));
+        let gradient = scene.push_paint(&Paint::Gradient(gradient));
+        let black = scene.push_paint(&Paint::Color(ColorU::black()));
+        let clip_path = scene.push_clip_path(ClipPath::new(rect(1.0), String::new()));
+
+        let mut layer = Layer::new(String::new());
+        layer.set_opacity(0.5);
+        layer.set_clip_path(Some(clip_path));
+        scene.push_layer(layer);
+        scene.push_path(PathObject::new(rect(2.0), gradient, String::new()));
+        let mut path = PathObject::new(rect(3.0), black, String::new());
+        path.set_blend_mode(BlendMode::Screen);
+        scene.push_path(path);
+        scene.pop_layer();
+        scene.push_path(PathObject::new(rect(4.0), black, String::new()));
+
+        let mut bytes = vec![];
+        scene.write_pdf(&mut bytes).unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("<< /ExtGState << /GS0 6 0 R /GS1 7 0 R >> /Shading << /Sh0 8 0 R >> \
+                               /XObject << /X0 9 0 R >> >>"));
+        assert!(text.contains("<< /Type /ExtGState /ca 1 /BM /Screen >>"));
+        assert!(text.contains("<< /Type /ExtGState /ca 0.5 /BM /Normal >>"));
+        assert!(text.contains("<< /ShadingType 3 /ColorSpace /DeviceRGB /Coords [1 1 3 2 2 4] \
+                               /Extend [true true] /Function << /FunctionType 3 /Domain [0 1] \
+                               /Functions [ \
+                               << /FunctionType 2 /Domain [0 1] /C0 [0 0 0] /C1 [0 0 0] /N 1 >> \
+                               << /FunctionType 2 /Domain [0 1] /C0 [0 0 0] /C1 [1 0 0] /N 1 >> \
+                               << /FunctionType 2 /Domain [0 1] /C0 [1 0 0] /C1 [1 0 0] /N 1 >> \
+                               ] /Bounds [ 0.25 0.5 ] /Encode [ 0 1 0 1 0 1 ] >> >>"));
+
+        let contents = format!("1 0 0 -1 0 100 cm\nq\n{}W n\n/GS1 gs\n/X0 Do\nQ\n\
+                                0 0 0 rg\n{}f\n",
+                               rect_path(1.0),
+                               rect_path(4.0));
+        assert!(text.contains(&format!("<< /Length {} >>\nstream\n{}endstream\n",
+                                       contents.len(),
+                                       contents)));
+        let group = format!("q\n{}W n\n1 0 0 1 5 6 cm\n/Sh0 sh\nQ\n/GS0 gs\n0 0 0 rg\n{}f\n",
+                            rect_path(2.0),
+                            rect_path(3.0));
+        assert!(text.contains(&format!("<< /Type /XObject /Subtype /Form /BBox [0 0 200 100] \
+                                        /Group << /S /Transparency /I true >> \
+                                        /Resources 4 0 R /Length {} >>\nstream\n{}endstream\n",
+                                       group.len(),
+                                       group)));
+
+        check_xref(&bytes, 9);
+    }
+
+    fn rect(size: f32) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::default());
+        contour.push_endpoint(Vector2F::new(size, 0.0));
+        contour.push_endpoint(Vector2F::splat(size));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn rect_path(size: f32) -> String {
+        format!("0 0 m\n{} 0 l\n{} {} l\nh\n", size, size, size)
+    }
+
+    // Checks that each object is where the cross-reference table says it is.
+    fn check_xref(bytes: &[u8], object_count: usize) {
+        let text = String::from_utf8_lossy(bytes);
+        let xref_offset: usize = text.lines().rev().nth(1).unwrap().parse().unwrap();
+        let xref = str::from_utf8(&bytes[xref_offset..]).unwrap();
+        assert!(xref.starts_with(&format!("xref\n0 {}\n", object_count + 1)));
+        let entries: Vec<_> = xref.split("\r\n").skip(1).take(object_count).collect();
+        for (object_index, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            let object = str::from_utf8(&bytes[offset..]).unwrap();
+            assert!(object.starts_with(&format!("{} 0 obj\n", object_index + 1)));
+        }
+    }
+}
@@ -10,26 +10,40 @@
 
 //! A set of paths to be rendered.
 
+use crate::build_cache::{self, BuildCache};
 use crate::builder::SceneBuilder;
 use crate::concurrent::executor::Executor;
 use crate::options::{PreparedRenderOptions, PreparedRenderTransform};
 use crate::options::{RenderCommandListener, RenderOptions};
-use crate::paint::{Paint, PaintId};
+use crate::paint::{BlendMode, Paint, PaintId};
+use crate::spatial_index::SpatialIndex;
 use hashbrown::HashMap;
 use pathfinder_geometry::basic::vector::Vector2F;
 use pathfinder_geometry::basic::rect::RectF;
 use pathfinder_geometry::basic::transform2d::Transform2DF;
 use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
 use pathfinder_geometry::outline::Outline;
-use std::io::{self, Write};
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::Range;
 
 #[derive(Clone)]
 pub struct Scene {
     pub(crate) paths: Vec<PathObject>,
     pub(crate) paints: Vec<Paint>,
-    paint_cache: HashMap<Paint, PaintId>,
+    pub(crate) clip_paths: Vec<ClipPath>,
+    pub(crate) layers: Vec<Layer>,
+    open_layers: Vec<LayerId>,
+    pub(crate) paint_cache: HashMap<Paint, PaintId>,
     bounds: RectF,
     view_box: RectF,
+    path_index: SpatialIndex,
+
+    // Revision numbers of the paths and clip paths, which change whenever they do.
+    pub(crate) path_revisions: Vec<u64>,
+    pub(crate) clip_path_revisions: Vec<u64>,
+    pub(crate) build_cache: BuildCache,
 }
 
 impl Scene {
@@ -38,34 +52,291 @@ impl Scene {
         Scene {
             paths: vec![],
             paints: vec![],
+            clip_paths: vec![],
+            layers: vec![],
+            open_layers: vec![],
             paint_cache: HashMap::new(),
             bounds: RectF::default(),
             view_box: RectF::default(),
+            path_index: SpatialIndex::default(),
+
+            path_revisions: vec![],
+            clip_path_revisions: vec![],
+            build_cache: BuildCache::default(),
         }
     }
 
     pub fn push_path(&mut self, path: PathObject) {
         self.bounds = self.bounds.union_rect(path.outline.bounds());
+        self.path_index.push(path.outline.bounds());
         self.paths.push(path);
+        self.path_revisions.push(build_cache::next_revision());
+
+        let path_count = self.paths.len() as u32;
+        for layer_id in &self.open_layers {
+            self.layers[layer_id.0 as usize].path_range.end = path_count;
+        }
+    }
+
+    /// Starts a layer. Paths pushed until the matching `pop_layer()` are rendered into it, and
+    /// the layer is then composited as a whole.
+    pub fn push_layer(&mut self, mut layer: Layer) -> LayerId {
+        if let Some(clip_path) = layer.clip_path {
+            assert!((clip_path.0 as usize) < self.clip_paths.len(), "Unknown clip path!");
+        }
+
+        let path_count = self.paths.len() as u32;
+        layer.path_range = path_count..path_count;
+
+        let layer_id = LayerId(self.layers.len() as u32);
+        self.layers.push(layer);
+        self.open_layers.push(layer_id);
+        layer_id
+    }
+
+    pub fn pop_layer(&mut self) {
+        self.open_layers.pop().expect("No layer to pop!");
+    }
+
+    /// Returns the index of the layer that directly contains each layer, if any.
+    ///
+    /// An empty layer doesn't record where it was pushed, so its parent may be a layer that ended
+    /// just before it. No path is in an empty layer, so this doesn't matter.
+    pub(crate) fn layer_parents(&self) -> Vec<Option<u32>> {
+        // Layers are stored in the order in which they were pushed, and so by the start of their
+        // path ranges. The parent of a layer is the innermost earlier one that encloses it.
+        let mut parents = Vec::with_capacity(self.layers.len());
+        let mut open_layers: Vec<u32> = vec![];
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            while let Some(&open_layer_index) = open_layers.last() {
+                if self.layers[open_layer_index as usize].path_range.end >= layer.path_range.end {
+                    break;
+                }
+                open_layers.pop();
+            }
+            parents.push(open_layers.last().cloned());
+            open_layers.push(layer_index as u32);
+        }
+        parents
+    }
+
+    /// Returns the innermost layer that contains the given path, if any, given the parents from
+    /// `layer_parents()`.
+    pub(crate) fn innermost_layer(&self, layer_parents: &[Option<u32>], path_index: u32)
+                                  -> Option<u32> {
+        // The innermost layer that contains the path is the last one that starts at or before it,
+        // or one of that layer's ancestors.
+        let layers_starting_before = match self.layers.binary_search_by(|layer| {
+            if layer.path_range.start <= path_index { Ordering::Less } else { Ordering::Greater }
+        }) {
+            Ok(index) | Err(index) => index,
+        };
+        let mut layer_index = (layers_starting_before as u32).checked_sub(1);
+        while let Some(index) = layer_index {
+            if self.layers[index as usize].path_range.contains(&path_index) {
+                return Some(index);
+            }
+            layer_index = layer_parents[index as usize];
+        }
+        None
     }
 
-    #[allow(clippy::trivially_copy_pass_by_ref)]
     pub fn push_paint(&mut self, paint: &Paint) -> PaintId {
         if let Some(paint_id) = self.paint_cache.get(paint) {
             return *paint_id;
         }
 
-        let paint_id = PaintId(self.paints.len() as u16);
-        self.paint_cache.insert(*paint, paint_id);
-        self.paints.push(*paint);
+        let paint_id = PaintId(self.paints.len() as u32);
+        self.paint_cache.insert((*paint).clone(), paint_id);
+        self.paints.push((*paint).clone());
         paint_id
     }
 
+    #[inline]
+    pub fn paint(&self, paint_id: PaintId) -> &Paint {
+        &self.paints[paint_id.0 as usize]
+    }
+
+    /// Changes the paint with the given ID, and so every path that uses it.
+    pub fn replace_paint(&mut self, paint_id: PaintId, new_paint: &Paint) -> Paint {
+        let old_paint = mem::replace(&mut self.paints[paint_id.0 as usize], (*new_paint).clone());
+
+        // `push_paint()` may have handed out this ID for the old paint, in which case it should
+        // now hand out another one with the same paint, if there is one.
+        if self.paint_cache.get(&old_paint) == Some(&paint_id) {
+            self.paint_cache.remove(&old_paint);
+            if let Some(other_index) = self.paints.iter().position(|paint| *paint == old_paint) {
+                self.paint_cache.insert(old_paint.clone(), PaintId(other_index as u32));
+            }
+        }
+        self.paint_cache.entry((*new_paint).clone()).or_insert(paint_id);
+        old_paint
+    }
+
+    pub fn push_clip_path(&mut self, clip_path: ClipPath) -> ClipPathId {
+        if let Some(parent) = clip_path.clip_path {
+            assert!((parent.0 as usize) < self.clip_paths.len(), "Unknown parent clip path!");
+        }
+
+        let clip_path_id = ClipPathId(self.clip_paths.len() as u32);
+        self.clip_paths.push(clip_path);
+        self.clip_path_revisions.push(build_cache::next_revision());
+        clip_path_id
+    }
+
     #[inline]
     pub fn path_count(&self) -> usize {
         self.paths.len()
     }
 
+    #[inline]
+    pub fn path(&self, index: usize) -> &PathObject {
+        &self.paths[index]
+    }
+
+    /// Replaces the path at the given index, returning the old one.
+    ///
+    /// The tiles of the other paths are kept from the last build, so for scenes that change a
+    /// little at a time this is much cheaper than building a new scene.
+    pub fn replace_path(&mut self, index: usize, path: PathObject) -> PathObject {
+        self.bounds = self.bounds.union_rect(path.outline.bounds());
+        self.path_index.replace(index as u32, path.outline.bounds());
+        self.path_revisions[index] = build_cache::next_revision();
+        mem::replace(&mut self.paths[index], path)
+    }
+
+    /// Removes the path at the given index, returning it. The paths above it move down by one.
+    ///
+    /// Layers shrink to match. The scene bounds are left alone, since they needn't be the union
+    /// of the path bounds; call `set_bounds()` afterward to shrink them.
+    pub fn remove_path(&mut self, index: usize) -> PathObject {
+        let path = self.paths.remove(index);
+        self.path_revisions.remove(index);
+        for layer in &mut self.layers {
+            if layer.path_range.start > index as u32 {
+                layer.path_range.start -= 1;
+            }
+            if layer.path_range.end > index as u32 {
+                layer.path_range.end -= 1;
+            }
+        }
+        self.path_index.remove(index as u32);
+        path
+    }
+
+    /// Moves the path at index `from` to index `to`, shifting the paths in between by one.
+    ///
+    /// Layers cover the same indices as before, so a path moved into or out of a layer joins or
+    /// leaves it. The path keeps its tiles from the last build.
+    pub fn move_path(&mut self, from: usize, to: usize) {
+        if from < to {
+            self.paths[from..=to].rotate_left(1);
+            self.path_revisions[from..=to].rotate_left(1);
+        } else {
+            self.paths[to..=from].rotate_right(1);
+            self.path_revisions[to..=from].rotate_right(1);
+        }
+        self.path_index.move_path(from as u32, to as u32);
+    }
+
+    /// Appends the paths, clip paths, layers, and paints of another scene to this one, moved by
+    /// the given transform, and returns the indices of the new paths.
+    ///
+    /// The names of the new objects are prefixed with the given name and a slash, unless it's
+    /// empty. Paints that this scene already has are shared. The outlines are copied, since each
+    /// copy of a scene is tiled separately anyway.
+    pub fn append(&mut self, other: &Scene, transform: &Transform2DF, name: &str)
+                  -> Range<usize> {
+        let paint_ids: Vec<_> = other.paints.iter().map(|paint| {
+            let mut paint = (*paint).clone();
+            paint.transform(transform);
+            self.push_paint(&paint)
+        }).collect();
+
+        let first_clip_path_index = self.clip_paths.len() as u32;
+        let clip_path_id = |clip_path: Option<ClipPathId>| {
+            clip_path.map(|ClipPathId(index)| ClipPathId(first_clip_path_index + index))
+        };
+        for clip_path in &other.clip_paths {
+            self.push_clip_path(ClipPath {
+                outline: transformed_outline(&clip_path.outline, transform),
+                fill_rule: clip_path.fill_rule,
+                clip_path: clip_path_id(clip_path.clip_path),
+                name: appended_name(name, &clip_path.name),
+            });
+        }
+
+        let first_path_index = self.paths.len();
+        for layer in &other.layers {
+            let path_range = layer.path_range.start + first_path_index as u32..
+                layer.path_range.end + first_path_index as u32;
+            self.layers.push(Layer {
+                clip_path: clip_path_id(layer.clip_path),
+                path_range,
+                name: appended_name(name, &layer.name),
+                ..(*layer).clone()
+            });
+        }
+
+        for path in &other.paths {
+            self.push_path(PathObject {
+                outline: transformed_outline(&path.outline, transform),
+                paint: paint_ids[path.paint.0 as usize],
+                fill_rule: path.fill_rule,
+                clip_path: clip_path_id(path.clip_path),
+                blend_mode: path.blend_mode,
+                name: appended_name(name, &path.name),
+            });
+        }
+
+        self.bounds = self.bounds.union_rect(transform.transform_rect(&other.bounds));
+        first_path_index..self.paths.len()
+    }
+
+    /// Returns the indices of the paths that cover the given point, topmost first.
+    ///
+    /// Each path is tested against its outline under its fill rule, and then against its clip
+    /// path and the clip paths of the layers that contain it. The point is in scene coordinates,
+    /// before any render transform is applied.
+    pub fn hit_test(&self, point: Vector2F) -> Vec<usize> {
+        let candidates = self.path_index.query(RectF::new(point, Vector2F::default()));
+        let layer_parents = self.layer_parents();
+        candidates.into_iter().rev().filter(|&path_index| {
+            let path = &self.paths[path_index as usize];
+            path.outline.contains_point(point, path.fill_rule) &&
+                self.clip_path_contains_point(path.clip_path, point) &&
+                self.layer_clip_paths_contain_point(&layer_parents, path_index, point)
+        }).map(|path_index| path_index as usize).collect()
+    }
+
+    // Tests the point against the clip paths of all the layers that contain the path.
+    fn layer_clip_paths_contain_point(&self,
+                                      layer_parents: &[Option<u32>],
+                                      path_index: u32,
+                                      point: Vector2F)
+                                      -> bool {
+        let mut layer_index = self.innermost_layer(layer_parents, path_index);
+        while let Some(index) = layer_index {
+            if !self.clip_path_contains_point(self.layers[index as usize].clip_path, point) {
+                return false;
+            }
+            layer_index = layer_parents[index as usize];
+        }
+        true
+    }
+
+    fn clip_path_contains_point(&self, mut clip_path_id: Option<ClipPathId>, point: Vector2F)
+                                -> bool {
+        while let Some(ClipPathId(clip_path_index)) = clip_path_id {
+            let clip_path = &self.clip_paths[clip_path_index as usize];
+            if !clip_path.outline.contains_point(point, clip_path.fill_rule) {
+                return false;
+            }
+            clip_path_id = clip_path.clip_path;
+        }
+        true
+    }
+
     #[inline]
     pub fn bounds(&self) -> RectF {
         self.bounds
@@ -114,16 +385,7 @@ impl Scene {
                 // TODO(pcwalton): Short circuit.
                 outline = (*original_outline).clone();
                 if options.transform.is_2d() || options.subpixel_aa_enabled {
-                    let mut transform = match options.transform {
-                        PreparedRenderTransform::Transform2D(transform) => transform,
-                        PreparedRenderTransform::None => Transform2DF::default(),
-                        PreparedRenderTransform::Perspective { .. } => unreachable!(),
-                    };
-                    if options.subpixel_aa_enabled {
-                        transform = transform
-                            .post_mul(&Transform2DF::from_scale(Vector2F::new(3.0, 1.0)))
-                    }
-                    outline.transform(&transform);
+                    outline.transform(&options.transform_2d());
                 }
                 outline.clip_against_rect(effective_view_box);
             }
@@ -139,6 +401,38 @@ impl Scene {
         outline
     }
 
+    /// Returns the indices of the paths that may be visible with the given render options, in
+    /// drawing order. Paths outside the view box or the perspective clip polygon are left out.
+    pub(crate) fn paths_in_view(&self, options: &PreparedRenderOptions) -> Vec<u32> {
+        let culling_rect = match options.transform {
+            PreparedRenderTransform::Perspective { ref clip_polygon, .. } => {
+                clip_polygon.iter().fold(None, |rect: Option<RectF>, &point| {
+                    Some(match rect {
+                        None => RectF::new(point, Vector2F::default()),
+                        Some(rect) => rect.union_point(point),
+                    })
+                })
+            }
+            PreparedRenderTransform::None | PreparedRenderTransform::Transform2D(_) => {
+                // Outlines are dilated after they're transformed, so dilate the view box before
+                // mapping it back into scene space.
+                let transform = options.transform_2d();
+                let det = transform.m11() * transform.m22() - transform.m12() * transform.m21();
+                if det == 0.0 {
+                    None
+                } else {
+                    let view_box = self.effective_view_box(options).dilate(options.dilation);
+                    Some(transform.inverse().transform_rect(&view_box))
+                }
+            }
+        };
+
+        match culling_rect {
+            Some(culling_rect) => self.path_index.query(culling_rect),
+            None => (0..self.paths.len() as u32).collect(),
+        }
+    }
+
     pub fn monochrome_color(&self) -> Option<ColorU> {
         if self.paths.is_empty() {
             return None;
@@ -152,7 +446,10 @@ impl Scene {
             .any(|path_object| path_object.paint != first_paint_id) {
             return None;
         }
-        Some(self.paints[first_paint_id.0 as usize].color)
+        match self.paints[first_paint_id.0 as usize] {
+            Paint::Color(color) => Some(color),
+            Paint::Gradient(_) | Paint::Pattern(_) => None,
+        }
     }
 
     #[inline]
@@ -169,48 +466,80 @@ impl Scene {
                     options: RenderOptions,
                     listener: Box<dyn RenderCommandListener>,
                     executor: &E)
-                    where E: Executor {
+                    where E: Executor + Sync {
         let prepared_options = options.prepare(self.bounds);
         SceneBuilder::new(self, &prepared_options, listener).build(executor)
     }
+}
 
-    pub fn write_svg<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
-        writeln!(
-            writer,
-            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
-            self.view_box.origin().x(),
-            self.view_box.origin().y(),
-            self.view_box.size().x(),
-            self.view_box.size().y()
-        )?;
-        for path_object in &self.paths {
-            let paint = &self.paints[path_object.paint.0 as usize];
-            write!(writer, "    <path")?;
-            if !path_object.name.is_empty() {
-                write!(writer, " id=\"{}\"", path_object.name)?;
-            }
-            writeln!(
-                writer,
-                " fill=\"{:?}\" d=\"{:?}\" />",
-                paint.color, path_object.outline
-            )?;
-        }
-        writeln!(writer, "</svg>")?;
-        Ok(())
+fn transformed_outline(outline: &Outline, transform: &Transform2DF) -> Outline {
+    let mut outline = (*outline).clone();
+    if !transform.is_identity() {
+        outline.transform(transform);
+    }
+    outline
+}
+
+fn appended_name(prefix: &str, name: &str) -> String {
+    match (prefix.is_empty(), name.is_empty()) {
+        (true, _) => name.to_owned(),
+        (false, true) => prefix.to_owned(),
+        (false, false) => format!("{}/{}", prefix, name),
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PathObject {
     outline: Outline,
     paint: PaintId,
+    fill_rule: FillRule,
+    clip_path: Option<ClipPathId>,
+    blend_mode: BlendMode,
     name: String,
 }
 
+/// An outline that restricts drawing to its interior.
+///
+/// A clip path may itself be clipped by another clip path, in which case it restricts drawing to
+/// the intersection of the two.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClipPath {
+    outline: Outline,
+    fill_rule: FillRule,
+    clip_path: Option<ClipPathId>,
+    name: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ClipPathId(pub u32);
+
+/// A group of paths that is rendered offscreen and then composited with an opacity.
+///
+/// Unlike multiplying the opacity into each path, overlapping paths within a layer don't show
+/// through one another.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Layer {
+    opacity: f32,
+    clip_path: Option<ClipPathId>,
+    blend_mode: BlendMode,
+    path_range: Range<u32>,
+    name: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LayerId(pub u32);
+
 impl PathObject {
     #[inline]
     pub fn new(outline: Outline, paint: PaintId, name: String) -> PathObject {
-        PathObject { outline, paint, name }
+        PathObject {
+            outline,
+            paint,
+            fill_rule: FillRule::Winding,
+            clip_path: None,
+            blend_mode: BlendMode::SourceOver,
+            name,
+        }
     }
 
     #[inline]
@@ -222,4 +551,382 @@ impl PathObject {
     pub(crate) fn paint(&self) -> PaintId {
         self.paint
     }
+
+    #[inline]
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    #[inline]
+    pub fn set_fill_rule(&mut self, new_fill_rule: FillRule) {
+        self.fill_rule = new_fill_rule
+    }
+
+    #[inline]
+    pub fn clip_path(&self) -> Option<ClipPathId> {
+        self.clip_path
+    }
+
+    #[inline]
+    pub fn set_clip_path(&mut self, new_clip_path: Option<ClipPathId>) {
+        self.clip_path = new_clip_path
+    }
+
+    #[inline]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    #[inline]
+    pub fn set_blend_mode(&mut self, new_blend_mode: BlendMode) {
+        self.blend_mode = new_blend_mode
+    }
+
+    /// The name given to the path when it was created. Paths imported from SVG are named
+    /// `Fill(id)` or `Stroke(id)` after the element and the part of it they came from.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl ClipPath {
+    #[inline]
+    pub fn new(outline: Outline, name: String) -> ClipPath {
+        ClipPath { outline, fill_rule: FillRule::Winding, clip_path: None, name }
+    }
+
+    #[inline]
+    pub fn outline(&self) -> &Outline {
+        &self.outline
+    }
+
+    #[inline]
+    pub fn fill_rule(&self) -> FillRule {
+        self.fill_rule
+    }
+
+    #[inline]
+    pub fn set_fill_rule(&mut self, new_fill_rule: FillRule) {
+        self.fill_rule = new_fill_rule
+    }
+
+    /// The clip path that encloses this one, if any.
+    #[inline]
+    pub fn clip_path(&self) -> Option<ClipPathId> {
+        self.clip_path
+    }
+
+    #[inline]
+    pub fn set_clip_path(&mut self, new_clip_path: Option<ClipPathId>) {
+        self.clip_path = new_clip_path
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Layer {
+    #[inline]
+    pub fn new(name: String) -> Layer {
+        Layer {
+            opacity: 1.0,
+            clip_path: None,
+            blend_mode: BlendMode::SourceOver,
+            path_range: 0..0,
+            name,
+        }
+    }
+
+    #[inline]
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    #[inline]
+    pub fn set_opacity(&mut self, new_opacity: f32) {
+        self.opacity = new_opacity
+    }
+
+    /// The clip path that masks the layer as it's composited, if any.
+    #[inline]
+    pub fn clip_path(&self) -> Option<ClipPathId> {
+        self.clip_path
+    }
+
+    #[inline]
+    pub fn set_clip_path(&mut self, new_clip_path: Option<ClipPathId>) {
+        self.clip_path = new_clip_path
+    }
+
+    /// How the layer is combined with what lies beneath it when it's composited.
+    #[inline]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    #[inline]
+    pub fn set_blend_mode(&mut self, new_blend_mode: BlendMode) {
+        self.blend_mode = new_blend_mode
+    }
+
+    /// The indices of the paths in this layer, including those in nested layers.
+    #[inline]
+    pub(crate) fn path_range(&self) -> Range<u32> {
+        self.path_range.clone()
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClipPath, ClipPathId, Layer, PathObject, Scene};
+    use crate::paint::{Gradient, Paint, PaintId};
+    use pathfinder_geometry::basic::line_segment::LineSegment2F;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::transform2d::Transform2DF;
+    use pathfinder_geometry::basic::vector::Vector2F;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use std::f32::consts::PI;
+
+    fn push_rect(outline: &mut Outline, rect: RectF) {
+        let mut contour = Contour::new();
+        contour.push_endpoint(rect.origin());
+        contour.push_endpoint(rect.upper_right());
+        contour.push_endpoint(rect.lower_right());
+        contour.push_endpoint(rect.lower_left());
+        contour.close();
+        outline.push_contour(contour);
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> RectF {
+        RectF::new(Vector2F::new(x, y), Vector2F::new(width, height))
+    }
+
+    fn path(paint: PaintId, rects: &[RectF]) -> PathObject {
+        let mut outline = Outline::new();
+        for &rect in rects {
+            push_rect(&mut outline, rect);
+        }
+        PathObject::new(outline, paint, String::new())
+    }
+
+    #[test]
+    fn test_hit_test_order_and_fill_rule() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        scene.push_path(path(paint, &[rect(0.0, 0.0, 32.0, 32.0)]));
+
+        // Two nested squares with the same orientation: a hole under even-odd only.
+        let mut ring = path(paint, &[rect(8.0, 8.0, 16.0, 16.0), rect(12.0, 12.0, 8.0, 8.0)]);
+        ring.set_fill_rule(FillRule::EvenOdd);
+        scene.push_path(ring);
+
+        assert_eq!(scene.hit_test(Vector2F::new(10.0, 10.0)), vec![1, 0]);
+        assert_eq!(scene.hit_test(Vector2F::new(16.0, 16.0)), vec![0]);
+        assert_eq!(scene.hit_test(Vector2F::new(40.0, 16.0)), Vec::<usize>::new());
+
+        let mut ring = scene.path(1).clone();
+        ring.set_fill_rule(FillRule::Winding);
+        scene.replace_path(1, ring);
+        assert_eq!(scene.hit_test(Vector2F::new(16.0, 16.0)), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_hit_test_curves_and_clips() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+
+        let mut circle = Contour::new();
+        circle.push_ellipse(&Transform2DF::from_scale_rotation_translation(Vector2F::splat(16.0),
+                                                                          0.0,
+                                                                          Vector2F::splat(16.0)));
+        let mut outline = Outline::new();
+        outline.push_contour(circle);
+        scene.push_path(PathObject::new(outline, paint, "Fill(circle)".to_owned()));
+
+        assert_eq!(scene.hit_test(Vector2F::new(16.0, 16.0)), vec![0]);
+        assert_eq!(scene.hit_test(Vector2F::new(16.0, 1.0)), vec![0]);
+        assert_eq!(scene.hit_test(Vector2F::new(2.0, 2.0)), Vec::<usize>::new());
+        assert_eq!(scene.path(0).name(), "Fill(circle)");
+
+        let mut outline = Outline::new();
+        push_rect(&mut outline, rect(0.0, 0.0, 16.0, 32.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(outline, String::new()));
+        let mut clipped = scene.path(0).clone();
+        clipped.set_clip_path(Some(clip_path));
+        scene.push_path(clipped);
+
+        assert_eq!(scene.hit_test(Vector2F::new(8.0, 16.0)), vec![1, 0]);
+        assert_eq!(scene.hit_test(Vector2F::new(24.0, 16.0)), vec![0]);
+
+        // The clip path of a layer applies to the paths of the layers within it too.
+        let mut outline = Outline::new();
+        push_rect(&mut outline, rect(0.0, 16.0, 32.0, 16.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(outline, String::new()));
+        let mut layer = Layer::new(String::new());
+        layer.set_clip_path(Some(clip_path));
+        scene.push_layer(layer);
+        scene.push_layer(Layer::new(String::new()));
+        let circle = scene.path(0).clone();
+        scene.push_path(circle);
+        scene.pop_layer();
+        scene.pop_layer();
+
+        assert_eq!(scene.hit_test(Vector2F::new(24.0, 24.0)), vec![2, 0]);
+        assert_eq!(scene.hit_test(Vector2F::new(24.0, 8.0)), vec![0]);
+    }
+
+    #[test]
+    fn test_innermost_layers() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let push_path = |scene: &mut Scene| {
+            scene.push_path(PathObject::new(Outline::new(), paint, String::new()))
+        };
+
+        push_path(&mut scene);
+        scene.push_layer(Layer::new(String::new()));
+        push_path(&mut scene);
+        scene.push_layer(Layer::new(String::new()));
+        push_path(&mut scene);
+        scene.pop_layer();
+        push_path(&mut scene);
+        scene.pop_layer();
+        scene.push_layer(Layer::new(String::new()));
+        scene.pop_layer();
+        scene.push_layer(Layer::new(String::new()));
+        push_path(&mut scene);
+        scene.pop_layer();
+        push_path(&mut scene);
+
+        let layer_parents = scene.layer_parents();
+        assert_eq!((layer_parents[0], layer_parents[1], layer_parents[3]), (None, Some(0), None));
+        let innermost_layers: Vec<_> = (0..6).map(|path_index| {
+            scene.innermost_layer(&layer_parents, path_index)
+        }).collect();
+        assert_eq!(innermost_layers, vec![None, Some(0), Some(1), Some(0), Some(3), None]);
+    }
+
+    #[test]
+    fn test_remove_and_move_paths() {
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        scene.push_path(path(paint, &[rect(0.0, 0.0, 8.0, 8.0)]));
+        scene.push_layer(Layer::new(String::new()));
+        scene.push_path(path(paint, &[rect(0.0, 0.0, 16.0, 16.0)]));
+        scene.push_path(path(paint, &[rect(0.0, 0.0, 24.0, 24.0)]));
+        scene.pop_layer();
+        scene.push_path(path(paint, &[rect(0.0, 0.0, 32.0, 32.0)]));
+        assert_eq!(scene.hit_test(Vector2F::splat(4.0)), vec![3, 2, 1, 0]);
+
+        let removed = scene.remove_path(1);
+        assert_eq!(removed.outline().bounds(), rect(0.0, 0.0, 16.0, 16.0));
+        assert_eq!(scene.path_count(), 3);
+        assert_eq!(scene.layers[0].path_range(), 1..2);
+        assert_eq!(scene.hit_test(Vector2F::splat(12.0)), vec![2, 1]);
+
+        // Move the biggest square to the bottom, and then the smallest one back to the top.
+        scene.move_path(2, 0);
+        assert_eq!(scene.path(0).outline().bounds(), rect(0.0, 0.0, 32.0, 32.0));
+        assert_eq!(scene.hit_test(Vector2F::splat(28.0)), vec![0]);
+        scene.move_path(1, 2);
+        assert_eq!(scene.path(2).outline().bounds(), rect(0.0, 0.0, 8.0, 8.0));
+        assert_eq!(scene.hit_test(Vector2F::splat(4.0)), vec![2, 1, 0]);
+        assert_eq!(scene.hit_test(Vector2F::splat(12.0)), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_replace_paint() {
+        let mut scene = Scene::new();
+        let black = Paint::Color(ColorU::black());
+        let white = Paint::Color(ColorU::from_u32(0xffffffff));
+        let first = scene.push_paint(&black);
+        assert_eq!(scene.replace_paint(first, &white), black);
+        assert_eq!(*scene.paint(first), white);
+        assert_eq!(scene.push_paint(&white), first);
+
+        // The old paint gets a new ID, and the ID of a paint that's still around is handed out.
+        let second = scene.push_paint(&black);
+        assert_eq!(second, PaintId(1));
+        scene.replace_paint(first, &black);
+        scene.replace_paint(second, &white);
+        assert_eq!(scene.push_paint(&black), first);
+        assert_eq!(scene.push_paint(&white), second);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut icon = Scene::new();
+        icon.push_paint(&Paint::Color(ColorU::black()));
+        let gradient = Gradient::linear(LineSegment2F::new(Vector2F::default(),
+                                                           Vector2F::splat(8.0)));
+        let gradient = icon.push_paint(&Paint::Gradient(gradient));
+        let mut outline = Outline::new();
+        push_rect(&mut outline, rect(0.0, 0.0, 4.0, 8.0));
+        let clip_path = icon.push_clip_path(ClipPath::new(outline, "clip".to_owned()));
+        icon.push_layer(Layer::new(String::new()));
+        let mut clipped = path(gradient, &[rect(0.0, 0.0, 8.0, 8.0)]);
+        clipped.set_clip_path(Some(clip_path));
+        icon.push_path(clipped);
+        icon.pop_layer();
+
+        let mut scene = Scene::new();
+        let white = scene.push_paint(&Paint::Color(ColorU::from_u32(0xffffffff)));
+        scene.push_path(path(white, &[rect(0.0, 0.0, 64.0, 64.0)]));
+        let black = scene.push_paint(&Paint::Color(ColorU::black()));
+
+        let transform = Transform2DF::from_translation(Vector2F::splat(16.0));
+        assert_eq!(scene.append(&icon, &Transform2DF::default(), ""), 1..2);
+        assert_eq!(scene.append(&icon, &transform, "icon"), 2..3);
+        assert_eq!(scene.paints.len(), 4);
+        assert_eq!(scene.clip_paths.len(), 2);
+        assert_eq!(scene.layers.iter().map(|layer| layer.path_range()).collect::<Vec<_>>(),
+                   vec![1..2, 2..3]);
+        assert_eq!(scene.path(1).name(), "");
+        assert_eq!(scene.path(2).name(), "icon");
+        assert_eq!(scene.clip_paths[1].name(), "icon/clip");
+        assert_eq!(scene.path(2).clip_path(), Some(ClipPathId(1)));
+        assert_eq!(scene.path(2).outline().bounds(), rect(16.0, 16.0, 8.0, 8.0));
+        match *scene.paint(scene.path(2).paint()) {
+            Paint::Gradient(ref gradient) => assert_eq!(gradient.transform, transform),
+            _ => panic!("the gradient wasn't appended"),
+        }
+
+        // Paints are shared with the scene, and clip paths only apply to their own copy.
+        assert_eq!(scene.push_paint(&Paint::Color(ColorU::black())), black);
+        assert_eq!(scene.hit_test(Vector2F::splat(6.0)), vec![0]);
+        assert_eq!(scene.hit_test(Vector2F::splat(2.0)), vec![1, 0]);
+        assert_eq!(scene.hit_test(Vector2F::splat(18.0)), vec![2, 0]);
+        assert_eq!(scene.hit_test(Vector2F::splat(22.0)), vec![0]);
+    }
+
+    #[test]
+    fn test_append_rotated_paint() {
+        // The gradient is stretched before it is turned, not after.
+        let mut icon = Scene::new();
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::default(),
+                                                               Vector2F::new(1.0, 0.0)));
+        gradient.transform = Transform2DF::from_scale(Vector2F::new(2.0, 1.0));
+        let gradient = icon.push_paint(&Paint::Gradient(gradient));
+        icon.push_path(path(gradient, &[rect(0.0, 0.0, 8.0, 8.0)]));
+
+        let mut scene = Scene::new();
+        scene.append(&icon, &Transform2DF::from_rotation(PI * 0.5), "");
+        match *scene.paint(scene.path(0).paint()) {
+            Paint::Gradient(ref gradient) => {
+                let end = gradient.transform.transform_point(Vector2F::new(1.0, 0.0));
+                assert!(end.x().abs() < 0.0001 && (end.y() - 2.0).abs() < 0.0001);
+            }
+            _ => panic!("the gradient wasn't appended"),
+        }
+    }
 }
@@ -0,0 +1,611 @@
+// pathfinder/renderer/src/scene_file/binary.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A compact binary encoding of the serde data model.
+//!
+//! The encoding isn't self-describing, so the reader must know the types it expects. Numbers are
+//! little-endian and of fixed size; booleans and `Option` tags are single bytes; strings,
+//! sequences, and maps are prefixed with a `u32` length; and enum variants are prefixed with
+//! their `u32` index. Structs and tuples are their fields in order, with no framing.
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::de::{self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess};
+use serde::de::{VariantAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, SerializeStruct};
+use serde::ser::{SerializeStructVariant, SerializeTuple, SerializeTupleStruct};
+use serde::ser::SerializeTupleVariant;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Message(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref error) => write!(formatter, "{}", error),
+            Error::Message(ref message) => write!(formatter, "{}", message),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+impl From<io::Error> for Error {
+    #[inline]
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T>(message: T) -> Error where T: Display {
+        Error::Message(message.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T>(message: T) -> Error where T: Display {
+        Error::Message(message.to_string())
+    }
+}
+
+pub fn to_writer<W, T>(writer: &mut W, value: &T) -> Result<(), Error>
+                       where W: Write, T: Serialize + ?Sized {
+    value.serialize(&mut Serializer { writer })
+}
+
+pub fn from_reader<R, T>(reader: &mut R) -> Result<T, Error>
+                        where R: Read, T: de::DeserializeOwned {
+    T::deserialize(&mut Deserializer { reader })
+}
+
+// Serialization
+
+struct Serializer<'a, W> where W: Write {
+    writer: &'a mut W,
+}
+
+impl<'a, W> Serializer<'a, W> where W: Write {
+    fn write_length(&mut self, length: Option<usize>) -> Result<(), Error> {
+        match length {
+            Some(length) if length <= u32::MAX as usize => {
+                Ok(self.writer.write_u32::<LittleEndian>(length as u32)?)
+            }
+            Some(_) => Err(Error::Message("sequence is too long".to_owned())),
+            None => Err(Error::Message("sequence length must be known".to_owned())),
+        }
+    }
+}
+
+impl<'a, 'b, W> ser::Serializer for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        Ok(self.writer.write_u8(value as u8)?)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        Ok(self.writer.write_i8(value)?)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        Ok(self.writer.write_i16::<LittleEndian>(value)?)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        Ok(self.writer.write_i32::<LittleEndian>(value)?)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        Ok(self.writer.write_i64::<LittleEndian>(value)?)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        Ok(self.writer.write_u8(value)?)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        Ok(self.writer.write_u16::<LittleEndian>(value)?)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        Ok(self.writer.write_u32::<LittleEndian>(value)?)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        Ok(self.writer.write_u64::<LittleEndian>(value)?)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        Ok(self.writer.write_f32::<LittleEndian>(value)?)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        Ok(self.writer.write_f64::<LittleEndian>(value)?)
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        Ok(self.writer.write_u32::<LittleEndian>(value as u32)?)
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.serialize_bytes(value.as_bytes())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        self.write_length(Some(value.len()))?;
+        Ok(self.writer.write_all(value)?)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(self.writer.write_u8(0)?)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error> where T: Serialize + ?Sized {
+        self.writer.write_u8(1)?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, variant_index: u32, _: &'static str)
+                              -> Result<(), Error> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<(), Error>
+                                   where T: Serialize + ?Sized {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(self,
+                                    _: &'static str,
+                                    variant_index: u32,
+                                    _: &'static str,
+                                    value: &T)
+                                    -> Result<(), Error>
+                                    where T: Serialize + ?Sized {
+        self.writer.write_u32::<LittleEndian>(variant_index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, length: Option<usize>) -> Result<Self, Error> {
+        self.write_length(length)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(self,
+                               _: &'static str,
+                               variant_index: u32,
+                               _: &'static str,
+                               _: usize)
+                               -> Result<Self, Error> {
+        self.writer.write_u32::<LittleEndian>(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, length: Option<usize>) -> Result<Self, Error> {
+        self.write_length(length)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(self,
+                                _: &'static str,
+                                variant_index: u32,
+                                _: &'static str,
+                                _: usize)
+                                -> Result<Self, Error> {
+        self.writer.write_u32::<LittleEndian>(variant_index)?;
+        Ok(self)
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, 'b, W> SerializeSeq for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+                            where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, 'b, W> SerializeTuple for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+                            where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, 'b, W> SerializeTupleStruct for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error> where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, 'b, W> SerializeTupleVariant for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error> where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, 'b, W> SerializeMap for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error> where T: Serialize + ?Sized {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error> where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, 'b, W> SerializeStruct for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _: &'static str, value: &T) -> Result<(), Error>
+                          where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a, 'b, W> SerializeStructVariant for &'b mut Serializer<'a, W> where W: Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _: &'static str, value: &T) -> Result<(), Error>
+                          where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+// Deserialization
+
+struct Deserializer<'a, R> where R: Read {
+    reader: &'a mut R,
+}
+
+impl<'a, R> Deserializer<'a, R> where R: Read {
+    fn read_length(&mut self) -> Result<usize, Error> {
+        Ok(self.reader.read_u32::<LittleEndian>()? as usize)
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        // Don't trust the length enough to allocate it all up front.
+        let length = self.read_length()?;
+        let mut bytes = vec![];
+        (&mut *self.reader).take(length as u64).read_to_end(&mut bytes)?;
+        if bytes.len() < length {
+            return Err(Error::Io(io::Error::from(io::ErrorKind::UnexpectedEof)));
+        }
+        Ok(bytes)
+    }
+
+    fn read_string(&mut self) -> Result<String, Error> {
+        String::from_utf8(self.read_bytes()?).map_err(|_| {
+            Error::Message("string is not valid UTF-8".to_owned())
+        })
+    }
+}
+
+impl<'de, 'a, 'b, R> de::Deserializer<'de> for &'b mut Deserializer<'a, R> where R: Read {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        Err(Error::Message("the binary scene format is not self-describing".to_owned()))
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        match self.reader.read_u8()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            _ => Err(Error::Message("invalid boolean".to_owned())),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_i8(self.reader.read_i8()?)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_i16(self.reader.read_i16::<LittleEndian>()?)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_i32(self.reader.read_i32::<LittleEndian>()?)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_i64(self.reader.read_i64::<LittleEndian>()?)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_u8(self.reader.read_u8()?)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_u16(self.reader.read_u16::<LittleEndian>()?)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_u32(self.reader.read_u32::<LittleEndian>()?)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_u64(self.reader.read_u64::<LittleEndian>()?)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_f32(self.reader.read_f32::<LittleEndian>()?)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_f64(self.reader.read_f64::<LittleEndian>()?)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        match std::char::from_u32(self.reader.read_u32::<LittleEndian>()?) {
+            Some(value) => visitor.visit_char(value),
+            None => Err(Error::Message("invalid character".to_owned())),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_byte_buf(self.read_bytes()?)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Error>
+                               where V: Visitor<'de> {
+        visitor.visit_byte_buf(self.read_bytes()?)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        match self.reader.read_u8()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            _ => Err(Error::Message("invalid option tag".to_owned())),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, Error>
+                                  where V: Visitor<'de> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V)
+                                     -> Result<V::Value, Error>
+                                     where V: Visitor<'de> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        let length = self.read_length()?;
+        visitor.visit_seq(Access { deserializer: self, remaining: length })
+    }
+
+    fn deserialize_tuple<V>(self, length: usize, visitor: V) -> Result<V::Value, Error>
+                            where V: Visitor<'de> {
+        visitor.visit_seq(Access { deserializer: self, remaining: length })
+    }
+
+    fn deserialize_tuple_struct<V>(self, _: &'static str, length: usize, visitor: V)
+                                   -> Result<V::Value, Error>
+                                   where V: Visitor<'de> {
+        self.deserialize_tuple(length, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        let length = self.read_length()?;
+        visitor.visit_map(Access { deserializer: self, remaining: length })
+    }
+
+    fn deserialize_struct<V>(self,
+                             _: &'static str,
+                             fields: &'static [&'static str],
+                             visitor: V)
+                             -> Result<V::Value, Error>
+                             where V: Visitor<'de> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V>(self,
+                           _: &'static str,
+                           _: &'static [&'static str],
+                           visitor: V)
+                           -> Result<V::Value, Error>
+                           where V: Visitor<'de> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+                                 where V: Visitor<'de> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, _: V) -> Result<V::Value, Error> where V: Visitor<'de> {
+        Err(Error::Message("the binary scene format can't skip values".to_owned()))
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct Access<'a, 'b, R> where R: Read {
+    deserializer: &'b mut Deserializer<'a, R>,
+    remaining: usize,
+}
+
+impl<'de, 'a, 'b, R> SeqAccess<'de> for Access<'a, 'b, R> where R: Read {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+                            where T: DeserializeSeed<'de> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de, 'a, 'b, R> MapAccess<'de> for Access<'a, 'b, R> where R: Read {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+                        where K: DeserializeSeed<'de> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+                          where V: DeserializeSeed<'de> {
+        seed.deserialize(&mut *self.deserializer)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de, 'a, 'b, R> EnumAccess<'de> for &'b mut Deserializer<'a, R> where R: Read {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), Error>
+                       where V: DeserializeSeed<'de> {
+        let variant_index = self.reader.read_u32::<LittleEndian>()?;
+        let variant_index: de::value::U32Deserializer<Error> = variant_index.into_deserializer();
+        let value = seed.deserialize(variant_index)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a, 'b, R> VariantAccess<'de> for &'b mut Deserializer<'a, R> where R: Read {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+                               where T: DeserializeSeed<'de> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, length: usize, visitor: V) -> Result<V::Value, Error>
+                        where V: Visitor<'de> {
+        de::Deserializer::deserialize_tuple(self, length, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V)
+                         -> Result<V::Value, Error>
+                         where V: Visitor<'de> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
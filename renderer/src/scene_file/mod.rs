@@ -0,0 +1,469 @@
+// pathfinder/renderer/src/scene_file/mod.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Saving scenes to files and loading them back.
+//!
+//! A scene file holds the paths, clip paths, layers, and paints of a scene along with its view
+//! box and bounds, so a scene can be rendered again without the SVG or canvas code that built
+//! it. Images are stored once each in a table, which patterns refer to by index, so patterns that
+//! share an image still share it once the scene is loaded again. There are two variants:
+//!
+//! * The binary format is the magic number `PFSC`, the format version as a little-endian `u32`,
+//!   and then the scene in the encoding described in `binary.rs`.
+//!
+//! * The JSON format is an object with a `version` field and a `scene` field.
+//!
+//! Readers reject files with a version other than `SCENE_FILE_VERSION`.
+
+use crate::paint::{Gradient, Image, Paint, PaintId, Pattern};
+use crate::scene::{ClipPath, Layer, PathObject, Scene};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use hashbrown::HashMap;
+use pathfinder_geometry::basic::rect::RectF;
+use pathfinder_geometry::basic::transform2d::Transform2DF;
+use pathfinder_geometry::color::ColorU;
+use serde_json::{self, Value};
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+mod binary;
+
+/// The version of the scene file format that this crate reads and writes.
+pub const SCENE_FILE_VERSION: u32 = 1;
+
+const BINARY_MAGIC: [u8; 4] = *b"PFSC";
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(io::Error),
+    /// The file doesn't start with the binary scene file magic number.
+    NotASceneFile,
+    /// The file was written by an incompatible version of Pathfinder.
+    UnsupportedVersion(u32),
+    /// The file couldn't be decoded.
+    Malformed(String),
+    /// The file decoded, but refers to paints, clip paths, or paths that it doesn't contain.
+    Invalid(&'static str),
+}
+
+impl Display for SceneFileError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            SceneFileError::Io(ref error) => write!(formatter, "I/O error: {}", error),
+            SceneFileError::NotASceneFile => write!(formatter, "not a scene file"),
+            SceneFileError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported scene file version {}", version)
+            }
+            SceneFileError::Malformed(ref message) => {
+                write!(formatter, "malformed scene file: {}", message)
+            }
+            SceneFileError::Invalid(message) => write!(formatter, "invalid scene: {}", message),
+        }
+    }
+}
+
+impl StdError for SceneFileError {}
+
+impl From<io::Error> for SceneFileError {
+    #[inline]
+    fn from(error: io::Error) -> SceneFileError {
+        SceneFileError::Io(error)
+    }
+}
+
+impl From<binary::Error> for SceneFileError {
+    fn from(error: binary::Error) -> SceneFileError {
+        match error {
+            binary::Error::Io(error) => SceneFileError::Io(error),
+            binary::Error::Message(message) => SceneFileError::Malformed(message),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SceneFileError {
+    fn from(error: serde_json::Error) -> SceneFileError {
+        if error.is_io() {
+            SceneFileError::Io(error.into())
+        } else {
+            SceneFileError::Malformed(error.to_string())
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SceneDataRef<'a> {
+    view_box: RectF,
+    bounds: RectF,
+    images: Vec<&'a Image>,
+    paints: Vec<PaintRef<'a>>,
+    clip_paths: &'a [ClipPath],
+    paths: &'a [PathObject],
+    layers: &'a [Layer],
+}
+
+#[derive(Deserialize)]
+struct SceneData {
+    view_box: RectF,
+    bounds: RectF,
+    images: Vec<Image>,
+    paints: Vec<PaintData>,
+    clip_paths: Vec<ClipPath>,
+    paths: Vec<PathObject>,
+    layers: Vec<Layer>,
+}
+
+// A paint as stored in a scene file, with the image of a pattern replaced by its index in the
+// image table.
+#[derive(Serialize)]
+#[serde(rename = "Paint")]
+enum PaintRef<'a> {
+    Color(ColorU),
+    Gradient(&'a Gradient),
+    Pattern(PatternData),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Paint")]
+enum PaintData {
+    Color(ColorU),
+    Gradient(Gradient),
+    Pattern(PatternData),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Pattern")]
+struct PatternData {
+    image: u32,
+    transform: Transform2DF,
+    repeat_x: bool,
+    repeat_y: bool,
+    opacity: f32,
+}
+
+#[derive(Serialize)]
+struct JsonSceneFileRef<'a> {
+    version: u32,
+    scene: SceneDataRef<'a>,
+}
+
+impl Scene {
+    /// Writes this scene in the binary scene file format.
+    pub fn write_binary<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        writer.write_all(&BINARY_MAGIC)?;
+        writer.write_u32::<LittleEndian>(SCENE_FILE_VERSION)?;
+        binary::to_writer(writer, &self.scene_data()).map_err(|error| match error {
+            binary::Error::Io(error) => error,
+            binary::Error::Message(message) => io::Error::other(message),
+        })
+    }
+
+    /// Reads a scene written with `write_binary()`.
+    pub fn read_binary<R>(reader: &mut R) -> Result<Scene, SceneFileError> where R: Read {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != BINARY_MAGIC {
+            return Err(SceneFileError::NotASceneFile);
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != SCENE_FILE_VERSION {
+            return Err(SceneFileError::UnsupportedVersion(version));
+        }
+        Scene::from_scene_data(binary::from_reader(reader)?)
+    }
+
+    /// Writes this scene in the JSON scene file format.
+    pub fn write_json<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        let file = JsonSceneFileRef { version: SCENE_FILE_VERSION, scene: self.scene_data() };
+        serde_json::to_writer(writer, &file).map_err(io::Error::from)
+    }
+
+    /// Reads a scene written with `write_json()`.
+    pub fn read_json<R>(reader: &mut R) -> Result<Scene, SceneFileError> where R: Read {
+        // Check the version before trying to make sense of the rest.
+        let mut file: Value = serde_json::from_reader(reader)?;
+        match file.get("version").and_then(Value::as_u64) {
+            Some(version) if version == SCENE_FILE_VERSION as u64 => {}
+            Some(version) => return Err(SceneFileError::UnsupportedVersion(version as u32)),
+            None => return Err(SceneFileError::NotASceneFile),
+        }
+        let scene = match file.get_mut("scene") {
+            Some(scene) => scene.take(),
+            None => return Err(SceneFileError::NotASceneFile),
+        };
+        Scene::from_scene_data(serde_json::from_value(scene)?)
+    }
+
+    fn scene_data(&self) -> SceneDataRef<'_> {
+        // Images compare by identity, so each shared image gets one entry in the table.
+        let (mut images, mut image_indices) = (vec![], HashMap::new());
+        let paints = self.paints.iter().map(|paint| {
+            match *paint {
+                Paint::Color(color) => PaintRef::Color(color),
+                Paint::Gradient(ref gradient) => PaintRef::Gradient(gradient),
+                Paint::Pattern(ref pattern) => {
+                    let image = *image_indices.entry(&pattern.image).or_insert_with(|| {
+                        images.push(&pattern.image);
+                        images.len() as u32 - 1
+                    });
+                    PaintRef::Pattern(PatternData {
+                        image,
+                        transform: pattern.transform,
+                        repeat_x: pattern.repeat_x,
+                        repeat_y: pattern.repeat_y,
+                        opacity: pattern.opacity,
+                    })
+                }
+            }
+        }).collect();
+
+        SceneDataRef {
+            view_box: self.view_box(),
+            bounds: self.bounds(),
+            images,
+            paints,
+            clip_paths: &self.clip_paths,
+            paths: &self.paths,
+            layers: &self.layers,
+        }
+    }
+
+    fn from_scene_data(data: SceneData) -> Result<Scene, SceneFileError> {
+        let SceneData { view_box, bounds, images, paints, clip_paths, paths, layers } = data;
+
+        // Check every reference so that building the scene can't go out of bounds. Outlines were
+        // already checked as they were deserialized. Patterns that refer to the same image get
+        // clones of it, so they share it as they did when the scene was written.
+        let paints = paints.into_iter().map(|paint| {
+            match paint {
+                PaintData::Color(color) => Ok(Paint::Color(color)),
+                PaintData::Gradient(gradient) => Ok(Paint::Gradient(gradient)),
+                PaintData::Pattern(pattern) => {
+                    let image = match images.get(pattern.image as usize) {
+                        Some(image) => image.clone(),
+                        None => return Err(SceneFileError::Invalid("pattern has an unknown image")),
+                    };
+                    Ok(Paint::Pattern(Pattern {
+                        image,
+                        transform: pattern.transform,
+                        repeat_x: pattern.repeat_x,
+                        repeat_y: pattern.repeat_y,
+                        opacity: pattern.opacity,
+                    }))
+                }
+            }
+        }).collect::<Result<Vec<_>, _>>()?;
+        for (clip_path_index, clip_path) in clip_paths.iter().enumerate() {
+            if let Some(parent) = clip_path.clip_path() {
+                if parent.0 as usize >= clip_path_index {
+                    return Err(SceneFileError::Invalid("clip path has an unknown parent"));
+                }
+            }
+        }
+        for path in &paths {
+            if path.paint().0 as usize >= paints.len() {
+                return Err(SceneFileError::Invalid("path has an unknown paint"));
+            }
+            if let Some(clip_path) = path.clip_path() {
+                if clip_path.0 as usize >= clip_paths.len() {
+                    return Err(SceneFileError::Invalid("path has an unknown clip path"));
+                }
+            }
+        }
+        for layer in &layers {
+            let path_range = layer.path_range();
+            if path_range.start > path_range.end || path_range.end as usize > paths.len() {
+                return Err(SceneFileError::Invalid("layer has an unknown path"));
+            }
+            if let Some(clip_path) = layer.clip_path() {
+                if clip_path.0 as usize >= clip_paths.len() {
+                    return Err(SceneFileError::Invalid("layer has an unknown clip path"));
+                }
+            }
+        }
+
+        let mut paint_cache = HashMap::new();
+        for (paint_index, paint) in paints.iter().enumerate() {
+            paint_cache.entry(paint.clone()).or_insert(PaintId(paint_index as u32));
+        }
+
+        let mut scene = Scene::new();
+        scene.paints = paints;
+        scene.paint_cache = paint_cache;
+        for clip_path in clip_paths {
+            scene.push_clip_path(clip_path);
+        }
+        for path in paths {
+            scene.push_path(path);
+        }
+        scene.layers = layers;
+        scene.set_view_box(view_box);
+        scene.set_bounds(bounds);
+        Ok(scene)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SceneFileError;
+    use crate::paint::{BlendMode, ColorStop, Gradient, Image, Paint, Pattern};
+    use crate::scene::{ClipPath, Layer, PathObject, Scene};
+    use pathfinder_geometry::basic::line_segment::LineSegment2F;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::transform2d::Transform2DF;
+    use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use std::sync::Arc;
+
+    const WHITE: ColorU = ColorU { r: 255, g: 255, b: 255, a: 255 };
+
+    fn outline(rect: RectF) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(rect.origin());
+        contour.push_quadratic(rect.upper_right(), rect.lower_right());
+        contour.push_endpoint(rect.lower_left());
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::new(64.0, 64.0)));
+
+        let mut clip_path = ClipPath::new(outline(RectF::new(Vector2F::new(0.0, 0.0),
+                                                             Vector2F::new(32.0, 64.0))),
+                                          "clip".to_owned());
+        clip_path.set_fill_rule(FillRule::EvenOdd);
+        let clip_path = scene.push_clip_path(clip_path);
+
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::default(),
+                                                               Vector2F::new(64.0, 0.0)));
+        gradient.add_color_stop(ColorStop { offset: 0.0, color: ColorU::black() });
+        gradient.add_color_stop(ColorStop { offset: 1.0, color: WHITE });
+        let gradient = scene.push_paint(&Paint::Gradient(gradient));
+        let pixels = Arc::new(vec![WHITE, ColorU::black(), ColorU::black(),
+                                   ColorU::transparent_black()]);
+        let pattern = Pattern::new(Image::new(Vector2I::splat(2), pixels));
+        let pattern = scene.push_paint(&Paint::Pattern(pattern));
+
+        let mut path = PathObject::new(outline(RectF::new(Vector2F::new(8.0, 8.0),
+                                                          Vector2F::new(48.0, 48.0))),
+                                       gradient,
+                                       "gradient".to_owned());
+        path.set_clip_path(Some(clip_path));
+        scene.push_path(path);
+
+        let mut layer = Layer::new("layer".to_owned());
+        layer.set_opacity(0.5);
+        layer.set_blend_mode(BlendMode::Multiply);
+        scene.push_layer(layer);
+        scene.push_path(PathObject::new(outline(RectF::new(Vector2F::new(16.0, 16.0),
+                                                           Vector2F::new(8.0, 8.0))),
+                                        pattern,
+                                        "pattern".to_owned()));
+        scene.pop_layer();
+        scene
+    }
+
+    fn to_json(scene: &Scene) -> String {
+        let mut json = vec![];
+        scene.write_json(&mut json).unwrap();
+        String::from_utf8(json).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let scene = scene();
+        let json = to_json(&scene);
+
+        let mut binary = vec![];
+        scene.write_binary(&mut binary).unwrap();
+        let binary_scene = Scene::read_binary(&mut &binary[..]).unwrap();
+        assert_eq!(to_json(&binary_scene), json);
+        assert_eq!(binary_scene.bounds(), scene.bounds());
+        assert_eq!(binary_scene.view_box(), scene.view_box());
+
+        let json_scene = Scene::read_json(&mut json.as_bytes()).unwrap();
+        assert_eq!(to_json(&json_scene), json);
+    }
+
+    #[test]
+    fn test_shared_images() {
+        // Two patterns share the image of the pattern of `scene()`.
+        let mut scene = scene();
+        let mut pattern = match scene.paints[1] {
+            Paint::Pattern(ref pattern) => pattern.clone(),
+            _ => unreachable!(),
+        };
+        pattern.transform = Transform2DF::from_scale(Vector2F::splat(2.0));
+        scene.push_paint(&Paint::Pattern(pattern));
+
+        let json = to_json(&scene);
+        assert_eq!(json.matches("\"pixels\"").count(), 1);
+        let mut binary = vec![];
+        scene.write_binary(&mut binary).unwrap();
+
+        for loaded_scene in &[Scene::read_json(&mut json.as_bytes()).unwrap(),
+                              Scene::read_binary(&mut &binary[..]).unwrap()] {
+            match (&loaded_scene.paints[1], &loaded_scene.paints[2]) {
+                (Paint::Pattern(a), Paint::Pattern(b)) => assert_eq!(a.image, b.image),
+                _ => panic!("expected two patterns"),
+            }
+        }
+
+        let json = json.replace("\"image\":0", "\"image\":1");
+        match Scene::read_json(&mut json.as_bytes()) {
+            Err(SceneFileError::Invalid(_)) => {}
+            _ => panic!("expected a dangling image index to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_reject_bad_files() {
+        let mut binary = vec![];
+        scene().write_binary(&mut binary).unwrap();
+
+        let mut bad_magic = binary.clone();
+        bad_magic[0] = b'X';
+        match Scene::read_binary(&mut &bad_magic[..]) {
+            Err(SceneFileError::NotASceneFile) => {}
+            _ => panic!("expected a bad magic number to be rejected"),
+        }
+
+        let mut bad_version = binary.clone();
+        bad_version[4] = 99;
+        match Scene::read_binary(&mut &bad_version[..]) {
+            Err(SceneFileError::UnsupportedVersion(99)) => {}
+            _ => panic!("expected an unknown version to be rejected"),
+        }
+
+        let truncated = &binary[..binary.len() - 1];
+        assert!(Scene::read_binary(&mut &truncated[..]).is_err());
+
+        let json = to_json(&scene()).replace("\"paint\":1", "\"paint\":7");
+        match Scene::read_json(&mut json.as_bytes()) {
+            Err(SceneFileError::Invalid(_)) => {}
+            _ => panic!("expected a dangling paint ID to be rejected"),
+        }
+
+        // Contours that end with a control point, start with one, or have a lone second one.
+        for bad_flags in &["[0,1,0,1]", "[1,0,0,0]", "[0,2,0,0]"] {
+            let json = to_json(&scene()).replace("[0,1,0,0]", bad_flags);
+            match Scene::read_json(&mut json.as_bytes()) {
+                Err(SceneFileError::Malformed(_)) => {}
+                _ => panic!("expected a malformed contour to be rejected"),
+            }
+        }
+    }
+}
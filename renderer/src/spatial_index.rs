@@ -0,0 +1,306 @@
+// pathfinder/renderer/src/spatial_index.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Finds the paths of a scene that intersect a rectangle without looking at all of them.
+//!
+//! This is a hierarchical grid. Each level has square cells twice the size of the level below,
+//! and every path lives in the cell that contains the upper left corner of its bounds on the
+//! lowest level whose cells are at least as big as the path. A path therefore overlaps at most
+//! the 2x2 cells to the lower right of its own, and adding or resizing a path only touches one
+//! cell.
+//!
+//! Cells refer to paths by slots, which stay the same when paths are removed or reordered, so
+//! removing a path only touches its own cell and moving one touches none. The path indices of the
+//! slots are worked out again by the first query after such edits, once for all of them.
+
+use hashbrown::HashMap;
+use pathfinder_geometry::basic::rect::RectF;
+use std::sync::Mutex;
+
+// Cells are 2^MIN_LEVEL to 2^MAX_LEVEL units across.
+const MIN_LEVEL: i32 = -8;
+const MAX_LEVEL: i32 = 64;
+
+#[derive(Default)]
+pub(crate) struct SpatialIndex {
+    // Indexed by slot. The slots of removed paths are empty until new paths take them.
+    entries: Vec<Option<Entry>>,
+    free_slots: Vec<u32>,
+    // The slot of each path.
+    path_slots: Vec<u32>,
+    // The path index of each slot, or `None` if paths have been removed or moved since it was
+    // last worked out.
+    slot_paths: Mutex<Option<Vec<u32>>>,
+    // Sorted by level number.
+    levels: Vec<Level>,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    bounds: RectF,
+    level: i32,
+    cell: (i32, i32),
+}
+
+#[derive(Clone)]
+struct Level {
+    level: i32,
+    cells: HashMap<(i32, i32), Vec<u32>>,
+}
+
+impl SpatialIndex {
+    /// Adds a path with the next index.
+    pub(crate) fn push(&mut self, bounds: RectF) {
+        let index = self.path_slots.len() as u32;
+        let entry = Entry::new(bounds);
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.entries[slot as usize] = Some(entry);
+                slot
+            }
+            None => {
+                self.entries.push(Some(entry));
+                self.entries.len() as u32 - 1
+            }
+        };
+        self.level_mut(entry.level).cells.entry(entry.cell).or_default().push(slot);
+        self.path_slots.push(slot);
+
+        if let Some(ref mut slot_paths) = *self.slot_paths.get_mut().unwrap() {
+            slot_paths.resize(self.entries.len(), 0);
+            slot_paths[slot as usize] = index;
+        }
+    }
+
+    /// Moves a path to the cell that its new bounds belong in.
+    pub(crate) fn replace(&mut self, index: u32, bounds: RectF) {
+        let slot = self.path_slots[index as usize];
+        let new_entry = Entry::new(bounds);
+        let old_entry = self.entries[slot as usize].replace(new_entry).unwrap();
+        if old_entry.level == new_entry.level && old_entry.cell == new_entry.cell {
+            return;
+        }
+
+        self.remove_from_cell(old_entry, slot);
+        self.level_mut(new_entry.level).cells.entry(new_entry.cell).or_default().push(slot);
+    }
+
+    /// Removes a path. The paths above it move down by one.
+    pub(crate) fn remove(&mut self, index: u32) {
+        let slot = self.path_slots.remove(index as usize);
+        let entry = self.entries[slot as usize].take().unwrap();
+        self.remove_from_cell(entry, slot);
+        self.free_slots.push(slot);
+
+        // The other paths only keep their indices if this one was on top.
+        if index as usize != self.path_slots.len() {
+            *self.slot_paths.get_mut().unwrap() = None;
+        }
+    }
+
+    /// Moves the path at index `from` to index `to`, shifting the paths in between by one.
+    pub(crate) fn move_path(&mut self, from: u32, to: u32) {
+        if from < to {
+            self.path_slots[(from as usize)..=(to as usize)].rotate_left(1);
+        } else {
+            self.path_slots[(to as usize)..=(from as usize)].rotate_right(1);
+        }
+        *self.slot_paths.get_mut().unwrap() = None;
+    }
+
+    /// Returns the indices of the paths whose bounds touch the rectangle, in increasing order.
+    /// Bounds that only share an edge with the rectangle count, so that a point can be looked up
+    /// with an empty rectangle.
+    pub(crate) fn query(&self, rect: RectF) -> Vec<u32> {
+        let mut slots = vec![];
+        for level in &self.levels {
+            // The paths that can reach the rectangle live in its cells or those just above and to
+            // the left of them.
+            let cell_size = cell_size(level.level);
+            let (min_x, min_y) = cell_coords(rect.min_x(), rect.min_y(), cell_size);
+            let (max_x, max_y) = cell_coords(rect.max_x(), rect.max_y(), cell_size);
+            let (min_x, min_y) = (min_x.saturating_sub(1), min_y.saturating_sub(1));
+
+            // If the rectangle spans more cells than are occupied, visit the occupied cells
+            // instead.
+            let cell_count = (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1);
+            if cell_count > level.cells.len() as i64 {
+                for (&(x, y), cell) in &level.cells {
+                    if x >= min_x && x <= max_x && y >= min_y && y <= max_y {
+                        self.collect(cell, rect, &mut slots);
+                    }
+                }
+            } else {
+                for y in min_y..=max_y {
+                    for x in min_x..=max_x {
+                        if let Some(cell) = level.cells.get(&(x, y)) {
+                            self.collect(cell, rect, &mut slots);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut slot_paths = self.slot_paths.lock().unwrap();
+        let slot_paths = slot_paths.get_or_insert_with(|| {
+            let mut slot_paths = vec![0; self.entries.len()];
+            for (index, &slot) in self.path_slots.iter().enumerate() {
+                slot_paths[slot as usize] = index as u32;
+            }
+            slot_paths
+        });
+        let mut indices: Vec<u32> = slots.into_iter().map(|slot| {
+            slot_paths[slot as usize]
+        }).collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    fn collect(&self, cell: &[u32], rect: RectF, slots: &mut Vec<u32>) {
+        slots.extend(cell.iter().filter(|&&slot| {
+            let bounds = self.entries[slot as usize].unwrap().bounds;
+            bounds.min_x() <= rect.max_x() && rect.min_x() <= bounds.max_x() &&
+                bounds.min_y() <= rect.max_y() && rect.min_y() <= bounds.max_y()
+        }));
+    }
+
+    fn remove_from_cell(&mut self, entry: Entry, slot: u32) {
+        let level = self.level_mut(entry.level);
+        let cell_is_empty = {
+            let cell = level.cells.get_mut(&entry.cell).unwrap();
+            cell.retain(|&other_slot| other_slot != slot);
+            cell.is_empty()
+        };
+        if cell_is_empty {
+            level.cells.remove(&entry.cell);
+        }
+    }
+
+    fn level_mut(&mut self, level: i32) -> &mut Level {
+        let position = match self.levels.binary_search_by_key(&level, |other| other.level) {
+            Ok(position) => position,
+            Err(position) => {
+                self.levels.insert(position, Level { level, cells: HashMap::new() });
+                position
+            }
+        };
+        &mut self.levels[position]
+    }
+}
+
+impl Clone for SpatialIndex {
+    fn clone(&self) -> SpatialIndex {
+        SpatialIndex {
+            entries: self.entries.clone(),
+            free_slots: self.free_slots.clone(),
+            path_slots: self.path_slots.clone(),
+            slot_paths: Mutex::new(self.slot_paths.lock().unwrap().clone()),
+            levels: self.levels.clone(),
+        }
+    }
+}
+
+impl Entry {
+    fn new(bounds: RectF) -> Entry {
+        let size = f32::max(bounds.size().x(), bounds.size().y());
+        let level = if size > 0.0 {
+            f32::max(f32::ceil(f32::log2(size)), MIN_LEVEL as f32) as i32
+        } else {
+            MIN_LEVEL
+        };
+        let level = i32::min(level, MAX_LEVEL);
+        let cell = cell_coords(bounds.min_x(), bounds.min_y(), cell_size(level));
+        Entry { bounds, level, cell }
+    }
+}
+
+#[inline]
+fn cell_size(level: i32) -> f32 {
+    f32::powi(2.0, level)
+}
+
+// Float to integer casts saturate, so far away paths land in the outermost cells.
+#[inline]
+fn cell_coords(x: f32, y: f32, cell_size: f32) -> (i32, i32) {
+    (f32::floor(x / cell_size) as i32, f32::floor(y / cell_size) as i32)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::spatial_index::SpatialIndex;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::vector::Vector2F;
+
+    fn rect((x, y, width, height): (i16, i16, u16, u16)) -> RectF {
+        RectF::new(Vector2F::new(x as f32 * 0.25, y as f32 * 0.25),
+                   Vector2F::new(width as f32 * 0.125, height as f32 * 0.125))
+    }
+
+    fn touches(a: RectF, b: RectF) -> bool {
+        a.min_x() <= b.max_x() && b.min_x() <= a.max_x() &&
+            a.min_y() <= b.max_y() && b.min_y() <= a.max_y()
+    }
+
+    fn linear_scan(bounds: &[RectF], query: RectF) -> Vec<u32> {
+        (0..bounds.len() as u32).filter(|&path_index| {
+            touches(bounds[path_index as usize], query)
+        }).collect()
+    }
+
+    #[test]
+    fn test_query_matches_linear_scan() {
+        quickcheck::quickcheck(prop_query as fn(Vec<_>, Vec<_>, Vec<_>, Vec<_>, Vec<_>, _) -> bool);
+
+        fn prop_query(paths: Vec<(i16, i16, u16, u16)>,
+                      replacements: Vec<(usize, (i16, i16, u16, u16))>,
+                      moves: Vec<(usize, usize)>,
+                      removals: Vec<usize>,
+                      additions: Vec<(i16, i16, u16, u16)>,
+                      query: (i16, i16, u16, u16))
+                      -> bool {
+            let query = rect(query);
+            let mut bounds: Vec<RectF> = paths.into_iter().map(rect).collect();
+            let mut index = SpatialIndex::default();
+            for &path_bounds in &bounds {
+                index.push(path_bounds);
+            }
+            if !bounds.is_empty() {
+                for (path_index, path_bounds) in replacements {
+                    let path_index = path_index % bounds.len();
+                    bounds[path_index] = rect(path_bounds);
+                    index.replace(path_index as u32, bounds[path_index]);
+                }
+                for (from, to) in moves {
+                    let (from, to) = (from % bounds.len(), to % bounds.len());
+                    let path_bounds = bounds.remove(from);
+                    bounds.insert(to, path_bounds);
+                    index.move_path(from as u32, to as u32);
+                }
+            }
+            for path_index in removals {
+                if bounds.is_empty() {
+                    break;
+                }
+                let path_index = path_index % bounds.len();
+                bounds.remove(path_index);
+                index.remove(path_index as u32);
+            }
+            assert_eq!(index.query(query), linear_scan(&bounds, query));
+
+            // New paths take the slots of the removed ones.
+            for path_bounds in additions {
+                bounds.push(rect(path_bounds));
+                index.push(rect(path_bounds));
+            }
+            assert_eq!(index.query(query), linear_scan(&bounds, query));
+            true
+        }
+    }
+}
@@ -0,0 +1,575 @@
+// pathfinder/renderer/src/svg_export.rs
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Writes scenes out as SVG.
+//!
+//! Scenes made of solid fills, and layers that only have an opacity, survive a round trip through
+//! `pathfinder_svg`: names of the form `Fill(id)` and `Group(id)` are written as the `id` that
+//! `pathfinder_svg` derived them from, and coordinates and colors are written exactly.
+
+use crate::paint::{BlendMode, Gradient, GradientGeometry, Paint, Pattern, SpreadMode};
+use crate::scene::{ClipPathId, Scene};
+use hashbrown::HashSet;
+use image::ColorType;
+use image::png::PNGEncoder;
+use pathfinder_geometry::basic::rect::RectF;
+use pathfinder_geometry::basic::transform2d::Transform2DF;
+use pathfinder_geometry::basic::vector::Vector2F;
+use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
+use pathfinder_geometry::outline::Outline;
+use pathfinder_geometry::segment::SegmentKind;
+use std::io::{self, Write};
+
+impl Scene {
+    /// Writes the scene as an SVG document whose view box is that of the scene.
+    ///
+    /// Not everything has an SVG equivalent:
+    ///
+    /// * Conic gradients are written as the color at their start.
+    ///
+    /// * The Porter-Duff operators other than `SourceOver` and `Lighter`, which CSS
+    ///   `mix-blend-mode` doesn't have, are written as `SourceOver`.
+    ///
+    /// * Patterns are written as an `<image>` holding a PNG copy of their image, which reads back
+    ///   as a new image rather than the one the scene shares with other scenes.
+    pub fn write_svg<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        let view_box = self.view_box();
+        write!(writer, "<svg xmlns=\"http://www.w3.org/2000/svg\"")?;
+        if self.paints.iter().any(|paint| matches!(*paint, Paint::Pattern(_))) {
+            write!(writer, " xmlns:xlink=\"http://www.w3.org/1999/xlink\"")?;
+        }
+        writeln!(writer,
+                 " viewBox=\"{} {} {} {}\">",
+                 view_box.origin().x(),
+                 view_box.origin().y(),
+                 view_box.size().x(),
+                 view_box.size().y())?;
+        self.write_svg_defs(writer)?;
+
+        let mut ids = HashSet::new();
+        let mut open_layers = vec![];
+        for (path_index, path_object) in self.paths.iter().enumerate() {
+            let path_index = path_index as u32;
+            self.close_svg_layers(writer, &mut open_layers, path_index)?;
+            for (layer_index, layer) in self.layers.iter().enumerate() {
+                let path_range = layer.path_range();
+                if path_range.start != path_index || path_range.end == path_index {
+                    continue;
+                }
+                write_indent(writer, open_layers.len())?;
+                write!(writer, "<g")?;
+                write_id(writer, layer.name(), &mut ids)?;
+                if layer.opacity() != 1.0 {
+                    write!(writer, " opacity=\"{}\"", layer.opacity())?;
+                }
+                write_clip_path(writer, layer.clip_path())?;
+                write_blend_mode(writer, layer.blend_mode())?;
+                writeln!(writer, ">")?;
+                open_layers.push(layer_index);
+            }
+
+            write_indent(writer, open_layers.len())?;
+            write!(writer, "<path")?;
+            write_id(writer, path_object.name(), &mut ids)?;
+            let paint_id = path_object.paint();
+            match self.paints[paint_id.0 as usize] {
+                Paint::Color(color) => write_color(writer, "fill", "fill-opacity", color)?,
+                Paint::Gradient(ref gradient) if has_svg_equivalent(gradient) => {
+                    write!(writer, " fill=\"url(#paint{})\"", paint_id.0)?
+                }
+                // SVG has no conic gradients, so these get the color at the start.
+                Paint::Gradient(ref gradient) => {
+                    write_color(writer, "fill", "fill-opacity", gradient.sample(0.0))?
+                }
+                Paint::Pattern(_) => write!(writer, " fill=\"url(#paint{})\"", paint_id.0)?,
+            }
+            write_fill_rule(writer, "fill-rule", path_object.fill_rule())?;
+            write_clip_path(writer, path_object.clip_path())?;
+            write_blend_mode(writer, path_object.blend_mode())?;
+            write!(writer, " d=\"")?;
+            write_path_data(writer, path_object.outline())?;
+            writeln!(writer, "\"/>")?;
+        }
+        self.close_svg_layers(writer, &mut open_layers, self.paths.len() as u32)?;
+
+        writeln!(writer, "</svg>")?;
+        Ok(())
+    }
+
+    fn write_svg_defs<W>(&self, writer: &mut W) -> io::Result<()> where W: Write {
+        let has_paint_defs = self.paints.iter().any(|paint| {
+            match *paint {
+                Paint::Gradient(ref gradient) => has_svg_equivalent(gradient),
+                Paint::Pattern(_) => true,
+                Paint::Color(_) => false,
+            }
+        });
+        if !has_paint_defs && self.clip_paths.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(writer, "    <defs>")?;
+        for (paint_index, paint) in self.paints.iter().enumerate() {
+            match *paint {
+                Paint::Gradient(ref gradient) if has_svg_equivalent(gradient) => {
+                    write_gradient(writer, paint_index, gradient)?
+                }
+                Paint::Pattern(ref pattern) => {
+                    let bounds = self.pattern_bounds(paint_index, pattern);
+                    write_pattern(writer, paint_index, pattern, bounds)?
+                }
+                Paint::Color(_) | Paint::Gradient(_) => {}
+            }
+        }
+        for (clip_path_index, clip_path) in self.clip_paths.iter().enumerate() {
+            write!(writer, "        <clipPath id=\"clip{}\"", clip_path_index)?;
+            write_clip_path(writer, clip_path.clip_path())?;
+            write!(writer, "><path")?;
+            write_fill_rule(writer, "clip-rule", clip_path.fill_rule())?;
+            write!(writer, " d=\"")?;
+            write_path_data(writer, clip_path.outline())?;
+            writeln!(writer, "\"/></clipPath>")?;
+        }
+        writeln!(writer, "    </defs>")
+    }
+
+    // Returns the area in pattern space that the paths filled with the given pattern cover,
+    // together with the image.
+    fn pattern_bounds(&self, paint_index: usize, pattern: &Pattern) -> RectF {
+        let inverse_transform = pattern.transform.inverse();
+        let image_rect = RectF::new(Vector2F::default(), pattern.image.size().to_f32());
+        self.paths.iter().filter(|path| path.paint().0 as usize == paint_index).fold(
+            image_rect,
+            |bounds, path| {
+                bounds.union_rect(inverse_transform.transform_rect(&path.outline().bounds()))
+            })
+    }
+
+    // Closes the layers that end before the given path.
+    fn close_svg_layers<W>(&self, writer: &mut W, open_layers: &mut Vec<usize>, path_index: u32)
+                           -> io::Result<()>
+                           where W: Write {
+        while let Some(&layer_index) = open_layers.last() {
+            if self.layers[layer_index].path_range().end > path_index {
+                break;
+            }
+            open_layers.pop();
+            write_indent(writer, open_layers.len())?;
+            writeln!(writer, "</g>")?;
+        }
+        Ok(())
+    }
+}
+
+fn has_svg_equivalent(gradient: &Gradient) -> bool {
+    match gradient.geometry {
+        GradientGeometry::Linear(_) | GradientGeometry::Radial { .. } => true,
+        GradientGeometry::Conic { .. } => false,
+    }
+}
+
+fn write_gradient<W>(writer: &mut W, paint_index: usize, gradient: &Gradient) -> io::Result<()>
+                     where W: Write {
+    match gradient.geometry {
+        GradientGeometry::Linear(line) => {
+            write!(writer,
+                   "        <linearGradient id=\"paint{}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"",
+                   paint_index,
+                   line.from_x(),
+                   line.from_y(),
+                   line.to_x(),
+                   line.to_y())?;
+        }
+        GradientGeometry::Radial { line, start_radius, end_radius } => {
+            write!(writer,
+                   "        <radialGradient id=\"paint{}\" fx=\"{}\" fy=\"{}\" cx=\"{}\" cy=\"{}\" \
+                    r=\"{}\"",
+                   paint_index,
+                   line.from_x(),
+                   line.from_y(),
+                   line.to_x(),
+                   line.to_y(),
+                   end_radius)?;
+            if start_radius != 0.0 {
+                write!(writer, " fr=\"{}\"", start_radius)?;
+            }
+        }
+        GradientGeometry::Conic { .. } => unreachable!(),
+    }
+
+    write!(writer, " gradientUnits=\"userSpaceOnUse\"")?;
+    match gradient.spread {
+        SpreadMode::Pad => {}
+        SpreadMode::Reflect => write!(writer, " spreadMethod=\"reflect\"")?,
+        SpreadMode::Repeat => write!(writer, " spreadMethod=\"repeat\"")?,
+    }
+    write_transform(writer, "gradientTransform", &gradient.transform)?;
+    writeln!(writer, ">")?;
+
+    for stop in gradient.stops() {
+        write!(writer, "            <stop offset=\"{}\"", stop.offset)?;
+        write_color(writer, "stop-color", "stop-opacity", stop.color)?;
+        writeln!(writer, "/>")?;
+    }
+
+    match gradient.geometry {
+        GradientGeometry::Linear(_) => writeln!(writer, "        </linearGradient>"),
+        GradientGeometry::Radial { .. } => writeln!(writer, "        </radialGradient>"),
+        GradientGeometry::Conic { .. } => unreachable!(),
+    }
+}
+
+// SVG patterns always repeat, so along an axis that doesn't, the tile is made big enough to hold
+// the whole of `bounds`, so that only one copy of the image is visible.
+fn write_pattern<W>(writer: &mut W, paint_index: usize, pattern: &Pattern, bounds: RectF)
+                    -> io::Result<()>
+                    where W: Write {
+    let image_size = pattern.image.size();
+    let (x, width) = if pattern.repeat_x {
+        (0.0, image_size.x() as f32)
+    } else {
+        let x = f32::min(f32::floor(bounds.min_x()), 0.0);
+        (x, f32::max(f32::ceil(bounds.max_x()), image_size.x() as f32) - x)
+    };
+    let (y, height) = if pattern.repeat_y {
+        (0.0, image_size.y() as f32)
+    } else {
+        let y = f32::min(f32::floor(bounds.min_y()), 0.0);
+        (y, f32::max(f32::ceil(bounds.max_y()), image_size.y() as f32) - y)
+    };
+
+    write!(writer,
+           "        <pattern id=\"paint{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+            patternUnits=\"userSpaceOnUse\"",
+           paint_index,
+           x,
+           y,
+           width,
+           height)?;
+    write_transform(writer, "patternTransform", &pattern.transform)?;
+    // The contents of a tile are placed relative to its upper left corner. (Subtracting from zero
+    // keeps a zero offset from being written as `-0`.)
+    write!(writer,
+           "><image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+           0.0 - x,
+           0.0 - y,
+           image_size.x(),
+           image_size.y())?;
+    if pattern.opacity != 1.0 {
+        write!(writer, " opacity=\"{}\"", pattern.opacity)?;
+    }
+    write!(writer, " xlink:href=\"data:image/png;base64,")?;
+
+    let mut png = vec![];
+    let pixels: Vec<u8> = pattern.image.pixels().iter().flat_map(|pixel| {
+        vec![pixel.r, pixel.g, pixel.b, pixel.a]
+    }).collect();
+    PNGEncoder::new(&mut png).encode(&pixels,
+                                     image_size.x() as u32,
+                                     image_size.y() as u32,
+                                     ColorType::RGBA(8))?;
+    write!(writer, "{}", base64::encode(&png))?;
+    writeln!(writer, "\"/></pattern>")
+}
+
+// Writes the path data of an outline. Floats are written with as many digits as it takes to read
+// them back exactly.
+fn write_path_data<W>(writer: &mut W, outline: &Outline) -> io::Result<()> where W: Write {
+    for (contour_index, contour) in outline.contours().iter().enumerate() {
+        if contour.is_empty() {
+            continue;
+        }
+        if contour_index > 0 {
+            write!(writer, " ")?;
+        }
+
+        let mut segments: Vec<_> = contour.iter().collect();
+        // The last segment of a closed contour is the line back to the start, which `Z` draws.
+        if contour.is_closed() {
+            segments.pop();
+        }
+
+        let start = contour.position_of(0);
+        write!(writer, "M {} {}", start.x(), start.y())?;
+        for segment in segments {
+            let (ctrl, to) = (segment.ctrl, segment.baseline.to());
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => write!(writer, " L {} {}", to.x(), to.y())?,
+                SegmentKind::Quadratic => {
+                    write!(writer, " Q {} {} {} {}", ctrl.from_x(), ctrl.from_y(), to.x(), to.y())?
+                }
+                SegmentKind::Cubic => {
+                    write!(writer,
+                           " C {} {} {} {} {} {}",
+                           ctrl.from_x(),
+                           ctrl.from_y(),
+                           ctrl.to_x(),
+                           ctrl.to_y(),
+                           to.x(),
+                           to.y())?
+                }
+            }
+        }
+        if contour.is_closed() {
+            write!(writer, " Z")?;
+        }
+    }
+    Ok(())
+}
+
+// Writes a color, with its alpha as a separate opacity attribute.
+fn write_color<W>(writer: &mut W, attribute: &str, opacity_attribute: &str, color: ColorU)
+                  -> io::Result<()>
+                  where W: Write {
+    write!(writer, " {}=\"#{:02x}{:02x}{:02x}\"", attribute, color.r, color.g, color.b)?;
+    if color.a != 255 {
+        write!(writer, " {}=\"{}\"", opacity_attribute, color.a as f32 / 255.0)?;
+    }
+    Ok(())
+}
+
+fn write_fill_rule<W>(writer: &mut W, attribute: &str, fill_rule: FillRule) -> io::Result<()>
+                      where W: Write {
+    match fill_rule {
+        FillRule::Winding => Ok(()),
+        FillRule::EvenOdd => write!(writer, " {}=\"evenodd\"", attribute),
+    }
+}
+
+fn write_clip_path<W>(writer: &mut W, clip_path: Option<ClipPathId>) -> io::Result<()>
+                      where W: Write {
+    match clip_path {
+        None => Ok(()),
+        Some(ClipPathId(clip_path_index)) => {
+            write!(writer, " clip-path=\"url(#clip{})\"", clip_path_index)
+        }
+    }
+}
+
+// Blend modes that CSS has no equivalent for are left out.
+fn write_blend_mode<W>(writer: &mut W, blend_mode: BlendMode) -> io::Result<()> where W: Write {
+    let css_blend_mode = match blend_mode {
+        BlendMode::Lighter => "plus-lighter",
+        BlendMode::Multiply => "multiply",
+        BlendMode::Screen => "screen",
+        BlendMode::Overlay => "overlay",
+        BlendMode::Darken => "darken",
+        BlendMode::Lighten => "lighten",
+        BlendMode::ColorDodge => "color-dodge",
+        BlendMode::ColorBurn => "color-burn",
+        BlendMode::HardLight => "hard-light",
+        BlendMode::SoftLight => "soft-light",
+        BlendMode::Difference => "difference",
+        BlendMode::Exclusion => "exclusion",
+        BlendMode::Hue => "hue",
+        BlendMode::Saturation => "saturation",
+        BlendMode::Color => "color",
+        BlendMode::Luminosity => "luminosity",
+        BlendMode::SourceOver | BlendMode::SourceIn | BlendMode::SourceOut |
+        BlendMode::SourceAtop | BlendMode::DestinationOver | BlendMode::DestinationIn |
+        BlendMode::DestinationOut | BlendMode::DestinationAtop | BlendMode::Xor |
+        BlendMode::Copy => return Ok(()),
+    };
+    write!(writer, " style=\"mix-blend-mode: {}\"", css_blend_mode)
+}
+
+fn write_transform<W>(writer: &mut W, attribute: &str, transform: &Transform2DF)
+                      -> io::Result<()>
+                      where W: Write {
+    if transform.is_identity() {
+        return Ok(());
+    }
+    let translation = transform.translation();
+    write!(writer,
+           " {}=\"matrix({} {} {} {} {} {})\"",
+           attribute,
+           transform.m11(),
+           transform.m21(),
+           transform.m12(),
+           transform.m22(),
+           translation.x(),
+           translation.y())
+}
+
+// Writes the ID that `pathfinder_svg` derived the name from, if any. IDs have to be unique, so a
+// name that's already been used is left out.
+fn write_id<W>(writer: &mut W, name: &str, ids: &mut HashSet<String>) -> io::Result<()>
+               where W: Write {
+    let id = ["Fill(", "Group("].iter().filter_map(|prefix| {
+        if name.starts_with(prefix) && name.ends_with(')') {
+            Some(&name[prefix.len()..(name.len() - 1)])
+        } else {
+            None
+        }
+    }).next().unwrap_or(name);
+
+    if id.is_empty() || !ids.insert(id.to_owned()) {
+        return Ok(());
+    }
+    write!(writer, " id=\"")?;
+    for character in id.chars() {
+        match character {
+            '&' => write!(writer, "&amp;")?,
+            '<' => write!(writer, "&lt;")?,
+            '>' => write!(writer, "&gt;")?,
+            '"' => write!(writer, "&quot;")?,
+            '\'' => write!(writer, "&apos;")?,
+            _ => write!(writer, "{}", character)?,
+        }
+    }
+    write!(writer, "\"")
+}
+
+fn write_indent<W>(writer: &mut W, depth: usize) -> io::Result<()> where W: Write {
+    write!(writer, "    ")?;
+    for _ in 0..depth {
+        write!(writer, "    ")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::paint::{BlendMode, ColorStop, Gradient, Image, Paint, Pattern};
+    use crate::scene::{ClipPath, Layer, PathObject, Scene};
+    use image::ImageFormat;
+    use pathfinder_geometry::basic::line_segment::LineSegment2F;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::transform2d::Transform2DF;
+    use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use std::sync::Arc;
+
+    fn triangle(scale: f32) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.1, 0.2));
+        contour.push_endpoint(Vector2F::new(scale, 0.0));
+        contour.push_cubic(Vector2F::new(scale, scale / 3.0),
+                           Vector2F::new(scale / 3.0, scale),
+                           Vector2F::splat(scale));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn svg(scene: &Scene) -> String {
+        let mut bytes = vec![];
+        scene.write_svg(&mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_write_paths() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 255, g: 0, b: 128, a: 51 }));
+        scene.push_path(PathObject::new(triangle(10.0), paint, "Fill(a<\"b\">&c)".to_owned()));
+        let mut path = PathObject::new(triangle(30.0), paint, "Stroke(d)".to_owned());
+        path.set_fill_rule(FillRule::EvenOdd);
+        path.set_blend_mode(BlendMode::Multiply);
+        scene.push_path(path);
+        scene.push_path(PathObject::new(triangle(20.0), paint, "Stroke(d)".to_owned()));
+
+        assert_eq!(svg(&scene), "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 100\">
+    <path id=\"a&lt;&quot;b&quot;&gt;&amp;c\" fill=\"#ff0080\" fill-opacity=\"0.2\" \
+d=\"M 0.1 0.2 L 10 0 C 10 3.3333333 3.3333333 10 10 10 Z\"/>
+    <path id=\"Stroke(d)\" fill=\"#ff0080\" fill-opacity=\"0.2\" fill-rule=\"evenodd\" \
+style=\"mix-blend-mode: multiply\" d=\"M 0.1 0.2 L 30 0 C 30 10 10 30 30 30 Z\"/>
+    <path fill=\"#ff0080\" fill-opacity=\"0.2\" \
+d=\"M 0.1 0.2 L 20 0 C 20 6.6666665 6.6666665 20 20 20 Z\"/>
+</svg>
+");
+    }
+
+    #[test]
+    fn test_write_layers_clips_and_gradients() {
+        let mut scene = Scene::new();
+        let clip_path = scene.push_clip_path(ClipPath::new(triangle(8.0), String::new()));
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::default(),
+                                                               Vector2F::new(8.0, 0.0)));
+        gradient.add_color_stop(ColorStop { offset: 0.0, color: ColorU::black() });
+        gradient.add_color_stop(ColorStop {
+            offset: 1.0,
+            color: ColorU { r: 255, g: 255, b: 255, a: 0 },
+        });
+        let paint = scene.push_paint(&Paint::Gradient(gradient));
+
+        let mut layer = Layer::new("Group(g)".to_owned());
+        layer.set_opacity(0.5);
+        layer.set_clip_path(Some(clip_path));
+        scene.push_layer(layer);
+        scene.push_path(PathObject::new(triangle(4.0), paint, String::new()));
+        scene.pop_layer();
+
+        assert_eq!(svg(&scene), "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 0 0\">
+    <defs>
+        <linearGradient id=\"paint0\" x1=\"0\" y1=\"0\" x2=\"8\" y2=\"0\" \
+gradientUnits=\"userSpaceOnUse\">
+            <stop offset=\"0\" stop-color=\"#000000\"/>
+            <stop offset=\"1\" stop-color=\"#ffffff\" stop-opacity=\"0\"/>
+        </linearGradient>
+        <clipPath id=\"clip0\"><path \
+d=\"M 0.1 0.2 L 8 0 C 8 2.6666667 2.6666667 8 8 8 Z\"/></clipPath>
+    </defs>
+    <g id=\"g\" opacity=\"0.5\" clip-path=\"url(#clip0)\">
+        <path fill=\"url(#paint0)\" d=\"M 0.1 0.2 L 4 0 C 4 1.3333334 1.3333334 4 4 4 Z\"/>
+    </g>
+</svg>
+");
+    }
+
+    #[test]
+    fn test_write_pattern() {
+        let mut scene = Scene::new();
+        let pixels = vec![ColorU { r: 255, g: 0, b: 128, a: 51 }, ColorU::black()];
+        let image = Image::new(Vector2I::new(2, 1), Arc::new(pixels.clone()));
+        let paint = scene.push_paint(&Paint::Pattern(Pattern {
+            image,
+            transform: Transform2DF::from_scale(Vector2F::splat(2.0)),
+            repeat_x: true,
+            repeat_y: false,
+            opacity: 1.0,
+        }));
+        scene.push_path(PathObject::new(triangle(8.0), paint, String::new()));
+
+        // The tile is as tall as the triangle in pattern space, so the image doesn't repeat down.
+        let svg = svg(&scene);
+        let prefix = "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+viewBox=\"0 0 0 0\">
+    <defs>
+        <pattern id=\"paint0\" x=\"0\" y=\"0\" width=\"2\" height=\"4\" \
+patternUnits=\"userSpaceOnUse\" patternTransform=\"matrix(2 0 0 2 0 0)\"><image x=\"0\" \
+y=\"0\" width=\"2\" height=\"1\" xlink:href=\"data:image/png;base64,";
+        let suffix = "\
+\"/></pattern>
+    </defs>
+    <path fill=\"url(#paint0)\" d=\"M 0.1 0.2 L 8 0 C 8 2.6666667 2.6666667 8 8 8 Z\"/>
+</svg>
+";
+        assert!(svg.starts_with(prefix) && svg.ends_with(suffix));
+
+        let png = base64::decode(&svg[prefix.len()..(svg.len() - suffix.len())]).unwrap();
+        let image = image::load_from_memory_with_format(&png, ImageFormat::PNG).unwrap();
+        let image = image.to_rgba();
+        assert_eq!(image.dimensions(), (2, 1));
+        let expected_pixels: Vec<u8> = pixels.iter().flat_map(|pixel| {
+            vec![pixel.r, pixel.g, pixel.b, pixel.a]
+        }).collect();
+        assert_eq!(image.into_raw(), expected_pixels);
+    }
+}
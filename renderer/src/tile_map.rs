@@ -11,7 +11,7 @@
 use pathfinder_geometry::basic::vector::Vector2I;
 use pathfinder_geometry::basic::rect::RectI;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct DenseTileMap<T> {
     pub data: Vec<T>,
     pub rect: RectI,
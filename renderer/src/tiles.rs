@@ -8,13 +8,18 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::builder::SceneBuilder;
+use crate::clip::ClipTile;
+use crate::concurrent::executor::Executor;
 use crate::gpu_data::{AlphaTileBatchPrimitive, BuiltObject, TileObjectPrimitive};
-use crate::paint::{self, PaintId};
+use crate::paint::PaintId;
+use crate::scene::PathObject;
 use crate::sorted_vector::SortedVector;
+use crate::tile_map::DenseTileMap;
+use crate::z_buffer::ZBuffer;
 use pathfinder_geometry::basic::line_segment::LineSegment2F;
 use pathfinder_geometry::basic::vector::{Vector2F, Vector2I};
 use pathfinder_geometry::basic::rect::{RectF, RectI};
+use pathfinder_geometry::fill::FillRule;
 use pathfinder_geometry::outline::{Contour, Outline, PointIndex};
 use pathfinder_geometry::segment::Segment;
 use std::cmp::Ordering;
@@ -27,12 +32,9 @@ pub const TILE_WIDTH: u32 = 16;
 pub const TILE_HEIGHT: u32 = 16;
 
 pub(crate) struct Tiler<'a> {
-    builder: &'a SceneBuilder<'a>,
     outline: &'a Outline,
     pub built_object: BuiltObject,
-    paint_id: PaintId,
-    object_index: u16,
-    object_is_opaque: bool,
+    fill_rule: FillRule,
 
     point_queue: SortedVector<QueuedEndpoint>,
     active_edges: SortedVector<ActiveEdge>,
@@ -42,12 +44,9 @@ pub(crate) struct Tiler<'a> {
 impl<'a> Tiler<'a> {
     #[allow(clippy::or_fun_call)]
     pub(crate) fn new(
-        builder: &'a SceneBuilder<'a>,
         outline: &'a Outline,
         view_box: RectF,
-        object_index: u16,
-        paint_id: PaintId,
-        object_is_opaque: bool,
+        fill_rule: FillRule,
     ) -> Tiler<'a> {
         let bounds = outline
             .bounds()
@@ -56,12 +55,9 @@ impl<'a> Tiler<'a> {
         let built_object = BuiltObject::new(bounds);
 
         Tiler {
-            builder,
             outline,
             built_object,
-            object_index,
-            paint_id,
-            object_is_opaque,
+            fill_rule,
 
             point_queue: SortedVector::new(),
             active_edges: SortedVector::new(),
@@ -69,7 +65,9 @@ impl<'a> Tiler<'a> {
         }
     }
 
-    pub(crate) fn generate_tiles(&mut self) {
+    /// Generates the fills and tiles of the outline one strip of tiles at a time, stopping early
+    /// if the build is cancelled. Returns false if it stopped early.
+    pub(crate) fn generate_tiles<E>(&mut self, executor: &E) -> bool where E: Executor {
         // Initialize the point queue.
         self.init_point_queue();
 
@@ -80,14 +78,15 @@ impl<'a> Tiler<'a> {
         // Generate strips.
         let tile_rect = self.built_object.tile_rect();
         for strip_origin_y in tile_rect.min_y()..tile_rect.max_y() {
+            if executor.is_cancelled() {
+                return false;
+            }
             self.generate_strip(strip_origin_y);
         }
 
-        // Pack and cull.
-        self.pack_and_cull();
-
         // Done!
         debug!("{:#?}", self.built_object);
+        true
     }
 
     fn generate_strip(&mut self, strip_origin_y: i32) {
@@ -95,7 +94,7 @@ impl<'a> Tiler<'a> {
         self.process_old_active_edges(strip_origin_y);
 
         // Add new active edges.
-        let strip_max_y = ((i32::from(strip_origin_y) + 1) * TILE_HEIGHT as i32) as f32;
+        let strip_max_y = ((strip_origin_y + 1) * TILE_HEIGHT as i32) as f32;
         while let Some(queued_endpoint) = self.point_queue.peek() {
             // We're done when we see an endpoint that belongs to the next tile strip.
             //
@@ -109,39 +108,6 @@ impl<'a> Tiler<'a> {
         }
     }
 
-    fn pack_and_cull(&mut self) {
-        for (tile_index, tile) in self.built_object.tiles.data.iter().enumerate() {
-            let tile_coords = self
-                .built_object
-                .local_tile_index_to_coords(tile_index as u32);
-
-            if tile.is_solid() {
-                // Blank tiles are always skipped.
-                if tile.backdrop == 0 {
-                    continue;
-                }
-
-                // If this is a solid tile, poke it into the Z-buffer and stop here.
-                if self.object_is_opaque {
-                    self.builder.z_buffer.update(tile_coords, self.object_index);
-                    continue;
-                }
-            }
-
-            let origin_uv = paint::paint_id_to_tex_coords(self.paint_id);
-
-            let alpha_tile = AlphaTileBatchPrimitive::new(
-                tile_coords,
-                tile.backdrop,
-                self.object_index,
-                tile.alpha_tile_index as u16,
-                origin_uv,
-            );
-
-            self.built_object.alpha_tiles.push(alpha_tile);
-        }
-    }
-
     fn process_old_active_edges(&mut self, tile_y: i32) {
         let mut current_tile_x = self.built_object.tile_rect().min_x();
         let mut current_subtile_x = 0.0;
@@ -153,7 +119,7 @@ impl<'a> Tiler<'a> {
         // FIXME(pcwalton): Yuck.
         let mut last_segment_x = -9999.0;
 
-        let tile_top = (i32::from(tile_y) * TILE_HEIGHT as i32) as f32;
+        let tile_top = (tile_y * TILE_HEIGHT as i32) as f32;
 
         debug!("---------- tile y {}({}) ----------", tile_y, tile_top);
         debug!("old active edges: {:#?}", self.old_active_edges);
@@ -192,11 +158,10 @@ impl<'a> Tiler<'a> {
             let segment_tile_x = f32::floor(segment_x) as i32 / TILE_WIDTH as i32;
             if current_tile_x < segment_tile_x && current_subtile_x > 0.0 {
                 let current_x =
-                    (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32 + current_subtile_x;
-                let tile_right_x = ((i32::from(current_tile_x) + 1) * TILE_WIDTH as i32) as f32;
+                    (current_tile_x * TILE_WIDTH as i32) as f32 + current_subtile_x;
+                let tile_right_x = ((current_tile_x + 1) * TILE_WIDTH as i32) as f32;
                 let current_tile_coords = Vector2I::new(current_tile_x, tile_y);
                 self.built_object.add_active_fill(
-                    self.builder,
                     current_x,
                     tile_right_x,
                     current_winding,
@@ -217,9 +182,8 @@ impl<'a> Tiler<'a> {
                     .built_object
                     .tile_coords_to_local_index(current_tile_coords)
                 {
-                    // FIXME(pcwalton): Handle winding overflow.
                     self.built_object.tiles.data[tile_index as usize].backdrop =
-                        current_winding as i8;
+                        backdrop_for_winding(current_winding, self.fill_rule);
                 }
 
                 current_tile_x += 1;
@@ -229,13 +193,12 @@ impl<'a> Tiler<'a> {
             // Do final subtile fill, if necessary.
             debug_assert_eq!(current_tile_x, segment_tile_x);
             let segment_subtile_x =
-                segment_x - (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32;
+                segment_x - (current_tile_x * TILE_WIDTH as i32) as f32;
             if segment_subtile_x > current_subtile_x {
                 let current_x =
-                    (i32::from(current_tile_x) * TILE_WIDTH as i32) as f32 + current_subtile_x;
+                    (current_tile_x * TILE_WIDTH as i32) as f32 + current_subtile_x;
                 let current_tile_coords = Vector2I::new(current_tile_x, tile_y);
                 self.built_object.add_active_fill(
-                    self.builder,
                     current_x,
                     segment_x,
                     current_winding,
@@ -250,7 +213,7 @@ impl<'a> Tiler<'a> {
             // Process the edge.
             debug!("about to process existing active edge {:#?}", active_edge);
             debug_assert!(f32::abs(active_edge.crossing.y() - tile_top) < 0.1);
-            active_edge.process(self.builder, &mut self.built_object, tile_y);
+            active_edge.process(&mut self.built_object, tile_y);
             if !active_edge.segment.is_none() {
                 self.active_edges.push(active_edge);
             }
@@ -286,7 +249,6 @@ impl<'a> Tiler<'a> {
                 contour,
                 prev_endpoint_index,
                 &mut self.active_edges,
-                self.builder,
                 &mut self.built_object,
                 tile_y,
             );
@@ -310,7 +272,6 @@ impl<'a> Tiler<'a> {
                 contour,
                 point_index.point(),
                 &mut self.active_edges,
-                self.builder,
                 &mut self.built_object,
                 tile_y,
             );
@@ -363,17 +324,35 @@ pub fn round_rect_out_to_tile_bounds(rect: RectF) -> RectI {
     .to_i32()
 }
 
+// Only the parity of the winding number matters for the even-odd rule, so we reduce the backdrop
+// modulo 2. This also keeps blank even-odd tiles (backdrop 0) out of the Z-buffer.
+fn backdrop_for_winding(winding: i32, fill_rule: FillRule) -> i8 {
+    match fill_rule {
+        // FIXME(pcwalton): Handle winding overflow.
+        FillRule::Winding => winding as i8,
+        FillRule::EvenOdd => (winding & 1) as i8,
+    }
+}
+
+/// Encodes a fill rule the way the tile shaders expect.
+#[inline]
+pub(crate) fn encode_fill_rule(fill_rule: FillRule) -> u8 {
+    match fill_rule {
+        FillRule::Winding => 0,
+        FillRule::EvenOdd => 1,
+    }
+}
+
 fn process_active_segment(
     contour: &Contour,
     from_endpoint_index: u32,
     active_edges: &mut SortedVector<ActiveEdge>,
-    builder: &SceneBuilder,
     built_object: &mut BuiltObject,
     tile_y: i32,
 ) {
     let mut active_edge = ActiveEdge::from_segment(&contour.segment_after(from_endpoint_index));
     debug!("... process_active_segment({:#?})", active_edge);
-    active_edge.process(builder, built_object, tile_y);
+    active_edge.process(built_object, tile_y);
     if !active_edge.segment.is_none() {
         debug!("... ... pushing resulting active edge: {:#?}", active_edge);
         active_edges.push(active_edge);
@@ -423,8 +402,8 @@ impl ActiveEdge {
         }
     }
 
-    fn process(&mut self, builder: &SceneBuilder, built_object: &mut BuiltObject, tile_y: i32) {
-        let tile_bottom = ((i32::from(tile_y) + 1) * TILE_HEIGHT as i32) as f32;
+    fn process(&mut self, built_object: &mut BuiltObject, tile_y: i32) {
+        let tile_bottom = ((tile_y + 1) * TILE_HEIGHT as i32) as f32;
         debug!(
             "process_active_edge({:#?}, tile_y={}({}))",
             self, tile_y, tile_bottom
@@ -436,7 +415,7 @@ impl ActiveEdge {
         if segment.is_line() {
             let line_segment = segment.as_line_segment();
             self.segment =
-                match self.process_line_segment(&line_segment, builder, built_object, tile_y) {
+                match self.process_line_segment(&line_segment, built_object, tile_y) {
                     Some(lower_part) => Segment::line(&lower_part),
                     None => Segment::none(),
                 };
@@ -453,7 +432,7 @@ impl ActiveEdge {
             let first_line_segment =
                 LineSegment2F::new(self.crossing, segment.baseline.upper_point()).orient(winding);
             if self
-                .process_line_segment(&first_line_segment, builder, built_object, tile_y)
+                .process_line_segment(&first_line_segment, built_object, tile_y)
                 .is_some()
             {
                 return;
@@ -484,7 +463,7 @@ impl ActiveEdge {
             );
 
             let line = before_segment.baseline.orient(winding);
-            match self.process_line_segment(&line, builder, built_object, tile_y) {
+            match self.process_line_segment(&line, built_object, tile_y) {
                 Some(ref lower_part) if split_t == 1.0 => {
                     self.segment = Segment::line(&lower_part);
                     return;
@@ -505,23 +484,22 @@ impl ActiveEdge {
     fn process_line_segment(
         &mut self,
         line_segment: &LineSegment2F,
-        builder: &SceneBuilder,
         built_object: &mut BuiltObject,
         tile_y: i32,
     ) -> Option<LineSegment2F> {
-        let tile_bottom = ((i32::from(tile_y) + 1) * TILE_HEIGHT as i32) as f32;
+        let tile_bottom = ((tile_y + 1) * TILE_HEIGHT as i32) as f32;
         debug!(
             "process_line_segment({:?}, tile_y={}) tile_bottom={}",
             line_segment, tile_y, tile_bottom
         );
 
         if line_segment.max_y() <= tile_bottom {
-            built_object.generate_fill_primitives_for_line(builder, *line_segment, tile_y);
+            built_object.generate_fill_primitives_for_line(*line_segment, tile_y);
             return None;
         }
 
         let (upper_part, lower_part) = line_segment.split_at_y(tile_bottom);
-        built_object.generate_fill_primitives_for_line(builder, upper_part, tile_y);
+        built_object.generate_fill_primitives_for_line(upper_part, tile_y);
         self.crossing = lower_part.upper_point();
         Some(lower_part)
     }
@@ -533,23 +511,82 @@ impl PartialOrd<ActiveEdge> for ActiveEdge {
     }
 }
 
+impl BuiltObject {
+    /// Turns the generated tiles, moved by the given number of tiles, into alpha tiles, and solid
+    /// tiles in the Z-buffer, to be drawn as the given path. Clip paths are tiled but never
+    /// packed.
+    ///
+    /// Each alpha tile comes with its alpha tile index local to the object, to be filled in once
+    /// the object's alpha tiles have been given room in the mask framebuffer.
+    pub(crate) fn pack_and_cull(&self,
+                                tile_offset: Vector2I,
+                                z_buffer: &ZBuffer,
+                                object_index: u32,
+                                path_object: &PathObject,
+                                object_is_opaque: bool,
+                                clip_tiles: Option<&DenseTileMap<ClipTile>>)
+                                -> Vec<(AlphaTileBatchPrimitive, u32)> {
+        let mut alpha_tiles = vec![];
+        for (tile_index, tile) in self.tiles.data.iter().enumerate() {
+            let tile_coords = self.local_tile_index_to_coords(tile_index as u32) + tile_offset;
+
+            let clip_tile_index = match ClipTile::at(clip_tiles, tile_coords) {
+                // Tiles outside the clip path are always skipped.
+                ClipTile::Empty => continue,
+                ClipTile::Full => !0,
+                ClipTile::Mask(clip_tile_index) => clip_tile_index,
+            };
+
+            if tile.is_solid() {
+                // Blank tiles are always skipped.
+                if tile.backdrop == 0 {
+                    continue;
+                }
+
+                // If this is a solid tile, poke it into the Z-buffer and stop here.
+                if object_is_opaque && clip_tile_index == !0 {
+                    z_buffer.update(tile_coords, object_index);
+                    continue;
+                }
+            }
+
+            let alpha_tile = AlphaTileBatchPrimitive::new(
+                tile_coords,
+                tile.backdrop,
+                object_index,
+                !0,
+                path_object.paint(),
+                path_object.fill_rule(),
+                clip_tile_index,
+            );
+
+            alpha_tiles.push((alpha_tile, tile.alpha_tile_index));
+        }
+        alpha_tiles
+    }
+}
+
 impl AlphaTileBatchPrimitive {
     #[inline]
     fn new(tile_coords: Vector2I,
            backdrop: i8,
-           object_index: u16,
+           object_index: u32,
            tile_index: u16,
-           origin_uv: Vector2I)
+           paint_id: PaintId,
+           fill_rule: FillRule,
+           clip_tile_index: u16)
            -> AlphaTileBatchPrimitive {
         AlphaTileBatchPrimitive {
             tile_x_lo: (tile_coords.x() & 0xff) as u8,
             tile_y_lo: (tile_coords.y() & 0xff) as u8,
             tile_hi: (((tile_coords.x() >> 8) & 0x0f) | ((tile_coords.y() >> 4) & 0xf0)) as u8,
             backdrop,
-            object_index,
             tile_index,
-            origin_u: origin_uv.x() as u16,
-            origin_v: origin_uv.y() as u16,
+            clip_tile_index,
+            paint_id: paint_id.0,
+            object_index,
+            fill_rule: encode_fill_rule(fill_rule),
+            pad: [0; 3],
         }
     }
 
@@ -573,3 +610,81 @@ impl TileObjectPrimitive {
     #[inline]
     pub fn is_solid(&self) -> bool { self.alpha_tile_index == !0 }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::gpu_data::{AlphaTileBatchPrimitive, RenderCommand, SolidTileBatchPrimitive};
+    use crate::options::RenderOptions;
+    use crate::paint::Paint;
+    use crate::scene::{PathObject, Scene};
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::vector::Vector2F;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use std::sync::{Arc, Mutex};
+
+    type BuiltTiles = (Vec<SolidTileBatchPrimitive>, Vec<AlphaTileBatchPrimitive>);
+
+    // Builds a path made of two nested squares with the same orientation, so that the winding
+    // number is 2 inside the inner one.
+    fn build_nested_squares(fill_rule: FillRule) -> BuiltTiles {
+        let mut outline = Outline::new();
+        for &(origin, size) in &[(8.0, 112.0), (40.0, 48.0)] {
+            let rect = RectF::new(Vector2F::splat(origin), Vector2F::splat(size));
+            let mut contour = Contour::new();
+            contour.push_endpoint(rect.origin());
+            contour.push_endpoint(rect.upper_right());
+            contour.push_endpoint(rect.lower_right());
+            contour.push_endpoint(rect.lower_left());
+            contour.close();
+            outline.push_contour(contour);
+        }
+
+        let view_box = RectF::new(Vector2F::default(), Vector2F::splat(128.0));
+        let mut scene = Scene::new();
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let mut path = PathObject::new(outline, paint, String::new());
+        path.set_fill_rule(fill_rule);
+        scene.push_path(path);
+        scene.set_view_box(view_box);
+        scene.set_bounds(view_box);
+
+        let tiles = Arc::new(Mutex::new((vec![], vec![])));
+        let listener_tiles = tiles.clone();
+        let listener = move |command: RenderCommand| {
+            let mut tiles = listener_tiles.lock().unwrap();
+            match command {
+                RenderCommand::SolidTile(solid_tiles) => tiles.0.extend(solid_tiles),
+                RenderCommand::AlphaTile(alpha_tiles, _) => tiles.1.extend(alpha_tiles),
+                _ => {}
+            }
+        };
+        scene.build(RenderOptions::default(), Box::new(listener), &SequentialExecutor);
+
+        let tiles = tiles.lock().unwrap();
+        (tiles.0.clone(), tiles.1.clone())
+    }
+
+    fn has_solid_tile(solid_tiles: &[SolidTileBatchPrimitive], x: i16, y: i16) -> bool {
+        solid_tiles.iter().any(|tile| (tile.tile_x, tile.tile_y) == (x, y))
+    }
+
+    #[test]
+    fn test_fill_rules_of_overlapping_contours() {
+        // The tiles from (3, 3) to (4, 4) lie wholly inside the inner square, and the tile at
+        // (1, 1) lies wholly between the two squares.
+        let (solid_tiles, alpha_tiles) = build_nested_squares(FillRule::Winding);
+        assert!(has_solid_tile(&solid_tiles, 1, 1));
+        assert!(has_solid_tile(&solid_tiles, 3, 3) && has_solid_tile(&solid_tiles, 4, 4));
+        assert!(alpha_tiles.iter().all(|tile| tile.fill_rule == 0));
+
+        // Under the even-odd rule, the inner square is a hole.
+        let (solid_tiles, alpha_tiles) = build_nested_squares(FillRule::EvenOdd);
+        assert!(has_solid_tile(&solid_tiles, 1, 1));
+        assert!(!has_solid_tile(&solid_tiles, 3, 3) && !has_solid_tile(&solid_tiles, 4, 4));
+        assert!(!alpha_tiles.is_empty());
+        assert!(alpha_tiles.iter().all(|tile| tile.fill_rule == 1));
+    }
+}
@@ -11,7 +11,7 @@
 //! Software occlusion culling.
 
 use crate::gpu_data::SolidTileBatchPrimitive;
-use crate::paint;
+use crate::paint::PaintId;
 use crate::scene::PathObject;
 use crate::tile_map::DenseTileMap;
 use crate::tiles;
@@ -38,10 +38,10 @@ impl ZBuffer {
         existing_depth < object_index as usize + 1
     }
 
-    pub fn update(&self, coords: Vector2I, object_index: u16) {
+    pub fn update(&self, coords: Vector2I, object_index: u32) {
         let tile_index = self.buffer.coords_to_index_unchecked(coords);
         let mut old_depth = self.buffer.data[tile_index].load(AtomicOrdering::SeqCst);
-        let new_depth = (object_index + 1) as usize;
+        let new_depth = object_index as usize + 1;
         while old_depth < new_depth {
             let prev_depth = self.buffer.data[tile_index].compare_and_swap(
                 old_depth,
@@ -71,11 +71,11 @@ impl ZBuffer {
                 continue;
             }
 
-            let origin_uv = paint::paint_id_to_tex_coords(paths[object_index as usize].paint());
+            let paint_id = paths[object_index as usize].paint();
 
             solid_tiles.push(SolidTileBatchPrimitive::new(tile_coords + self.buffer.rect.origin(),
-                                                          object_index as u16,
-                                                          origin_uv));
+                                                          object_index,
+                                                          paint_id));
         }
 
         solid_tiles
@@ -83,14 +83,13 @@ impl ZBuffer {
 }
 
 impl SolidTileBatchPrimitive {
-    fn new(tile_coords: Vector2I, object_index: u16, origin_uv: Vector2I)
+    fn new(tile_coords: Vector2I, object_index: u32, paint_id: PaintId)
            -> SolidTileBatchPrimitive {
         SolidTileBatchPrimitive {
             tile_x: tile_coords.x() as i16,
             tile_y: tile_coords.y() as i16,
             object_index: object_index,
-            origin_u: origin_uv.x() as u16,
-            origin_v: origin_uv.y() as u16,
+            paint_id: paint_id.0,
         }
     }
 }
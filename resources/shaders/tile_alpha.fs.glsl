@@ -13,14 +13,37 @@
 precision highp float;
 
 uniform sampler2D uStencilTexture;
+uniform sampler2D uClipMask;
 
 in vec2 vTexCoord;
+in vec2 vClipTexCoord;
 in float vBackdrop;
-in vec4 vColor;
+in float vFillRule;
+
+{{include_tile_paint}}
+{{include_tile_blend}}
 
 out vec4 oFragColor;
 
 void main() {
-    float coverage = abs(texture(uStencilTexture, vTexCoord).r + vBackdrop);
-    oFragColor = vec4(vColor.rgb, vColor.a * coverage);
+    float winding = texture(uStencilTexture, vTexCoord).r + vBackdrop;
+
+    // 0 = nonzero winding, 1 = even-odd.
+    float coverage;
+    if (vFillRule < 0.5)
+        coverage = abs(winding);
+    else
+        coverage = 1.0 - abs(1.0 - mod(winding, 2.0));
+
+    // A negative clip texture coordinate means that this tile is unclipped.
+    if (vClipTexCoord.x >= 0.0)
+        coverage *= texture(uClipMask, vClipTexCoord).r;
+
+    vec4 color = getPaintColor();
+
+    // Source-over is left to fixed-function blending.
+    if (uBlendMode == 0)
+        oFragColor = vec4(color.rgb, color.a * coverage);
+    else
+        oFragColor = blendColors(vec4(color.rgb * color.a, color.a), coverage);
 }
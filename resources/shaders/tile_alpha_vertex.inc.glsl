@@ -17,12 +17,20 @@ in vec2 aTessCoord;
 in uvec3 aTileOrigin;
 in int aBackdrop;
 in uint aTileIndex;
+in uint aFillRule;
+in uint aClipTileIndex;
 
 out vec2 vTexCoord;
+out vec2 vClipTexCoord;
 out float vBackdrop;
+out float vFillRule;
 out vec4 vColor;
+out vec4 vGradient;
+out vec2 vGradientPosition;
+out vec4 vGradientLine;
+out vec2 vGradientRadii;
 
-vec4 getColor();
+void computePaintVaryings(vec2 pixelPosition);
 
 vec2 computeTileOffset(uint tileIndex, float stencilTextureWidth) {
     uint tilesPerRow = uint(stencilTextureWidth / uTileSize.x);
@@ -38,8 +46,19 @@ void computeVaryings() {
     vec2 maskTexCoord = maskTexCoordOrigin + aTessCoord * uTileSize;
 
     vTexCoord = maskTexCoord / uStencilTextureSize;
+
+    // The clip mask has the same size and tile layout as the stencil texture.
+    if (aClipTileIndex == 0xffffu) {
+        vClipTexCoord = vec2(-1.0);
+    } else {
+        vec2 clipTexCoord = computeTileOffset(aClipTileIndex, uStencilTextureSize.x) +
+            aTessCoord * uTileSize;
+        vClipTexCoord = clipTexCoord / uStencilTextureSize;
+    }
+
     vBackdrop = float(aBackdrop);
-    vColor = getColor();
+    vFillRule = float(aFillRule);
+    computePaintVaryings(pixelPosition);
     gl_Position = vec4(position, 0.0, 1.0);
 }
 
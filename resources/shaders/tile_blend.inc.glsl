@@ -0,0 +1,208 @@
+// pathfinder/resources/shaders/tile_blend.inc.glsl
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Combines a color with a copy of the destination, for the blend modes that fixed-function
+// blending can't express. These follow the W3C Compositing and Blending specification.
+//
+// The mode numbers match `BlendMode::to_shader_index()`.
+
+#define BLEND_MODE_SOURCE_IN        1
+#define BLEND_MODE_SOURCE_OUT       2
+#define BLEND_MODE_SOURCE_ATOP      3
+#define BLEND_MODE_DESTINATION_OVER 4
+#define BLEND_MODE_DESTINATION_IN   5
+#define BLEND_MODE_DESTINATION_OUT  6
+#define BLEND_MODE_DESTINATION_ATOP 7
+#define BLEND_MODE_XOR              8
+#define BLEND_MODE_LIGHTER          9
+#define BLEND_MODE_COPY             10
+#define BLEND_MODE_MULTIPLY         11
+#define BLEND_MODE_SCREEN           12
+#define BLEND_MODE_OVERLAY          13
+#define BLEND_MODE_DARKEN           14
+#define BLEND_MODE_LIGHTEN          15
+#define BLEND_MODE_COLOR_DODGE      16
+#define BLEND_MODE_COLOR_BURN       17
+#define BLEND_MODE_HARD_LIGHT       18
+#define BLEND_MODE_SOFT_LIGHT       19
+#define BLEND_MODE_DIFFERENCE       20
+#define BLEND_MODE_EXCLUSION        21
+#define BLEND_MODE_HUE              22
+#define BLEND_MODE_SATURATION       23
+#define BLEND_MODE_COLOR            24
+#define BLEND_MODE_LUMINOSITY       25
+
+uniform int uBlendMode;
+uniform sampler2D uDest;
+// The origin and size, in window coordinates, of the area copied into `uDest`.
+uniform vec4 uDestRect;
+
+// The factors that the source and destination are multiplied by.
+vec2 porterDuffFactors(int mode, float srcAlpha, float destAlpha) {
+    if (mode == BLEND_MODE_SOURCE_IN)
+        return vec2(destAlpha, 0.0);
+    if (mode == BLEND_MODE_SOURCE_OUT)
+        return vec2(1.0 - destAlpha, 0.0);
+    if (mode == BLEND_MODE_SOURCE_ATOP)
+        return vec2(destAlpha, 1.0 - srcAlpha);
+    if (mode == BLEND_MODE_DESTINATION_OVER)
+        return vec2(1.0 - destAlpha, 1.0);
+    if (mode == BLEND_MODE_DESTINATION_IN)
+        return vec2(0.0, srcAlpha);
+    if (mode == BLEND_MODE_DESTINATION_OUT)
+        return vec2(0.0, 1.0 - srcAlpha);
+    if (mode == BLEND_MODE_DESTINATION_ATOP)
+        return vec2(1.0 - destAlpha, srcAlpha);
+    if (mode == BLEND_MODE_XOR)
+        return vec2(1.0 - destAlpha, 1.0 - srcAlpha);
+    if (mode == BLEND_MODE_LIGHTER)
+        return vec2(1.0, 1.0);
+    if (mode == BLEND_MODE_COPY)
+        return vec2(1.0, 0.0);
+
+    // The separable blend modes composite with source-over.
+    return vec2(1.0, 1.0 - srcAlpha);
+}
+
+vec3 hardLight(vec3 src, vec3 dest) {
+    vec3 multiply = 2.0 * src * dest;
+    vec3 screen = dest + (2.0 * src - 1.0) - dest * (2.0 * src - 1.0);
+    return mix(multiply, screen, step(vec3(0.5), src));
+}
+
+vec3 colorDodge(vec3 src, vec3 dest) {
+    vec3 result;
+    for (int i = 0; i < 3; i++) {
+        if (dest[i] == 0.0)
+            result[i] = 0.0;
+        else if (src[i] >= 1.0)
+            result[i] = 1.0;
+        else
+            result[i] = min(1.0, dest[i] / (1.0 - src[i]));
+    }
+    return result;
+}
+
+vec3 colorBurn(vec3 src, vec3 dest) {
+    vec3 result;
+    for (int i = 0; i < 3; i++) {
+        if (dest[i] >= 1.0)
+            result[i] = 1.0;
+        else if (src[i] == 0.0)
+            result[i] = 0.0;
+        else
+            result[i] = 1.0 - min(1.0, (1.0 - dest[i]) / src[i]);
+    }
+    return result;
+}
+
+vec3 softLight(vec3 src, vec3 dest) {
+    vec3 result;
+    for (int i = 0; i < 3; i++) {
+        if (src[i] <= 0.5) {
+            result[i] = dest[i] - (1.0 - 2.0 * src[i]) * dest[i] * (1.0 - dest[i]);
+        } else {
+            float d;
+            if (dest[i] <= 0.25)
+                d = ((16.0 * dest[i] - 12.0) * dest[i] + 4.0) * dest[i];
+            else
+                d = sqrt(dest[i]);
+            result[i] = dest[i] + (2.0 * src[i] - 1.0) * (d - dest[i]);
+        }
+    }
+    return result;
+}
+
+// The blend function B(Cb, Cs) of the separable blend modes, on unpremultiplied colors.
+vec3 blendSeparable(int mode, vec3 src, vec3 dest) {
+    if (mode == BLEND_MODE_MULTIPLY)
+        return src * dest;
+    if (mode == BLEND_MODE_SCREEN)
+        return src + dest - src * dest;
+    if (mode == BLEND_MODE_OVERLAY)
+        return hardLight(dest, src);
+    if (mode == BLEND_MODE_DARKEN)
+        return min(src, dest);
+    if (mode == BLEND_MODE_LIGHTEN)
+        return max(src, dest);
+    if (mode == BLEND_MODE_COLOR_DODGE)
+        return colorDodge(src, dest);
+    if (mode == BLEND_MODE_COLOR_BURN)
+        return colorBurn(src, dest);
+    if (mode == BLEND_MODE_HARD_LIGHT)
+        return hardLight(src, dest);
+    if (mode == BLEND_MODE_SOFT_LIGHT)
+        return softLight(src, dest);
+    if (mode == BLEND_MODE_DIFFERENCE)
+        return abs(src - dest);
+    return src + dest - 2.0 * src * dest;
+}
+
+float lum(vec3 color) {
+    return dot(color, vec3(0.3, 0.59, 0.11));
+}
+
+float sat(vec3 color) {
+    return max(color.r, max(color.g, color.b)) - min(color.r, min(color.g, color.b));
+}
+
+vec3 setLum(vec3 color, float newLum) {
+    color += newLum - lum(color);
+
+    // Bring the channels back into range while keeping the luminosity.
+    float l = lum(color);
+    float n = min(color.r, min(color.g, color.b)), x = max(color.r, max(color.g, color.b));
+    vec3 result = color;
+    if (n < 0.0)
+        result = l + (result - l) * l / (l - n);
+    if (x > 1.0)
+        result = l + (result - l) * (1.0 - l) / (x - l);
+    return result;
+}
+
+vec3 setSat(vec3 color, float newSat) {
+    float n = min(color.r, min(color.g, color.b)), x = max(color.r, max(color.g, color.b));
+    if (x <= n)
+        return vec3(0.0);
+    return (color - n) * newSat / (x - n);
+}
+
+// The blend function B(Cb, Cs) of the non-separable blend modes, on unpremultiplied colors.
+vec3 blendNonSeparable(int mode, vec3 src, vec3 dest) {
+    if (mode == BLEND_MODE_HUE)
+        return setLum(setSat(src, sat(dest)), lum(dest));
+    if (mode == BLEND_MODE_SATURATION)
+        return setLum(setSat(dest, sat(src)), lum(dest));
+    if (mode == BLEND_MODE_COLOR)
+        return setLum(src, lum(dest));
+    return setLum(dest, lum(src));
+}
+
+// Blends the premultiplied color `src` into the destination, replacing only `coverage` of it.
+// The result must be drawn with blending off.
+vec4 blendColors(vec4 src, float coverage) {
+    vec2 destTexCoord = (gl_FragCoord.xy - uDestRect.xy) / uDestRect.zw;
+    vec4 dest = texture(uDest, destTexCoord);
+
+    vec3 srcColor = src.rgb;
+    if (uBlendMode >= BLEND_MODE_MULTIPLY && src.a > 0.0 && dest.a > 0.0) {
+        // Mix the blended color in proportion to the destination's coverage.
+        vec3 cs = src.rgb / src.a, cd = dest.rgb / dest.a;
+        vec3 csClamped = min(cs, vec3(1.0)), cdClamped = min(cd, vec3(1.0));
+        vec3 blended = uBlendMode >= BLEND_MODE_HUE ?
+            blendNonSeparable(uBlendMode, csClamped, cdClamped) :
+            blendSeparable(uBlendMode, csClamped, cdClamped);
+        srcColor = src.a * ((1.0 - dest.a) * cs + dest.a * blended);
+    }
+
+    vec2 factors = porterDuffFactors(uBlendMode, src.a, dest.a);
+    vec4 result = clamp(vec4(srcColor, src.a) * factors.x + dest * factors.y, 0.0, 1.0);
+    return mix(dest, result, clamp(coverage, 0.0, 1.0));
+}
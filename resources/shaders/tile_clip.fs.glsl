@@ -0,0 +1,35 @@
+#version {{version}}
+
+// pathfinder/resources/shaders/tile_clip.fs.glsl
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+precision highp float;
+
+uniform sampler2D uStencilTexture;
+
+in vec2 vTexCoord;
+in float vBackdrop;
+in float vFillRule;
+
+out vec4 oFragColor;
+
+void main() {
+    float winding = texture(uStencilTexture, vTexCoord).r + vBackdrop;
+
+    // 0 = nonzero winding, 1 = even-odd.
+    float coverage;
+    if (vFillRule < 0.5)
+        coverage = min(abs(winding), 1.0);
+    else
+        coverage = 1.0 - abs(1.0 - mod(winding, 2.0));
+
+    // This is multiplied into the clip mask by the blend state.
+    oFragColor = vec4(coverage);
+}
@@ -0,0 +1,39 @@
+#version {{version}}
+
+// pathfinder/resources/shaders/tile_composite.fs.glsl
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+precision highp float;
+
+uniform sampler2D uLayer;
+uniform sampler2D uClipMask;
+
+in vec2 vTexCoord;
+in vec2 vClipTexCoord;
+in float vOpacity;
+
+{{include_tile_blend}}
+
+out vec4 oFragColor;
+
+void main() {
+    float alpha = vOpacity;
+
+    // A negative clip texture coordinate means that this tile is unclipped.
+    if (vClipTexCoord.x >= 0.0)
+        alpha *= texture(uClipMask, vClipTexCoord).r;
+
+    // The layer is premultiplied.
+    vec4 color = texture(uLayer, vTexCoord);
+    if (uBlendMode == 0)
+        oFragColor = color * alpha;
+    else
+        oFragColor = blendColors(color, alpha);
+}
@@ -0,0 +1,51 @@
+#version {{version}}
+
+// pathfinder/resources/shaders/tile_composite.vs.glsl
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+precision highp float;
+
+uniform vec2 uFramebufferSize;
+uniform vec2 uTileSize;
+uniform vec2 uClipMaskSize;
+
+in vec2 aTessCoord;
+in vec2 aTileOrigin;
+in uint aClipTileIndex;
+in float aOpacity;
+
+out vec2 vTexCoord;
+out vec2 vClipTexCoord;
+out float vOpacity;
+
+vec2 computeTileOffset(uint tileIndex, float stencilTextureWidth) {
+    uint tilesPerRow = uint(stencilTextureWidth / uTileSize.x);
+    uvec2 tileOffset = uvec2(tileIndex % tilesPerRow, tileIndex / tilesPerRow);
+    return vec2(tileOffset) * uTileSize;
+}
+
+void main() {
+    vec2 pixelPosition = (aTileOrigin + aTessCoord) * uTileSize;
+    vec2 position = (pixelPosition / uFramebufferSize * 2.0 - 1.0) * vec2(1.0, -1.0);
+
+    // The layer was drawn with the same flip, so its first row is at the top.
+    vTexCoord = vec2(pixelPosition.x, uFramebufferSize.y - pixelPosition.y) / uFramebufferSize;
+
+    if (aClipTileIndex == 0xffffu) {
+        vClipTexCoord = vec2(-1.0);
+    } else {
+        vec2 clipTexCoord = computeTileOffset(aClipTileIndex, uClipMaskSize.x) +
+            aTessCoord * uTileSize;
+        vClipTexCoord = clipTexCoord / uClipMaskSize;
+    }
+
+    vOpacity = aOpacity;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -10,6 +10,10 @@
 
 uniform vec4 uColor;
 
-vec4 getColor() {
-    return uColor;
+void computePaintVaryings(vec2 pixelPosition) {
+    vColor = uColor;
+    vGradient = vec4(0.0);
+    vGradientPosition = vec2(0.0);
+    vGradientLine = vec4(0.0);
+    vGradientRadii = vec2(0.0);
 }
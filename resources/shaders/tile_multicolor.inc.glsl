@@ -9,10 +9,27 @@
 // except according to those terms.
 
 uniform sampler2D uPaintTexture;
-uniform vec2 uPaintTextureSize;
+uniform sampler2D uPaintMetadata;
 
-in vec2 aColorTexCoord;
+in uint aPaintId;
 
-vec4 getColor() {
-    return texture(uPaintTexture, aColorTexCoord);
+// Each paint is described by four consecutive texels of the metadata texture. See
+// `PaintMetadata` in `gpu_data.rs`.
+vec4 fetchPaintMetadata(int index) {
+    int texelIndex = int(aPaintId) * 4 + index;
+    int width = textureSize(uPaintMetadata, 0).x;
+    return texelFetch(uPaintMetadata, ivec2(texelIndex % width, texelIndex / width), 0);
+}
+
+void computePaintVaryings(vec2 pixelPosition) {
+    vec4 info = fetchPaintMetadata(0);
+    vec4 transform = fetchPaintMetadata(1);
+    vec4 translationAndRadii = fetchPaintMetadata(2);
+
+    vColor = texture(uPaintTexture, info.xy);
+    vGradient = info;
+    vGradientPosition = vec2(dot(transform.xy, pixelPosition), dot(transform.zw, pixelPosition)) +
+        translationAndRadii.xy;
+    vGradientLine = fetchPaintMetadata(3);
+    vGradientRadii = translationAndRadii.zw;
 }
@@ -0,0 +1,124 @@
+// pathfinder/resources/shaders/tile_paint.inc.glsl
+//
+// Copyright © 2019 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Computes the color of a solid color, gradient, or pattern paint at each fragment.
+
+#define EPSILON     0.0001
+#define TWO_PI      6.28318530718
+
+// The number of texels in each gradient ramp. Must match `GRADIENT_RAMP_LENGTH` in `paint.rs`.
+#define GRADIENT_RAMP_LENGTH    256.0
+
+uniform sampler2D uPaintTexture;
+uniform sampler2D uImageAtlas;
+
+in vec4 vColor;
+in vec4 vGradient;
+in vec2 vGradientPosition;
+in vec4 vGradientLine;
+in vec2 vGradientRadii;
+
+// Finds the largest `t` for which the position lies on the circle interpolated between the start
+// and end circles with a nonnegative radius. Returns false if there is no such `t`.
+bool computeRadialGradientT(vec2 position,
+                            vec2 startCenter,
+                            vec2 endCenter,
+                            float startRadius,
+                            float endRadius,
+                            out float t) {
+    vec2 centerDelta = endCenter - startCenter, positionDelta = position - startCenter;
+    float radiusDelta = endRadius - startRadius;
+
+    float a = dot(centerDelta, centerDelta) - radiusDelta * radiusDelta;
+    float b = dot(positionDelta, centerDelta) + startRadius * radiusDelta;
+    float c = dot(positionDelta, positionDelta) - startRadius * startRadius;
+
+    if (abs(a) < EPSILON) {
+        if (b == 0.0)
+            return false;
+        t = c / (2.0 * b);
+        return startRadius + t * radiusDelta >= 0.0;
+    }
+
+    float discriminant = b * b - a * c;
+    if (discriminant < 0.0)
+        return false;
+
+    float root = sqrt(discriminant);
+    vec2 ts = vec2((b + root) / a, (b - root) / a);
+    float tMax = max(ts.x, ts.y), tMin = min(ts.x, ts.y);
+    if (startRadius + tMax * radiusDelta >= 0.0) {
+        t = tMax;
+        return true;
+    }
+    t = tMin;
+    return startRadius + tMin * radiusDelta >= 0.0;
+}
+
+// Samples the image of a pattern, which occupies the rectangle `vGradientLine` of the atlas.
+vec4 getPatternColor(vec2 position, float repeat) {
+    vec2 imageOrigin = vGradientLine.xy, imageSize = vGradientLine.zw;
+
+    // 1 = repeat horizontally, 2 = repeat vertically, 3 = both.
+    bvec2 repeats = bvec2(mod(repeat, 2.0) > 0.5, repeat > 1.5);
+    if (repeats.x)
+        position.x = mod(position.x, imageSize.x);
+    else if (position.x < 0.0 || position.x > imageSize.x)
+        return vec4(0.0);
+    if (repeats.y)
+        position.y = mod(position.y, imageSize.y);
+    else if (position.y < 0.0 || position.y > imageSize.y)
+        return vec4(0.0);
+
+    // Keep bilinear filtering from reaching into neighboring images.
+    position = clamp(position, vec2(0.5), imageSize - vec2(0.5));
+    return texture(uImageAtlas, (imageOrigin + position) / vec2(textureSize(uImageAtlas, 0)));
+}
+
+vec4 getPaintColor() {
+    // These are small integers, but they are interpolated, so compare with some slop.
+    float kind = vGradient.z, spread = vGradient.w;
+    if (kind < 0.5)
+        return vColor;
+    if (kind > 2.5 && kind < 3.5)
+        return getPatternColor(vGradientPosition, floor(spread + 0.5)) *
+            vec4(1.0, 1.0, 1.0, vGradientRadii.x);
+
+    vec2 from = vGradientLine.xy, to = vGradientLine.zw;
+    float t;
+    if (kind < 1.5) {
+        vec2 vector = to - from;
+        t = dot(vGradientPosition - from, vector) / dot(vector, vector);
+    } else if (kind > 3.5) {
+        // Conic gradients have the center in `from` and the start angle in `to.x`.
+        vec2 vector = vGradientPosition - from;
+        t = fract((atan(vector.y, vector.x) - to.x) / TWO_PI);
+    } else if (!computeRadialGradientT(vGradientPosition,
+                                       from,
+                                       to,
+                                       vGradientRadii.x,
+                                       vGradientRadii.y,
+                                       t)) {
+        return vec4(0.0);
+    }
+
+    // 0 = pad, 1 = reflect, 2 = repeat.
+    if (spread < 0.5)
+        t = clamp(t, 0.0, 1.0);
+    else if (spread < 1.5)
+        t = 1.0 - abs(mod(t, 2.0) - 1.0);
+    else
+        t = fract(t);
+
+    // Gradient ramps are packed several to a row of the paint texture.
+    float textureWidth = float(textureSize(uPaintTexture, 0).x);
+    return texture(uPaintTexture,
+                   vGradient.xy + vec2(t * (GRADIENT_RAMP_LENGTH - 1.0) / textureWidth, 0.0));
+}
@@ -12,10 +12,10 @@
 
 precision highp float;
 
-in vec4 vColor;
+{{include_tile_paint}}
 
 out vec4 oFragColor;
 
 void main() {
-    oFragColor = vColor;
+    oFragColor = getPaintColor();
 }
@@ -16,14 +16,17 @@ in vec2 aTessCoord;
 in vec2 aTileOrigin;
 
 out vec4 vColor;
+out vec4 vGradient;
+out vec2 vGradientPosition;
+out vec4 vGradientLine;
+out vec2 vGradientRadii;
 
-vec4 getColor();
+void computePaintVaryings(vec2 pixelPosition);
 
 void computeVaryings() {
     vec2 pixelPosition = (aTileOrigin + aTessCoord) * uTileSize + uViewBoxOrigin;
     vec2 position = (pixelPosition / uFramebufferSize * 2.0 - 1.0) * vec2(1.0, -1.0);
 
-    vColor = getColor();
-    //vColor = vec4(1.0, 0.0, 0.0, 1.0);
+    computePaintVaryings(pixelPosition);
     gl_Position = vec4(position, 0.0, 1.0);
 }
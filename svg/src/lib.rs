@@ -18,14 +18,16 @@ use pathfinder_geometry::basic::vector::Vector2F;
 use pathfinder_geometry::basic::rect::RectF;
 use pathfinder_geometry::basic::transform2d::{Transform2DF, Transform2DFPathIter};
 use pathfinder_geometry::color::ColorU;
+use pathfinder_geometry::fill::FillRule;
 use pathfinder_geometry::outline::Outline;
 use pathfinder_geometry::segment::{Segment, SegmentFlags};
 use pathfinder_geometry::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
 use pathfinder_renderer::paint::Paint;
-use pathfinder_renderer::scene::{PathObject, Scene};
+use pathfinder_renderer::scene::{Layer, PathObject, Scene};
 use std::fmt::{Display, Formatter, Result as FormatResult};
 use std::mem;
-use usvg::{Color as SvgColor, LineCap as UsvgLineCap, LineJoin as UsvgLineJoin, Node, NodeExt};
+use usvg::{Color as SvgColor, FillRule as UsvgFillRule, LineCap as UsvgLineCap};
+use usvg::{LineJoin as UsvgLineJoin, Node, NodeExt};
 use usvg::{NodeKind, Opacity, Paint as UsvgPaint, PathSegment as UsvgPathSegment};
 use usvg::{Rect as UsvgRect, Transform as UsvgTransform, Tree, Visibility};
 
@@ -104,14 +106,21 @@ impl BuiltSVG {
                     self.result_flags
                         .insert(BuildResultFlags::UNSUPPORTED_MASK_ATTR);
                 }
-                if group.opacity.is_some() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_OPACITY_ATTR);
+
+                // Group opacity applies to the group as a whole, so render it offscreen.
+                if let Some(opacity) = group.opacity {
+                    let mut layer = Layer::new(format!("Group({})", node.id()));
+                    layer.set_opacity(opacity.value() as f32);
+                    self.scene.push_layer(layer);
                 }
 
                 for kid in node.children() {
                     self.process_node(&kid, &transform)
                 }
+
+                if group.opacity.is_some() {
+                    self.scene.pop_layer();
+                }
             }
             NodeKind::Path(ref path) if path.visibility == Visibility::Visible => {
                 if let Some(ref fill) = path.fill {
@@ -126,7 +135,9 @@ impl BuiltSVG {
                     let outline = Outline::from_segments(path);
 
                     let name = format!("Fill({})", node.id());
-                    self.scene.push_path(PathObject::new(outline, style, name));
+                    let mut path_object = PathObject::new(outline, style, name);
+                    path_object.set_fill_rule(FillRule::from_usvg_fill_rule(fill.rule));
+                    self.scene.push_path(path_object);
                 }
 
                 if let Some(ref stroke) = path.stroke {
@@ -253,16 +264,14 @@ impl PaintExt for Paint {
     #[inline]
     fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity, result_flags: &mut BuildResultFlags)
                       -> Paint {
-        Paint {
-            color: match *svg_paint {
-                UsvgPaint::Color(color) => ColorU::from_svg_color(color, opacity),
-                UsvgPaint::Link(_) => {
-                    // TODO(pcwalton)
-                    result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
-                    ColorU::black()
-                }
-            },
-        }
+        Paint::Color(match *svg_paint {
+            UsvgPaint::Color(color) => ColorU::from_svg_color(color, opacity),
+            UsvgPaint::Link(_) => {
+                // TODO(pcwalton)
+                result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
+                ColorU::black()
+            }
+        })
     }
 }
 
@@ -385,6 +394,20 @@ impl ColorUExt for ColorU {
     }
 }
 
+trait FillRuleExt {
+    fn from_usvg_fill_rule(usvg_fill_rule: UsvgFillRule) -> Self;
+}
+
+impl FillRuleExt for FillRule {
+    #[inline]
+    fn from_usvg_fill_rule(usvg_fill_rule: UsvgFillRule) -> FillRule {
+        match usvg_fill_rule {
+            UsvgFillRule::NonZero => FillRule::Winding,
+            UsvgFillRule::EvenOdd => FillRule::EvenOdd,
+        }
+    }
+}
+
 trait LineCapExt {
     fn from_usvg_line_cap(usvg_line_cap: UsvgLineCap) -> Self;
 }
@@ -414,3 +437,64 @@ impl LineJoinExt for LineJoin {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::BuiltSVG;
+    use pathfinder_geometry::basic::rect::RectF;
+    use pathfinder_geometry::basic::vector::Vector2F;
+    use pathfinder_geometry::color::ColorU;
+    use pathfinder_geometry::fill::FillRule;
+    use pathfinder_geometry::outline::{Contour, Outline};
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{Layer, PathObject, Scene};
+    use usvg::{Options as UsvgOptions, Tree};
+
+    fn svg(scene: &Scene) -> Vec<u8> {
+        let mut bytes = vec![];
+        scene.write_svg(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.1, 0.3));
+        contour.push_endpoint(Vector2F::new(48.0, 1.0 / 3.0));
+        contour.push_cubic(Vector2F::new(50.0, 16.0), Vector2F::new(16.0, 50.0),
+                           Vector2F::splat(48.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let paint = scene.push_paint(&Paint::Color(ColorU { r: 12, g: 34, b: 56, a: 78 }));
+        scene.push_path(PathObject::new(outline.clone(), paint, "Fill(a&b)".to_owned()));
+        let mut layer = Layer::new("Group(layer)".to_owned());
+        layer.set_opacity(0.25);
+        scene.push_layer(layer);
+        let paint = scene.push_paint(&Paint::Color(ColorU::black()));
+        let mut path = PathObject::new(outline, paint, "Fill(b)".to_owned());
+        path.set_fill_rule(FillRule::EvenOdd);
+        scene.push_path(path);
+        scene.pop_layer();
+
+        let tree = Tree::from_data(&svg(&scene), &UsvgOptions::default()).unwrap();
+        let built_svg = BuiltSVG::from_tree(tree);
+        assert!(built_svg.result_flags.is_empty());
+
+        let round_tripped = &built_svg.scene;
+        assert_eq!(round_tripped.path_count(), scene.path_count());
+        for path_index in 0..scene.path_count() {
+            let (path, round_tripped_path) = (scene.path(path_index),
+                                              round_tripped.path(path_index));
+            assert_eq!(round_tripped_path.name(), path.name());
+            assert_eq!(round_tripped_path.fill_rule(), path.fill_rule());
+            assert_eq!(format!("{:?}", round_tripped_path.outline()),
+                       format!("{:?}", path.outline()));
+        }
+        assert_eq!(svg(round_tripped), svg(&scene));
+    }
+}